@@ -635,7 +635,7 @@ app.get('/api/tools', async (_req, res) => {
   }
 })
 
-app.listen(port, () => {
+const server = app.listen(port, () => {
   console.log(`🚀 MCP Chat Server running on http://localhost:${port}`)
   console.log(`📡 API endpoints:`)
   console.log(`   POST /api/chat/stream - Send messages to MCP agent (streaming)`) 
@@ -646,4 +646,19 @@ app.listen(port, () => {
     const servers = Object.keys((config as any).mcpServers || {})
     console.log(`🧩 MCP servers configured: ${servers.join(', ') || '(none)'}`)
   } catch {}
-}) 
\ No newline at end of file
+}) 
+
+// The desktop app sends SIGTERM on quit; close MCP sessions so their subprocesses exit too
+async function shutdown(signal: string) {
+  console.log(`[sidecar] ${signal} received; shutting down`)
+  server.close()
+  try {
+    await client.closeAllSessions()
+  } catch (error) {
+    console.error('[sidecar] Failed to close MCP sessions:', error)
+  }
+  process.exit(0)
+}
+
+process.once('SIGTERM', () => void shutdown('SIGTERM'))
+process.once('SIGINT', () => void shutdown('SIGINT'))
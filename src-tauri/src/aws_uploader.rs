@@ -1,57 +1,354 @@
 use anyhow::{anyhow, Context, Result};
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, ClientBuilder};
+use reqwest::{tls, Certificate};
 use serde::{Deserialize, Serialize};
-use std::{fs, io::Read, path::{Path, PathBuf}, thread, time::Duration, sync::mpsc::channel, collections::HashSet, sync::Mutex};
+use std::{fs, io::Read, path::{Path, PathBuf}, thread, time::{Duration, Instant}, sync::mpsc::channel, collections::{BTreeMap, HashMap, HashSet}, sync::Mutex};
+use std::ops::ControlFlow;
+use std::sync::{atomic::{AtomicU8, Ordering}, Arc, OnceLock, RwLock};
 use walkdir::WalkDir;
 use notify::{RecommendedWatcher, RecursiveMode, Watcher, event::EventKind};
 
+use crate::{memory_store, pii_scrubber, sigv4};
+
 // -------- config --------
 
-#[derive(Deserialize, Debug)]
+/// Uploader settings from config.toml. `ARKANGEL_API_URL`, `ARKANGEL_DEVICE_ID`,
+/// `ARKANGEL_WATCH_DIR` (one or more dirs, separated like PATH), `ARKANGEL_SCAN_INTERVAL_SECS`
+/// and `ARKANGEL_CONCURRENCY` override the file's values when set and non-empty; anything
+/// set in neither place falls back to its default.
+#[derive(Deserialize, Debug, Clone)]
 pub struct AwsConfig {
-    pub api_url: String,         // e.g., https://<api-id>.execute-api.us-west-2.amazonaws.com/ingest/new
+    #[serde(default)]
+    pub backend: UploadBackend,
+    #[serde(default)]
+    pub api_url: String,         // e.g., https://<api-id>.execute-api.us-west-2.amazonaws.com/ingest/new (presign only)
+    pub bucket: Option<String>,  // direct_s3 target; for presign, only used in log output
+    pub region: Option<String>,  // direct_s3 only, e.g. "us-west-2"
+    pub s3_endpoint: Option<String>, // direct_s3 only; defaults to https://<bucket>.s3.<region>.amazonaws.com
     pub device_id: String,       // e.g., "dev001"
-    pub watch_dir: String,       // e.g., ".\\memory"
+    pub watch_dir: WatchDirs,    // e.g., ".\\memory" or ["memory", "uploads"]
     pub scan_interval_secs: Option<u64>,
     pub concurrency: Option<usize>,
+    pub debounce_ms: Option<u64>, // how long a file must stay the same size before upload
+    #[serde(default)]
+    pub upload_policy: UploadPolicy,
+    #[serde(default)]
+    pub tls: TlsSettings,
+    #[serde(default)]
+    pub synced: SyncedOutput,
+    #[serde(default)]
+    pub archives: ArchiveSettings,
+    #[serde(default)]
+    pub dry_run: bool,           // log what would be uploaded; no network calls, nothing renamed
+    #[serde(default)]
+    pub recursive: bool,         // also upload from subfolders, keyed by their relative path
+}
+
+/// How files reach the bucket
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UploadBackend {
+    /// Ask the `api_url` ingest endpoint for a presigned PUT URL per file
+    #[default]
+    Presign,
+    /// PUT straight to `bucket` with SigV4, using credentials from the AWS_* env vars
+    DirectS3,
+}
+
+/// `watch_dir` in config.toml: a single directory or a list of them, all synced to the
+/// same bucket
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum WatchDirs {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl WatchDirs {
+    pub fn all(&self) -> &[String] {
+        match self {
+            WatchDirs::One(dir) => std::slice::from_ref(dir),
+            WatchDirs::Many(dirs) => dirs,
+        }
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.all().iter().map(Path::new)
+    }
+
+    /// Add `dir` unless it is already one of the directories
+    pub fn include(&mut self, dir: &Path) {
+        let same = |existing: &str| {
+            let existing = Path::new(existing);
+            existing == dir || existing.canonicalize().ok().zip(dir.canonicalize().ok()).is_some_and(|(a, b)| a == b)
+        };
+        if self.all().iter().any(|existing| same(existing)) {
+            return;
+        }
+        let dir = dir.to_string_lossy().to_string();
+        *self = match std::mem::replace(self, WatchDirs::Many(Vec::new())) {
+            WatchDirs::One(first) => WatchDirs::Many(vec![first, dir]),
+            WatchDirs::Many(mut dirs) => {
+                dirs.push(dir);
+                WatchDirs::Many(dirs)
+            }
+        };
+    }
+
+    fn all_mut(&mut self) -> &mut [String] {
+        match self {
+            WatchDirs::One(dir) => std::slice::from_mut(dir),
+            WatchDirs::Many(dirs) => dirs,
+        }
+    }
+}
+
+/// Guardrail that re-scans files before upload. Files whose scrub report still finds
+/// more PII than allowed are moved to `quarantine/` instead of being uploaded.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct UploadPolicy {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Residual matches allowed per category (default 0)
+    #[serde(default)]
+    pub max_residual_matches: usize,
+    /// Per-category overrides, e.g. `date = 5`
+    #[serde(default)]
+    pub category_limits: HashMap<pii_scrubber::Category, usize>,
+}
+
+impl UploadPolicy {
+    /// Returns the reason a file must be withheld, or None if it may be uploaded
+    fn violation(&self, bytes: &[u8]) -> Option<String> {
+        let report = match std::str::from_utf8(bytes).map_err(|e| e.to_string()).and_then(pii_scrubber::scrub_report_json) {
+            Ok(r) => r,
+            Err(e) => return Some(format!("unreadable conversation: {}", e)),
+        };
+        let tripped: Vec<String> = report
+            .counts
+            .iter()
+            .filter(|(category, n)| **n > *self.category_limits.get(category).unwrap_or(&self.max_residual_matches))
+            .map(|(category, n)| format!("{:?}={}", category, n))
+            .collect();
+        if tripped.is_empty() {
+            None
+        } else {
+            Some(format!("residual PII detected ({})", tripped.join(", ")))
+        }
+    }
+}
+
+/// What happens to a file once it has been uploaded
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncedMode {
+    /// Rename in place: foo.json -> foo.json.synced
+    #[default]
+    Rename,
+    /// Move to the archive folder as foo.json.synced, keeping the watch dir clean
+    Archive,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct SyncedOutput {
+    #[serde(default)]
+    pub mode: SyncedMode,
+    /// Archive folder; relative paths are resolved against the watch dir
+    #[serde(default = "default_archive_dir")]
+    pub archive_dir: String,
+}
+
+fn default_archive_dir() -> String {
+    "synced".to_string()
+}
+
+impl Default for SyncedOutput {
+    fn default() -> Self {
+        Self { mode: SyncedMode::default(), archive_dir: default_archive_dir() }
+    }
+}
+
+/// Zip bundles dropped into the watch dir. Each JSON member is extracted to a temp
+/// dir, scrubbed and uploaded on its own; the archive is marked synced once all succeed.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ArchiveSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Extensions treated as zip archives, without the dot
+    #[serde(default = "default_archive_extensions")]
+    pub extensions: Vec<String>,
+    /// Zip-bomb guards: archives exceeding any limit are quarantined unread
+    #[serde(default = "default_max_archive_entries")]
+    pub max_entries: usize,
+    #[serde(default = "default_max_entry_bytes")]
+    pub max_entry_bytes: u64,
+    #[serde(default = "default_max_total_bytes")]
+    pub max_total_bytes: u64,
+}
+
+fn default_archive_extensions() -> Vec<String> {
+    vec!["zip".to_string()]
+}
+
+fn default_max_archive_entries() -> usize {
+    100
+}
+
+fn default_max_entry_bytes() -> u64 {
+    50 * 1024 * 1024
+}
+
+fn default_max_total_bytes() -> u64 {
+    200 * 1024 * 1024
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            extensions: default_archive_extensions(),
+            max_entries: default_max_archive_entries(),
+            max_entry_bytes: default_max_entry_bytes(),
+            max_total_bytes: default_max_total_bytes(),
+        }
+    }
+}
+
+/// Optional hardening for outbound HTTPS. Leaving everything unset keeps reqwest's defaults.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TlsSettings {
+    /// Lowest protocol version to negotiate. Only "1.2": the native-tls backend can't
+    /// enforce a 1.3 minimum.
+    #[serde(default)]
+    pub min_version: Option<String>,
+    /// PEM certificates that replace the system's trusted roots. The server's chain must
+    /// end at one of them. This is not leaf pinning: any certificate those roots issued
+    /// is accepted.
+    #[serde(default)]
+    pub pinned_certs: Vec<String>,
+}
+
+impl TlsSettings {
+    fn min_tls_version(&self) -> Result<Option<tls::Version>> {
+        match self.min_version.as_deref().map(str::trim) {
+            None => Ok(None),
+            Some("1.2") => Ok(Some(tls::Version::TLS_1_2)),
+            Some("1.3") => Err(anyhow!(
+                "tls min_version \"1.3\" is not supported by the native TLS backend; use \"1.2\""
+            )),
+            Some(other) => Err(anyhow!("unsupported tls min_version {:?} (expected \"1.2\")", other)),
+        }
+    }
+
+    /// Client builder with the minimum version and trusted roots applied
+    pub(crate) fn client_builder(&self) -> Result<ClientBuilder> {
+        let mut builder = Client::builder();
+        if let Some(version) = self.min_tls_version()? {
+            builder = builder.min_tls_version(version);
+        }
+        if !self.pinned_certs.is_empty() {
+            builder = builder.tls_built_in_root_certs(false);
+            for path in &self.pinned_certs {
+                let pem = fs::read(path).with_context(|| format!("reading pinned certificate {}", path))?;
+                let cert = Certificate::from_pem(&pem).with_context(|| format!("parsing pinned certificate {}", path))?;
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+        Ok(builder)
+    }
+}
+
+/// Turn a failed send into an error that says plainly when the certificate was rejected
+pub(crate) fn describe_send_error(err: reqwest::Error, what: &str) -> anyhow::Error {
+    let mut source: Option<&dyn std::error::Error> = Some(&err);
+    while let Some(e) = source {
+        if e.to_string().contains("certificate") {
+            let detail = e.to_string();
+            return anyhow!(err).context(format!(
+                "{}: TLS certificate rejected (does not chain to the configured or system trusted roots): {}",
+                what, detail
+            ));
+        }
+        source = e.source();
+    }
+    anyhow!(err).context(what.to_string())
+}
+
+/// Locations searched for config.toml, relative to the working directory
+const CONFIG_PATHS: [&str; 3] = [
+    "config.toml",  // Current directory
+    "../config.toml",  // Parent directory (for when running from src-tauri)
+    "../../config.toml",  // Two levels up (fallback)
+];
+
+/// Read the first config.toml found, returning its contents and the path it came from
+pub(crate) fn read_config_file() -> Result<(String, &'static str)> {
+    for path in CONFIG_PATHS {
+        if let Ok(content) = fs::read_to_string(path) {
+            println!("🔍 AWS Config: Found config at {}", path);
+            return Ok((content, path));
+        }
+    }
+    Err(anyhow!("config.toml not found in any expected location"))
+}
+
+/// Where the app writes scrubbed conversations; always watched alongside `watch_dir`
+static MEMORY_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Register the app's conversation dir so every loaded config also uploads from it. Call
+/// once at startup, before the uploader starts.
+pub fn set_memory_dir(dir: PathBuf) {
+    let _ = MEMORY_DIR.set(dir);
 }
 
 impl AwsConfig {
     pub fn load() -> Result<Self> {
-        // Try to find config.toml in multiple locations
-        let config_paths = vec![
-            "config.toml",  // Current directory
-            "../config.toml",  // Parent directory (for when running from src-tauri)
-            "../../config.toml",  // Two levels up (fallback)
-        ];
-        
-        let mut config_content = None;
-        let mut found_path = None;
-        
-        for path in &config_paths {
-            if let Ok(content) = fs::read_to_string(path) {
-                config_content = Some(content);
-                found_path = Some(*path);
-                println!("🔍 AWS Config: Found config at {}", path);
-                break;
+        Ok(Self::load_with_source()?.0)
+    }
+
+    /// Load config.toml, also returning the absolute path it was read from
+    pub fn load_with_source() -> Result<(Self, PathBuf)> {
+        let (text, found_path) = read_config_file()?;
+        let config_path = std::env::current_dir()?.join(found_path);
+        let config_path = config_path.canonicalize().unwrap_or(config_path);
+        let mut cfg = Self::from_toml(&text, &config_path)?;
+        if let Some(memory_dir) = MEMORY_DIR.get() {
+            cfg.watch_dir.include(memory_dir);
+        }
+        Ok((cfg, config_path))
+    }
+
+    /// Parse config text and resolve relative paths against the project root
+    /// (the parent of the directory holding `config_path`)
+    fn from_toml(text: &str, config_path: &Path) -> Result<Self> {
+        Self::from_toml_with_env(text, config_path, |name| std::env::var(name).ok())
+    }
+
+    /// `from_toml`, taking environment overrides from `env`
+    fn from_toml_with_env(text: &str, config_path: &Path, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(text).context("parsing config.toml")?;
+        apply_env_overrides(&mut table, env)?;
+        for field in ["device_id", "watch_dir"] {
+            if !table.contains_key(field) {
+                return Err(anyhow!("config.toml: `{}` is missing; see the comments in config.toml", field));
             }
         }
-        
-        let text = config_content.ok_or_else(|| anyhow!("config.toml not found in any expected location"))?;
-        let mut cfg: AwsConfig = toml::from_str(&text).context("parsing config.toml")?;
+        let mut cfg: AwsConfig = table.try_into().context("parsing config.toml")?;
+        cfg.validate()?;
         
         // Resolve relative paths to absolute paths
-        if !cfg.watch_dir.starts_with("C:") && !cfg.watch_dir.starts_with("/") {
+        for watch_dir in cfg.watch_dir.all_mut() {
+            if watch_dir.starts_with("C:") || watch_dir.starts_with("/") {
+                continue;
+            }
             // Always resolve watch_dir relative to project root (one level up from where config.toml was found)
-            let project_root = match found_path {
-                Some("config.toml") => std::env::current_dir()?.join(".."),
-                Some("../config.toml") => std::env::current_dir()?.join("..").join(".."),
-                Some("../../config.toml") => std::env::current_dir()?.join("..").join("..").join(".."),
-                _ => std::env::current_dir()?.join(".."),
-            };
+            let project_root = config_path
+                .parent()
+                .ok_or_else(|| anyhow!("config path has no parent: {}", config_path.display()))?
+                .join("..");
             
             // Resolve the watch_dir relative to the project root
-            let resolved_path = project_root.join(&cfg.watch_dir);
+            let resolved_path = project_root.join(&*watch_dir);
             
             // Canonicalize the path if possible, otherwise use the joined path
             let final_path = if let Ok(canonical) = resolved_path.canonicalize() {
@@ -60,15 +357,149 @@ impl AwsConfig {
                 resolved_path
             };
             
-            cfg.watch_dir = final_path.to_string_lossy().to_string();
+            *watch_dir = final_path.to_string_lossy().to_string();
             println!("🔍 AWS Config: Project root: {}", project_root.display());
-            println!("🔍 AWS Config: Resolved watch_dir to: {}", cfg.watch_dir);
+            println!("🔍 AWS Config: Resolved watch_dir to: {}", watch_dir);
         }
         
+        // Pinned certificates are looked up next to config.toml
+        let config_dir = config_path.parent().unwrap_or(Path::new("."));
+        for cert in cfg.tls.pinned_certs.iter_mut() {
+            if Path::new(cert.as_str()).is_relative() {
+                *cert = config_dir.join(&*cert).to_string_lossy().to_string();
+            }
+        }
+
         if cfg.scan_interval_secs.is_none() { cfg.scan_interval_secs = Some(60); }
         if cfg.concurrency.is_none() { cfg.concurrency = Some(2); }
+        if cfg.debounce_ms.is_none() { cfg.debounce_ms = Some(DEFAULT_DEBOUNCE_MS); }
         Ok(cfg)
     }
+
+    /// Reject settings that would otherwise only fail deep inside an upload request
+    fn validate(&self) -> Result<()> {
+        if self.device_id.trim().is_empty() {
+            return Err(anyhow!("config.toml: `device_id` is empty; set a stable name for this computer, e.g. \"laptop-01\""));
+        }
+        if self.watch_dir.all().is_empty() {
+            return Err(anyhow!("config.toml: `watch_dir` must name at least one directory"));
+        }
+        if self.watch_dir.all().iter().any(|dir| dir.trim().is_empty()) {
+            return Err(anyhow!("config.toml: `watch_dir` contains an empty path"));
+        }
+        match self.backend {
+            UploadBackend::Presign if self.api_url.is_empty() => {
+                return Err(anyhow!("config.toml: `api_url` is required for the presign backend"));
+            }
+            UploadBackend::DirectS3 if self.bucket.is_none() || self.region.is_none() => {
+                return Err(anyhow!("config.toml: `bucket` and `region` are required for the direct_s3 backend"));
+            }
+            _ => {}
+        }
+        if let Err(e) = self.tls.min_tls_version() {
+            return Err(anyhow!("config.toml: `[tls]` {}", e));
+        }
+        if !self.api_url.is_empty() {
+            let valid = reqwest::Url::parse(&self.api_url)
+                .map_err(|e| e.to_string())
+                .and_then(|url| match url.scheme() {
+                    "http" | "https" => Ok(()),
+                    scheme => Err(format!("unsupported scheme \"{}\"", scheme)),
+                });
+            if let Err(e) = valid {
+                return Err(anyhow!("config.toml: `api_url` \"{}\" is not a valid http(s) URL ({})", self.api_url, e));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Replace config.toml values with those of the `ARKANGEL_*` variables that are set
+fn apply_env_overrides(table: &mut toml::Table, env: impl Fn(&str) -> Option<String>) -> Result<()> {
+    let var = |name: &str| env(name).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+    let number = |name: &str| -> Result<Option<toml::Value>> {
+        match var(name) {
+            Some(v) => {
+                let n: i64 = v.parse().with_context(|| format!("{} must be a whole number, got \"{}\"", name, v))?;
+                Ok(Some(toml::Value::Integer(n)))
+            }
+            None => Ok(None),
+        }
+    };
+    if let Some(url) = var("ARKANGEL_API_URL") {
+        table.insert("api_url".to_string(), toml::Value::String(url));
+    }
+    if let Some(device_id) = var("ARKANGEL_DEVICE_ID") {
+        table.insert("device_id".to_string(), toml::Value::String(device_id));
+    }
+    if let Some(dirs) = var("ARKANGEL_WATCH_DIR") {
+        let dirs: Vec<toml::Value> = std::env::split_paths(&dirs)
+            .map(|dir| toml::Value::String(dir.to_string_lossy().to_string()))
+            .collect();
+        let value = match <[toml::Value; 1]>::try_from(dirs) {
+            Ok([one]) => one,
+            Err(many) => toml::Value::Array(many),
+        };
+        table.insert("watch_dir".to_string(), value);
+    }
+    if let Some(secs) = number("ARKANGEL_SCAN_INTERVAL_SECS")? {
+        table.insert("scan_interval_secs".to_string(), secs);
+    }
+    if let Some(concurrency) = number("ARKANGEL_CONCURRENCY")? {
+        table.insert("concurrency".to_string(), concurrency);
+    }
+    Ok(())
+}
+
+/// The AWS config as actually in effect, for diagnosing path resolution
+#[derive(Serialize, Debug)]
+pub struct ResolvedAwsConfig {
+    pub source_path: String,     // absolute path of the config.toml that was loaded
+    pub backend: UploadBackend,
+    pub api_url: String,
+    pub bucket: Option<String>,
+    pub region: Option<String>,
+    pub s3_endpoint: Option<String>,
+    pub device_id: String,
+    pub watch_dir: WatchDirs,    // absolute
+    pub scan_interval_secs: u64,
+    pub concurrency: usize,
+    pub debounce_ms: u64,
+    pub upload_policy: UploadPolicy,
+    pub tls: TlsSettings,
+    pub synced: SyncedOutput,
+    pub archives: ArchiveSettings,
+    pub dry_run: bool,
+    pub recursive: bool,
+}
+
+impl ResolvedAwsConfig {
+    pub fn load() -> Result<Self> {
+        let (cfg, source_path) = AwsConfig::load_with_source()?;
+        Ok(Self::from_config(cfg, &source_path))
+    }
+
+    fn from_config(cfg: AwsConfig, source_path: &Path) -> Self {
+        Self {
+            source_path: source_path.to_string_lossy().to_string(),
+            backend: cfg.backend,
+            api_url: cfg.api_url,
+            bucket: cfg.bucket,
+            region: cfg.region,
+            s3_endpoint: cfg.s3_endpoint,
+            device_id: cfg.device_id,
+            watch_dir: cfg.watch_dir,
+            scan_interval_secs: cfg.scan_interval_secs.unwrap_or(60),
+            concurrency: cfg.concurrency.unwrap_or(2),
+            debounce_ms: cfg.debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS),
+            upload_policy: cfg.upload_policy,
+            tls: cfg.tls,
+            synced: cfg.synced,
+            archives: cfg.archives,
+            dry_run: cfg.dry_run,
+            recursive: cfg.recursive,
+        }
+    }
 }
 
 // -------- presign request/response contracts --------
@@ -78,29 +509,104 @@ struct PresignReq<'a> {
     #[serde(rename = "deviceId")]
     device_id: &'a str,
     filename: &'a str,
+    /// What the PUT will send, for endpoints that sign the content type into the URL
+    #[serde(rename = "contentType")]
+    content_type: &'a str,
 }
 
 #[derive(Deserialize, Debug)]
 struct PresignResp {
     url: String,
     key: String,
+    #[serde(default)]
+    bucket: Option<String>,
+}
+
+/// Bucket named in upload logs when neither the presign response nor config says otherwise
+const DEFAULT_BUCKET: &str = "arkangel-json-ingest-prod";
+
+/// Optional copy of the uploader's success lines, for tests that check what was logged
+#[cfg(test)]
+fn upload_log_sink() -> &'static Mutex<Option<std::sync::mpsc::Sender<String>>> {
+    static SINK: OnceLock<Mutex<Option<std::sync::mpsc::Sender<String>>>> = OnceLock::new();
+    SINK.get_or_init(|| Mutex::new(None))
+}
+
+fn log_upload(line: String) {
+    println!("{}", line);
+    #[cfg(test)]
+    if let Some(sink) = upload_log_sink().lock().unwrap().as_ref() {
+        let _ = sink.send(line);
+    }
 }
 
 // -------- helpers --------
 
-fn is_complete_json(path: &Path) -> bool {
+/// Whether `path` is inside a raw/ or quarantine/ folder below its watch dir. Folders
+/// above the watch dir don't count, so a watch dir of /home/raw/memory still syncs.
+fn in_held_back_dir(path: &Path, cfg: &AwsConfig) -> bool {
+    let below = cfg.watch_dir.paths().find_map(|root| path.strip_prefix(root).ok()).unwrap_or(path);
+    below
+        .components()
+        .any(|c| c.as_os_str() == memory_store::RAW_DIR_NAME || c.as_os_str() == QUARANTINE_DIR_NAME)
+}
+
+fn is_complete_json(path: &Path, cfg: &AwsConfig) -> bool {
     // Only pick *.json files (not *.tmp or already-synced files)
     if path.extension().and_then(|e| e.to_str()) != Some("json") {
         return false;
     }
+    // Never upload the unscrubbed copies kept for local QA, or quarantined files
+    if in_held_back_dir(path, cfg) {
+        return false;
+    }
     if path.file_name().and_then(|n| n.to_str()).map(|s| s.ends_with(".synced")).unwrap_or(false) {
         return false;
     }
     true
 }
 
-fn mark_synced(path: &Path) -> Result<()> {
-    let mut new_path = path.to_path_buf();
+fn is_pending_archive(path: &Path, cfg: &AwsConfig) -> bool {
+    let settings = &cfg.archives;
+    if !settings.enabled {
+        return false;
+    }
+    let matches_ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|ext| settings.extensions.iter().any(|x| x.eq_ignore_ascii_case(ext)))
+        .unwrap_or(false);
+    matches_ext && !in_held_back_dir(path, cfg)
+}
+
+/// Name the object is uploaded under: the file name, or with `recursive` its
+/// `/`-separated path relative to the watch dir it lives in
+fn object_name(cfg: &AwsConfig, path: &Path) -> String {
+    let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    if !cfg.recursive {
+        return filename;
+    }
+    cfg.watch_dir
+        .paths()
+        .find_map(|root| path.strip_prefix(root).ok())
+        .map(slash_path)
+        .unwrap_or(filename)
+}
+
+/// Anything the uploader should pick up: a finished JSON file or an enabled archive
+fn is_upload_candidate(path: &Path, cfg: &AwsConfig) -> bool {
+    is_complete_json(path, cfg) || is_pending_archive(path, cfg)
+}
+
+fn mark_synced(path: &Path, output: &SyncedOutput) -> Result<()> {
+    let mut new_path = match output.mode {
+        SyncedMode::Rename => path.to_path_buf(),
+        SyncedMode::Archive => {
+            let dir = path.parent().unwrap_or(Path::new(".")).join(&output.archive_dir);
+            fs::create_dir_all(&dir).with_context(|| format!("creating archive dir {}", dir.display()))?;
+            dir.join(path.file_name().unwrap())
+        }
+    };
     // change foo.json -> foo.json.synced
     let new_name = format!(
         "{}.synced",
@@ -115,283 +621,2512 @@ fn mark_synced(path: &Path) -> Result<()> {
     Ok(())
 }
 
-fn read_all_bytes(path: &Path) -> Result<Vec<u8>> {
-    // If the producer writes atomically (tmp+rename), this just works.
-    // If not, you can add a small sleep or check size-stability.
-    let mut f = fs::File::open(path)?;
-    let mut buf = Vec::new();
-    f.read_to_end(&mut buf)?;
-    Ok(buf)
-}
+// -------- upload events --------
 
-// -------- core upload logic --------
+pub const UPLOAD_STARTED_EVENT: &str = "aws-upload-started";
+pub const UPLOAD_SUCCEEDED_EVENT: &str = "aws-upload-succeeded";
+pub const UPLOAD_FAILED_EVENT: &str = "aws-upload-failed";
 
-fn presign(client: &Client, api_url: &str, device_id: &str, filename: &str) -> Result<PresignResp> {
-    let body = PresignReq { device_id, filename };
-    let resp = client
-        .post(api_url)
-        .header("content-type", "application/json")
-        .json(&body)
-        .send()
-        .context("calling presign endpoint")?
-        .error_for_status()
-        .context("non-200 from presign endpoint")?
-        .json::<PresignResp>()
-        .context("decoding presign response")?;
-    Ok(resp)
+/// Progress of a single file's upload; serialized as the event payload
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum UploadEvent {
+    Started { filename: String, path: String },
+    Succeeded { filename: String, key: String },
+    Failed { filename: String, error: String },
 }
 
-fn upload_with_put(client: &Client, put_url: &str, bytes: Vec<u8>) -> Result<()> {
-    let r = client
-        .put(put_url)
-        .header("content-type", "application/json")
-        .body(bytes)
-        .send()
-        .context("PUT to presigned URL")?;
-    if !r.status().is_success() {
-        return Err(anyhow!("upload failed with status {}", r.status()));
+impl UploadEvent {
+    /// Tauri event name the frontend listens on
+    pub fn name(&self) -> &'static str {
+        match self {
+            UploadEvent::Started { .. } => UPLOAD_STARTED_EVENT,
+            UploadEvent::Succeeded { .. } => UPLOAD_SUCCEEDED_EVENT,
+            UploadEvent::Failed { .. } => UPLOAD_FAILED_EVENT,
+        }
     }
-    Ok(())
 }
 
-// Exponential backoff helper
-fn retry<F>(mut f: F, attempts: usize, base_delay_ms: u64) -> Result<()>
-where
-    F: FnMut() -> Result<()>,
-{
-    let mut delay = base_delay_ms;
-    for i in 0..attempts {
-        match f() {
-            Ok(_) => return Ok(()),
-            Err(e) => {
-                eprintln!("attempt {}/{} failed: {e:?}", i + 1, attempts);
-                if i + 1 == attempts { break; }
-                thread::sleep(Duration::from_millis(delay));
-                delay = (delay as f64 * 1.8).min(30_000.0) as u64; // cap ~30s
-            }
+/// Receives upload progress; the app forwards it to the UI as Tauri events
+pub trait UploadNotifier: Send + Sync {
+    fn upload_event(&self, event: &UploadEvent) -> Result<()>;
+}
+
+fn notify(notifier: Option<&dyn UploadNotifier>, event: UploadEvent) {
+    if let Some(n) = notifier {
+        if let Err(e) = n.upload_event(&event) {
+            eprintln!("⚠️  failed emitting {}: {e:#}", event.name());
         }
     }
-    Err(anyhow!("all {} attempts failed", attempts))
 }
 
-fn process_file(client: &Client, cfg: &AwsConfig, path: &Path) -> Result<()> {
-    let filename = path.file_name().unwrap().to_string_lossy().to_string();
+// -------- upload state --------
 
-    // 1) presign with retry logic
-    let presigned = {
-        let mut last_error: Option<anyhow::Error> = None;
-        let mut result: Option<PresignResp> = None;
-        
-        for delay in [500, 1200, 2500] {
-            match presign(client, &cfg.api_url, &cfg.device_id, &filename) {
-                Ok(p) => { 
-                    result = Some(p); 
-                    break; 
-                }
-                Err(e) => { 
-                    last_error = Some(e); 
-                    thread::sleep(Duration::from_millis(delay)); 
-                }
-            }
+/// File in the watch dir recording uploads the server has confirmed, so a restart can
+/// skip them even if the `.synced` rename never happened. No `.json` extension, so the
+/// uploader never picks it up itself.
+const STATE_FILE_NAME: &str = ".upload-state";
+
+/// Where a confirmed upload went and the MD5 of what was sent. `size` and `modified_ns`
+/// let a scan skip hashing files that haven't changed since.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SyncedRecord {
+    pub key: String,
+    pub md5: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_ns: Option<u64>,
+}
+
+/// A file modified this recently may change again within the same mtime tick, so its
+/// size and mtime aren't trusted to stand for its contents
+const MTIME_SETTLE: Duration = Duration::from_secs(2);
+
+/// Size and mtime of a file for `SyncedRecord`, or None while the mtime is too recent
+fn file_stamp(meta: &fs::Metadata) -> Option<(u64, u64)> {
+    let modified = meta.modified().ok()?;
+    if modified.elapsed().map(|age| age < MTIME_SETTLE).unwrap_or(true) {
+        return None;
+    }
+    let ns = modified.duration_since(std::time::UNIX_EPOCH).ok()?.as_nanos();
+    Some((meta.len(), u64::try_from(ns).ok()?))
+}
+
+/// Confirmed uploads of one watch dir, keyed by `/`-separated path below it
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct UploadState {
+    #[serde(default)]
+    pub synced: BTreeMap<String, SyncedRecord>,
+}
+
+impl UploadState {
+    /// True if the file recorded under `key` was already uploaded with the current
+    /// contents of `path`. Only hashes when its size or mtime has changed.
+    fn is_synced(&self, key: &str, path: &Path) -> bool {
+        let Some(record) = self.synced.get(key) else {
+            return false;
+        };
+        let stamp = fs::metadata(path).ok().as_ref().and_then(file_stamp);
+        if stamp.is_some() && stamp == record.size.zip(record.modified_ns) {
+            return true;
         }
-        
-        result.ok_or_else(|| last_error.unwrap_or_else(|| anyhow!("Presign failed after all attempts")))
-    }?;
+        read_all_bytes(path).map(|b| md5_hex(&b) == record.md5).unwrap_or(false)
+    }
+}
 
-    // 2) read bytes
-    let bytes = read_all_bytes(path).context("reading file before upload")?;
+/// Where the upload of `path` is recorded: the state file of the watch dir it lives in,
+/// under its path below that dir. Files outside every watch dir use their own folder.
+fn state_location(cfg: &AwsConfig, path: &Path) -> (PathBuf, String) {
+    for root in cfg.watch_dir.paths() {
+        if let Ok(relative) = path.strip_prefix(root) {
+            return (root.to_path_buf(), slash_path(relative));
+        }
+    }
+    let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    (path.parent().unwrap_or(Path::new(".")).to_path_buf(), name)
+}
 
-    // 3) upload (presigned PUT)
-    retry(
-        || {
-            upload_with_put(client, &presigned.url, bytes.clone())
-        },
-        5,   // attempts
-        700, // base delay ms
-    )?;
+/// `relative` joined with `/`, whatever the platform separator
+fn slash_path(relative: &Path) -> String {
+    relative
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Serializes read-modify-write cycles on the state file across upload workers
+fn state_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
 
-    // 4) mark local file as synced
-    mark_synced(path)?;
+fn read_state(watch_dir: &Path) -> UploadState {
+    let path = watch_dir.join(STATE_FILE_NAME);
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(_) => return UploadState::default(),
+    };
+    serde_json::from_str(&text).unwrap_or_else(|e| {
+        eprintln!("⚠️  Ignoring unreadable upload state {}: {}", path.display(), e);
+        UploadState::default()
+    })
+}
 
-    println!("✅ uploaded: {}  →  s3://arkangel-json-ingest-prod/{}", filename, presigned.key);
+fn write_state(watch_dir: &Path, state: &UploadState) -> Result<()> {
+    let path = watch_dir.join(STATE_FILE_NAME);
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_vec_pretty(state)?)?;
+    fs::rename(&tmp, &path).with_context(|| format!("writing upload state {}", path.display()))?;
     Ok(())
 }
 
-// -------- public interface --------
+fn record_synced(watch_dir: &Path, key: &str, record: SyncedRecord) -> Result<()> {
+    let _guard = state_lock().lock().unwrap();
+    let mut state = read_state(watch_dir);
+    state.synced.insert(key.to_string(), record);
+    write_state(watch_dir, &state)
+}
 
-pub struct AwsUploader {
-    config: AwsConfig,
-    client: Client,
+/// Subdirectory of the watch dir holding files withheld by the upload policy
+pub const QUARANTINE_DIR_NAME: &str = "quarantine";
+
+fn quarantine_file(path: &Path, reason: &str) -> Result<PathBuf> {
+    let dir = path.parent().unwrap_or(Path::new(".")).join(QUARANTINE_DIR_NAME);
+    fs::create_dir_all(&dir)?;
+    let name = path.file_name().unwrap().to_string_lossy().to_string();
+    let dest = dir.join(&name);
+    if fs::rename(path, &dest).is_err() {
+        fs::copy(path, &dest)?;
+        fs::remove_file(path)?;
+    }
+    fs::write(dir.join(format!("{}.reason.txt", name)), reason)?;
+    println!("🚫 AWS Uploader: Quarantined {} ({})", name, reason);
+    Ok(dest)
 }
 
-impl AwsUploader {
-    pub fn new() -> Result<Self> {
-        let config = AwsConfig::load()?;
-        fs::create_dir_all(&config.watch_dir).ok();
+/// A file held in the quarantine dir, with the reason it was withheld
+#[derive(Serialize, Debug)]
+pub struct QuarantineEntry {
+    pub filename: String,
+    pub size: u64,
+    pub reason: String,
+}
+
+fn quarantine_dir(watch_dir: &Path) -> PathBuf {
+    watch_dir.join(QUARANTINE_DIR_NAME)
+}
 
-        // HTTP client with sensible timeouts
-        let client = Client::builder()
-            .timeout(Duration::from_secs(20))
-            .build()
-            .context("building http client")?;
+fn reason_path(dir: &Path, filename: &str) -> PathBuf {
+    dir.join(format!("{}.reason.txt", filename))
+}
 
-        Ok(Self { config, client })
+/// Resolve `filename` inside the quarantine dir, rejecting anything that isn't a plain file name
+fn quarantined_path(watch_dir: &Path, filename: &str) -> Result<PathBuf> {
+    let plain = Path::new(filename).file_name().map(|n| n == filename).unwrap_or(false);
+    if !plain || filename.ends_with(".reason.txt") {
+        return Err(anyhow!("invalid quarantine filename: {}", filename));
     }
+    let path = quarantine_dir(watch_dir).join(filename);
+    if !path.is_file() {
+        return Err(anyhow!("not in quarantine: {}", filename));
+    }
+    Ok(path)
+}
 
-    pub fn scan_and_upload(&self) -> Result<()> {
-        println!("🔍 AWS Uploader: Starting scan of directory: {}", self.config.watch_dir);
-        
-        // gather candidate files
-        let mut files: Vec<PathBuf> = Vec::new();
-        for entry in WalkDir::new(&self.config.watch_dir).max_depth(1) {
-            let entry = match entry { Ok(e) => e, Err(_) => continue };
-            let p = entry.path().to_path_buf();
-            if p.is_file() && is_complete_json(&p) {
-                println!("🔍 AWS Uploader: Found file: {}", p.display());
-                files.push(p);
-            }
-        }
+/// The watch dir whose quarantine holds `filename`, if any
+pub fn quarantine_owner(watch_dirs: &WatchDirs, filename: &str) -> Option<PathBuf> {
+    watch_dirs
+        .paths()
+        .find(|dir| quarantined_path(dir, filename).is_ok())
+        .map(Path::to_path_buf)
+}
 
-        if !files.is_empty() {
-            println!("🔍 AWS Uploader: Found {} file(s) to upload", files.len());
-        } else {
-            println!("🔍 AWS Uploader: No files found to upload");
+/// Files currently held in `<watch_dir>/quarantine`
+pub fn list_quarantine(watch_dir: &Path) -> Result<Vec<QuarantineEntry>> {
+    let dir = quarantine_dir(watch_dir);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+        let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        if !path.is_file() || filename.ends_with(".reason.txt") {
+            continue;
         }
+        entries.push(QuarantineEntry {
+            size: fs::metadata(&path)?.len(),
+            reason: fs::read_to_string(reason_path(&dir, &filename)).unwrap_or_default(),
+            filename,
+        });
+    }
+    entries.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(entries)
+}
 
-        // process files sequentially for now (can be made parallel later)
-        for p in files {
-            // Check if file still exists and is still a valid JSON (not already processed)
-            if p.exists() && is_complete_json(&p) {
-                if let Err(e) = process_file(&self.client, &self.config, &p) {
-                    eprintln!("⚠️  failed processing {}: {e:?}", p.display());
-                }
-            } else {
-                println!("🔍 AWS Uploader: Skipping file (no longer valid): {}", p.display());
-            }
+/// Delete one quarantined file (or all of them when `filename` is None), returning how many were removed
+pub fn clear_quarantine(watch_dir: &Path, filename: Option<&str>) -> Result<usize> {
+    let targets: Vec<String> = match filename {
+        Some(name) => {
+            quarantined_path(watch_dir, name)?;
+            vec![name.to_string()]
         }
+        None => list_quarantine(watch_dir)?.into_iter().map(|e| e.filename).collect(),
+    };
+    let dir = quarantine_dir(watch_dir);
+    for name in &targets {
+        fs::remove_file(dir.join(name))?;
+        let _ = fs::remove_file(reason_path(&dir, name));
+    }
+    println!("🗑️  AWS Uploader: Cleared {} quarantined file(s)", targets.len());
+    Ok(targets.len())
+}
 
-        Ok(())
+/// Scrub a quarantined file again and, if it now passes the upload policy, move it back
+/// into the watch dir for upload. Returns the path it was written to.
+pub fn resubmit_quarantine(cfg: &AwsConfig, filename: &str) -> Result<PathBuf> {
+    let owner = quarantine_owner(&cfg.watch_dir, filename)
+        .ok_or_else(|| anyhow!("not in quarantine: {}", filename))?;
+    let watch_dir = owner.as_path();
+    let path = quarantined_path(watch_dir, filename)?;
+    let dest = watch_dir.join(filename);
+    if dest.exists() {
+        return Err(anyhow!("{} already exists in the watch dir", filename));
     }
 
-    pub fn start_background_uploader() -> Result<()> {
-        let uploader = AwsUploader::new()?;
-        let scan_secs = uploader.config.scan_interval_secs.unwrap_or(60);
-        let watch_dir = uploader.config.watch_dir.clone();
-        let api_url = uploader.config.api_url.clone();
-        let device_id = uploader.config.device_id.clone();
-        let client = uploader.client.clone();
+    let content = fs::read_to_string(&path).context("reading quarantined file")?;
+    let scrubbed = pii_scrubber::scrub_conversation_json(content)
+        .map_err(|e| anyhow!("re-scrub failed: {}", e))?;
+    if cfg.upload_policy.enabled {
+        if let Some(reason) = cfg.upload_policy.violation(scrubbed.as_bytes()) {
+            fs::write(reason_path(&quarantine_dir(watch_dir), filename), &reason)?;
+            return Err(anyhow!("still withheld by upload policy: {}", reason));
+        }
+    }
 
-        // Start file watcher thread
-        std::thread::spawn(move || {
-            println!("🔍 AWS Uploader: File watcher thread started");
-            
-            // Track currently processing files to prevent duplicates
-            let processing_files: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
-            
-            // Create file watcher
-            let (tx, rx) = channel();
-            let mut watcher: RecommendedWatcher = match notify::recommended_watcher(move |res| {
-                let _ = tx.send(res);
-            }) {
-                Ok(w) => w,
-                Err(e) => {
-                    eprintln!("⚠️  Failed to create file watcher: {}", e);
-                    return;
-                }
+    // Write under a temp name first so the watcher never sees a partial file
+    let tmp = watch_dir.join(format!("{}.tmp", filename));
+    fs::write(&tmp, scrubbed)?;
+    fs::rename(&tmp, &dest)?;
+    fs::remove_file(&path)?;
+    let _ = fs::remove_file(reason_path(&quarantine_dir(watch_dir), filename));
+    println!("🔁 AWS Uploader: Resubmitted {} from quarantine", filename);
+    Ok(dest)
+}
+
+/// Gap between the two size reads in `wait_for_stable_size`
+const SIZE_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+/// Give up on a file still growing after this long; the next scan retries it
+const SIZE_STABLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Wait until two size reads `interval` apart agree, so a file still being written
+/// isn't uploaded truncated. Returns the settled size.
+fn wait_for_stable_size(path: &Path, interval: Duration, timeout: Duration) -> Result<u64> {
+    let started = Instant::now();
+    let mut last = fs::metadata(path)?.len();
+    loop {
+        thread::sleep(interval);
+        let size = fs::metadata(path)?.len();
+        if size == last {
+            return Ok(size);
+        }
+        if started.elapsed() >= timeout {
+            return Err(anyhow!("{} is still being written after {:?}", path.display(), timeout));
+        }
+        last = size;
+    }
+}
+
+fn read_all_bytes(path: &Path) -> Result<Vec<u8>> {
+    // Producers that don't write atomically (tmp+rename) are covered by
+    // `wait_for_stable_size` before a candidate is processed
+    let mut f = fs::File::open(path)?;
+    let mut buf = Vec::new();
+    f.read_to_end(&mut buf)?;
+    Ok(buf)
+}
+
+// -------- cancellation --------
+
+/// Returned when an in-flight upload is aborted via `cancel_upload`
+#[derive(Debug)]
+pub struct UploadCancelled;
+
+impl std::fmt::Display for UploadCancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upload cancelled")
+    }
+}
+
+impl std::error::Error for UploadCancelled {}
+
+/// Cancel state of an in-flight upload
+const RUNNING: u8 = 0;
+const CANCELLED: u8 = 1;
+/// The whole PUT body has been handed to the connection, so the object may already
+/// exist on S3; too late to cancel
+const COMMITTED: u8 = 2;
+
+/// Cancel state for uploads currently in flight, keyed by the file's full path
+fn in_flight() -> &'static Mutex<HashMap<PathBuf, Arc<AtomicU8>>> {
+    static IN_FLIGHT: OnceLock<Mutex<HashMap<PathBuf, Arc<AtomicU8>>>> = OnceLock::new();
+    IN_FLIGHT.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// Registers a file's cancel state for the duration of its upload
+struct InFlightUpload {
+    path: PathBuf,
+    state: Arc<AtomicU8>,
+}
+
+impl InFlightUpload {
+    fn register(path: &Path) -> Self {
+        let state = Arc::new(AtomicU8::new(RUNNING));
+        in_flight().lock().unwrap().insert(path.to_path_buf(), state.clone());
+        Self { path: path.to_path_buf(), state }
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.state.load(Ordering::SeqCst) == CANCELLED {
+            return Err(UploadCancelled.into());
+        }
+        Ok(())
+    }
+
+    /// PUT body for the next attempt. A retry after a failed attempt can be cancelled again.
+    fn body(&self, bytes: &Arc<Vec<u8>>) -> reqwest::blocking::Body {
+        let _ = self.state.compare_exchange(COMMITTED, RUNNING, Ordering::SeqCst, Ordering::SeqCst);
+        let len = bytes.len() as u64;
+        reqwest::blocking::Body::sized(CancellableBody { bytes: bytes.clone(), pos: 0, state: self.state.clone() }, len)
+    }
+}
+
+impl Drop for InFlightUpload {
+    fn drop(&mut self) {
+        let mut map = in_flight().lock().unwrap();
+        // Only remove our own state; a newer upload of the same file may have replaced it
+        if map.get(&self.path).map(|s| Arc::ptr_eq(s, &self.state)).unwrap_or(false) {
+            map.remove(&self.path);
+        }
+    }
+}
+
+/// Streams a PUT body and fails the next read once the upload is cancelled, so the
+/// connection drops mid-body and S3 never stores the object. Handing over the last
+/// chunk commits the upload.
+struct CancellableBody {
+    bytes: Arc<Vec<u8>>,
+    pos: usize,
+    state: Arc<AtomicU8>,
+}
+
+impl Read for CancellableBody {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = buf.len().min(self.bytes.len() - self.pos);
+        let last = self.pos + n == self.bytes.len();
+        let state = if last {
+            match self.state.compare_exchange(RUNNING, COMMITTED, Ordering::SeqCst, Ordering::SeqCst) {
+                Ok(_) => COMMITTED,
+                Err(current) => current,
+            }
+        } else {
+            self.state.load(Ordering::SeqCst)
+        };
+        if state == CANCELLED {
+            return Err(std::io::Error::other(UploadCancelled));
+        }
+        buf[..n].copy_from_slice(&self.bytes[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Abort the upload of the file at `path`. Returns false if nothing is in flight for it,
+/// or if its body has already been sent and the object may exist on S3.
+pub fn cancel_upload(path: &str) -> bool {
+    let map = in_flight().lock().unwrap();
+    let Some(state) = map.get(Path::new(path)) else {
+        return false;
+    };
+    match state.compare_exchange(RUNNING, CANCELLED, Ordering::SeqCst, Ordering::SeqCst) {
+        Ok(_) | Err(CANCELLED) => {
+            println!("🛑 AWS Uploader: Cancellation requested for {}", path);
+            true
+        }
+        Err(_) => {
+            println!("🛑 AWS Uploader: Too late to cancel {}; its body has already been sent", path);
+            false
+        }
+    }
+}
+
+// -------- core upload logic --------
+
+/// Presign calls are small; cancellation waits for at most this long before taking effect
+const PRESIGN_TIMEOUT: Duration = Duration::from_secs(15);
+
+fn presign(client: &Client, api_url: &str, device_id: &str, filename: &str, content_type: &str) -> Result<PresignResp> {
+    let body = PresignReq { device_id, filename, content_type };
+    let resp = client
+        .post(api_url)
+        .timeout(PRESIGN_TIMEOUT)
+        .header("content-type", "application/json")
+        .json(&body)
+        .send()
+        .map_err(|e| describe_send_error(e, "calling presign endpoint"))?
+        .error_for_status()
+        .context("non-200 from presign endpoint")?
+        .json::<PresignResp>()
+        .context("decoding presign response")?;
+    Ok(resp)
+}
+
+fn upload_with_put(client: &Client, put_url: &str, body: impl Into<reqwest::blocking::Body>, expected_md5: &str, content_type: &str, headers: &[(String, String)]) -> Result<()> {
+    let mut request = client.put(put_url).header("content-type", content_type);
+    // S3 rejects the PUT (400 BadDigest) if the body it receives doesn't hash to this
+    if let Some(digest) = content_md5(expected_md5) {
+        request = request.header("content-md5", digest);
+    }
+    for (name, value) in headers {
+        request = request.header(name.as_str(), value.as_str());
+    }
+    let r = request
+        .body(body)
+        .send()
+        .map_err(|e| describe_send_error(e, "PUT to presigned URL"))?;
+    if !r.status().is_success() {
+        return Err(anyhow!("upload failed with status {}", r.status()));
+    }
+    let kms = r
+        .headers()
+        .get("x-amz-server-side-encryption")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("aws:kms"));
+    match r.headers().get(reqwest::header::ETAG).and_then(|v| v.to_str().ok()) {
+        // Multipart and SSE-KMS objects have ETags that aren't the body's MD5
+        Some(etag) if kms || !is_md5_etag(etag) => {
+            println!("🔍 AWS Uploader: ETag {} is not a content MD5; relying on Content-MD5", etag);
+            Ok(())
+        }
+        Some(etag) if !verify_etag(expected_md5, etag) => {
+            Err(anyhow!("upload corrupted: ETag {} does not match local MD5 {}", etag, expected_md5))
+        }
+        Some(_) => Ok(()),
+        None => {
+            println!("🔍 AWS Uploader: No ETag returned; skipping integrity check");
+            Ok(())
+        }
+    }
+}
+
+/// MIME type for an uploaded object: from the extension when it is one we know,
+/// otherwise sniffed from the leading bytes
+fn content_type_for(filename: &str, bytes: &[u8]) -> &'static str {
+    let extension = Path::new(filename)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "json" => "application/json",
+        "txt" | "log" => "text/plain; charset=utf-8",
+        "md" => "text/markdown; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "pdf" => "application/pdf",
+        "gz" => "application/gzip",
+        "zip" => "application/zip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        _ if bytes.starts_with(b"%PDF-") => "application/pdf",
+        _ if bytes.starts_with(&[0x1f, 0x8b]) => "application/gzip",
+        _ if bytes.starts_with(b"PK\x03\x04") => "application/zip",
+        _ if bytes.first().is_some_and(|b| *b == b'{' || *b == b'[') => "application/json",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Hex MD5 of an upload body, as S3 reports it in the ETag of a single-part PUT
+fn md5_hex(bytes: &[u8]) -> String {
+    use md5::{Digest, Md5};
+    Md5::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// `Content-MD5` header value (base64 of the raw digest) for a hex MD5
+fn content_md5(md5_hex: &str) -> Option<String> {
+    use base64::Engine;
+    if md5_hex.len() != 32 {
+        return None;
+    }
+    let digest = (0..32)
+        .step_by(2)
+        .map(|i| u8::from_str_radix(md5_hex.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    Some(base64::engine::general_purpose::STANDARD.encode(digest))
+}
+
+/// ETag with any weak marker and quotes removed
+fn etag_value(etag: &str) -> &str {
+    etag.trim().trim_start_matches("W/").trim_matches('"')
+}
+
+/// Whether `etag` has the shape of a single-part MD5 (multipart ETags end in "-<parts>")
+fn is_md5_etag(etag: &str) -> bool {
+    let etag = etag_value(etag);
+    etag.len() == 32 && etag.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// True when `etag` (quoted or not) is the MD5 we computed before uploading
+fn verify_etag(expected_md5: &str, etag: &str) -> bool {
+    etag_value(etag).eq_ignore_ascii_case(expected_md5)
+}
+
+/// Upper bound on a single retry sleep
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// Spread `delay_ms` over 50%..150% so files that failed together don't retry together
+fn jittered(delay_ms: u64) -> u64 {
+    use rand::Rng;
+    let factor: f64 = rand::thread_rng().gen_range(0.5..1.5);
+    ((delay_ms as f64 * factor) as u64).min(MAX_RETRY_DELAY_MS)
+}
+
+// Exponential backoff helper, with jitter
+fn retry<F>(mut f: F, attempts: usize, base_delay_ms: u64) -> Result<()>
+where
+    F: FnMut() -> Result<()>,
+{
+    let mut delay = base_delay_ms;
+    let mut attempt = 1;
+    loop {
+        match f() {
+            Ok(_) => return Ok(()),
+            Err(e) if e.is::<UploadCancelled>() => return Err(e),
+            Err(e) => {
+                eprintln!("attempt {}/{} failed: {e:?}", attempt, attempts);
+                // The last error says what went wrong (ETag mismatch, 4xx body, ...)
+                if attempt >= attempts {
+                    return Err(e.context(format!("after {} attempts", attempt)));
+                }
+                thread::sleep(Duration::from_millis(jittered(delay)));
+                delay = (delay as f64 * 1.8).min(MAX_RETRY_DELAY_MS as f64) as u64; // cap ~30s
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// What a file is uploaded as, and where its confirmed upload is recorded
+struct UploadTarget {
+    /// What the object key is built from
+    object_name: String,
+    state_dir: PathBuf,
+    state_key: String,
+}
+
+impl UploadTarget {
+    fn for_file(cfg: &AwsConfig, path: &Path) -> Self {
+        let (state_dir, state_key) = state_location(cfg, path);
+        Self { object_name: object_name(cfg, path), state_dir, state_key }
+    }
+}
+
+/// Upload one JSON file, reporting start and outcome to `notifier`
+fn process_file(client: &Client, cfg: &AwsConfig, path: &Path, notifier: Option<&dyn UploadNotifier>) -> Result<()> {
+    process_file_as(client, cfg, path, &UploadTarget::for_file(cfg, path), notifier)
+}
+
+fn process_file_as(client: &Client, cfg: &AwsConfig, path: &Path, target: &UploadTarget, notifier: Option<&dyn UploadNotifier>) -> Result<()> {
+    let filename = path.file_name().unwrap().to_string_lossy().to_string();
+    if cfg.dry_run {
+        return dry_run_file(cfg, path, &target.object_name);
+    }
+    notify(notifier, UploadEvent::Started { filename: filename.clone(), path: path.to_string_lossy().to_string() });
+    match upload_file(client, cfg, path, &filename, target) {
+        Ok(key) => {
+            notify(notifier, UploadEvent::Succeeded { filename, key });
+            Ok(())
+        }
+        Err(e) => {
+            notify(notifier, UploadEvent::Failed { filename, error: format!("{:#}", e) });
+            Err(e)
+        }
+    }
+}
+
+/// Dry-run stand-in for `upload_file`: apply the policy check and log where the file
+/// would go, without presigning, uploading, renaming or quarantining anything
+fn dry_run_file(cfg: &AwsConfig, path: &Path, filename: &str) -> Result<()> {
+    let bytes = read_all_bytes(path).context("reading file for dry run")?;
+    if cfg.upload_policy.enabled {
+        if let Some(reason) = cfg.upload_policy.violation(&bytes) {
+            return Err(anyhow!("would be withheld by upload policy: {}", reason));
+        }
+    }
+    let target = match cfg.backend {
+        UploadBackend::Presign => format!("a presigned URL from {}", cfg.api_url),
+        UploadBackend::DirectS3 => format!(
+            "s3://{}/{}",
+            cfg.bucket.as_deref().unwrap_or(DEFAULT_BUCKET),
+            direct_s3_key(&cfg.device_id, filename)
+        ),
+    };
+    log_upload(format!("🧪 dry run: would upload {} ({} bytes)  →  {}", filename, bytes.len(), target));
+    Ok(())
+}
+
+/// Returns the object key the file was uploaded to. `filename` names the file in logs.
+fn upload_file(client: &Client, cfg: &AwsConfig, path: &Path, filename: &str, target: &UploadTarget) -> Result<String> {
+    let credentials = match cfg.backend {
+        UploadBackend::DirectS3 => Some(sigv4::Credentials::from_env()?),
+        UploadBackend::Presign => None,
+    };
+    upload_file_signed(client, cfg, path, filename, target, credentials)
+}
+
+/// `upload_file` with the credentials direct S3 uploads are signed with
+fn upload_file_signed(
+    client: &Client,
+    cfg: &AwsConfig,
+    path: &Path,
+    filename: &str,
+    target: &UploadTarget,
+    credentials: Option<sigv4::Credentials>,
+) -> Result<String> {
+    let upload = InFlightUpload::register(path);
+
+    // 0) policy check: withhold files that still look like they contain PII
+    if cfg.upload_policy.enabled {
+        let bytes = read_all_bytes(path).context("reading file for policy check")?;
+        if let Some(reason) = cfg.upload_policy.violation(&bytes) {
+            quarantine_file(path, &reason)?;
+            return Err(anyhow!("withheld by upload policy: {}", reason));
+        }
+    }
+
+    // 1) read bytes; the stamp is taken first, so a write racing the read changes the mtime
+    let stamp = fs::metadata(path).ok().as_ref().and_then(file_stamp);
+    let bytes = Arc::new(read_all_bytes(path).context("reading file before upload")?);
+    let md5 = md5_hex(&bytes);
+    let payload_hash = sigv4::sha256_hex(&bytes);
+    let content_type = content_type_for(filename, &bytes);
+
+    // 2) work out where the file goes: a presigned URL, or a key we sign for ourselves
+    let (put_url, key, credentials, presigned_bucket) = match cfg.backend {
+        UploadBackend::Presign => {
+            let presigned = presign_with_retry(client, cfg, &target.object_name, content_type, &upload)?;
+            (presigned.url, presigned.key, None, presigned.bucket)
+        }
+        UploadBackend::DirectS3 => {
+            let key = direct_s3_key(&cfg.device_id, &target.object_name);
+            let credentials = credentials.ok_or_else(|| anyhow!("direct_s3 uploads need AWS credentials"))?;
+            (direct_s3_url(cfg, &key)?, key, Some(credentials), None)
+        }
+    };
+
+    // 3) upload (PUT) and check the returned ETag; a cancelled or corrupted upload leaves
+    //    the file unsynced for the next scan. Direct S3 PUTs are re-signed per attempt.
+    retry(
+        || {
+            let headers = match &credentials {
+                Some(creds) => s3_put_headers(&put_url, cfg.region.as_deref().unwrap_or_default(), &payload_hash, content_type, creds)?,
+                None => Vec::new(),
             };
-            
-            // Watch the memory directory
-            if let Err(e) = watcher.watch(Path::new(&watch_dir), RecursiveMode::NonRecursive) {
-                eprintln!("⚠️  Failed to watch directory {}: {}", watch_dir, e);
-                return;
+            upload.check()?;
+            // A failed send after cancelling is the body read aborting the request
+            upload_with_put(client, &put_url, upload.body(&bytes), &md5, content_type, &headers).or_else(|e| {
+                upload.check()?;
+                Err(e)
+            })
+        },
+        5,   // attempts
+        700, // base delay ms
+    )?;
+
+    // 4) record the confirmed upload, then mark local file as synced
+    upload.check()?;
+    let record = SyncedRecord { key: key.clone(), md5, size: stamp.map(|s| s.0), modified_ns: stamp.map(|s| s.1) };
+    if let Err(e) = record_synced(&target.state_dir, &target.state_key, record) {
+        eprintln!("⚠️  failed recording upload state for {}: {e:#}", filename);
+    }
+    mark_synced(path, &cfg.synced)?;
+
+    // The presign endpoint knows best where the object went; fall back to config
+    let bucket = presigned_bucket.as_deref().or(cfg.bucket.as_deref()).unwrap_or(DEFAULT_BUCKET);
+    log_upload(format!("✅ uploaded: {}  →  s3://{}/{}", filename, bucket, key));
+    Ok(key)
+}
+
+fn presign_with_retry(client: &Client, cfg: &AwsConfig, filename: &str, content_type: &'static str, upload: &InFlightUpload) -> Result<PresignResp> {
+    {
+        let mut last_error: Option<anyhow::Error> = None;
+        let mut result: Option<PresignResp> = None;
+        
+        for delay in [500, 1200, 2500] {
+            upload.check()?;
+            match presign(client, &cfg.api_url, &cfg.device_id, filename, content_type).and_then(|p| upload.check().map(|_| p)) {
+                Ok(p) => { 
+                    result = Some(p); 
+                    break; 
+                }
+                Err(e) if e.is::<UploadCancelled>() => return Err(e),
+                Err(e) => { 
+                    last_error = Some(e); 
+                    thread::sleep(Duration::from_millis(delay)); 
+                }
             }
-            
-            println!("🔍 AWS Uploader: Watching directory: {}", watch_dir);
-            
-            // Event loop for file changes
-            loop {
-                match rx.recv() {
-                    Ok(Ok(event)) => {
-                        match event.kind {
-                            EventKind::Create(_) | EventKind::Modify(_) => {
-                                for path in event.paths {
-                                    if is_complete_json(&path) {
-                                        let path_buf = PathBuf::from(&path);
-                                        
-                                        // Check if file is already being processed
-                                        {
-                                            let mut processing = processing_files.lock().unwrap();
-                                            if processing.contains(&path_buf) {
-                                                println!("🔍 AWS Uploader: Skipping already processing file: {}", path_buf.display());
-                                                continue;
-                                            }
-                                            // Mark file as being processed
-                                            processing.insert(path_buf.clone());
-                                        }
-                                        
-                                        println!("🔍 AWS Uploader: File event detected: {}", path_buf.display());
-                                        
-                                        // Small delay to ensure file is fully written
-                                        thread::sleep(Duration::from_millis(150));
-                                        
-                                        // Double-check file still exists and is valid before processing
-                                        if !path_buf.exists() || !is_complete_json(&path_buf) {
-                                            println!("🔍 AWS Uploader: File no longer valid, skipping: {}", path_buf.display());
-                                            // Remove from processing set
-                                            {
-                                                let mut processing = processing_files.lock().unwrap();
-                                                processing.remove(&path_buf);
-                                            }
-                                            continue;
-                                        }
-                                        
-                                        // Create temporary config for this file processing
-                                        let temp_config = AwsConfig {
-                                            api_url: api_url.clone(),
-                                            device_id: device_id.clone(),
-                                            watch_dir: watch_dir.clone(),
-                                            scan_interval_secs: Some(scan_secs),
-                                            concurrency: Some(2),
-                                        };
-                                        
-                                        // Process the file
-                                        if let Err(e) = process_file(&client, &temp_config, &path_buf) {
-                                            eprintln!("⚠️  Event-triggered upload failed: {}", e);
-                                        }
-                                        
-                                        // Remove file from processing set
-                                        {
-                                            let mut processing = processing_files.lock().unwrap();
-                                            processing.remove(&path_buf);
-                                        }
-                                    }
-                                }
-                            }
-                            _ => {} // Ignore other events
-                        }
-                    }
-                    Ok(Err(e)) => eprintln!("⚠️  File watcher error: {}", e),
-                    Err(_) => {
-                        eprintln!("⚠️  File watcher channel closed");
-                        break;
+        }
+        
+        result.ok_or_else(|| last_error.unwrap_or_else(|| anyhow!("Presign failed after all attempts")))
+    }
+}
+
+/// Object key for a direct upload, matching the layout the ingest endpoint uses
+fn direct_s3_key(device_id: &str, filename: &str) -> String {
+    format!(
+        "uploads/{}/{}_{}_{}",
+        device_id,
+        chrono::Utc::now().format("%Y-%m-%dT%H-%M-%SZ"),
+        uuid::Uuid::new_v4(),
+        filename
+    )
+}
+
+/// Virtual-hosted URL on AWS, or path-style on a custom `s3_endpoint`
+fn direct_s3_url(cfg: &AwsConfig, key: &str) -> Result<String> {
+    let bucket = cfg.bucket.as_deref().ok_or_else(|| anyhow!("bucket is not configured"))?;
+    let region = cfg.region.as_deref().ok_or_else(|| anyhow!("region is not configured"))?;
+    Ok(match &cfg.s3_endpoint {
+        Some(endpoint) => format!("{}/{}/{}", endpoint.trim_end_matches('/'), bucket, sigv4::encode_key(key)),
+        None => format!("https://{}.s3.{}.amazonaws.com/{}", bucket, region, sigv4::encode_key(key)),
+    })
+}
+
+/// SigV4 headers for a PUT of a body hashing to `payload_hash`
+fn s3_put_headers(url: &str, region: &str, payload_hash: &str, content_type: &str, creds: &sigv4::Credentials) -> Result<Vec<(String, String)>> {
+    let parsed = reqwest::Url::parse(url).with_context(|| format!("invalid S3 URL {}", url))?;
+    let host = match parsed.port() {
+        Some(port) => format!("{}:{}", parsed.host_str().unwrap_or_default(), port),
+        None => parsed.host_str().unwrap_or_default().to_string(),
+    };
+    let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut headers = vec![
+        ("content-type".to_string(), content_type.to_string()),
+        ("host".to_string(), host),
+        ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+        ("x-amz-date".to_string(), amz_date.clone()),
+    ];
+    if let Some(token) = &creds.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    let signed: Vec<(&str, &str)> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let authorization = sigv4::authorization(
+        &sigv4::SigningRequest {
+            method: "PUT",
+            canonical_uri: parsed.path(),
+            canonical_query: "",
+            headers: &signed,
+            payload_hash,
+            region,
+            service: "s3",
+            amz_date: &amz_date,
+        },
+        creds,
+    );
+
+    // reqwest sets host and content-type itself; they only needed to be signed
+    headers.retain(|(k, _)| k != "host" && k != "content-type");
+    headers.push(("authorization".to_string(), authorization));
+    Ok(headers)
+}
+
+/// Upload either a single JSON file or every JSON member of an archive
+fn process_candidate(client: &Client, cfg: &AwsConfig, path: &Path, notifier: Option<&dyn UploadNotifier>) -> Result<()> {
+    wait_for_stable_size(path, SIZE_CHECK_INTERVAL, SIZE_STABLE_TIMEOUT)?;
+    if is_pending_archive(path, cfg) {
+        process_archive(client, cfg, path, notifier).map(|_| ())
+    } else {
+        process_file(client, cfg, path, notifier)
+    }
+}
+
+/// Extract, scrub and upload the JSON members of a zip archive, then mark the archive
+/// synced. Returns the number of members uploaded. If any member fails the archive is
+/// left in place for the next scan, which skips the members already recorded as uploaded.
+fn process_archive(client: &Client, cfg: &AwsConfig, path: &Path, notifier: Option<&dyn UploadNotifier>) -> Result<usize> {
+    let members = match extract_archive_members(path, &cfg.archives) {
+        Ok(members) => members,
+        Err(e) => {
+            if !cfg.dry_run {
+                quarantine_file(path, &format!("unreadable archive: {:#}", e))?;
+            }
+            return Err(e.context("archive withheld"));
+        }
+    };
+
+    // Members upload as `<archive name without extension>/<member path>`
+    let prefix = slash_path(&Path::new(&object_name(cfg, path)).with_extension(""));
+    // Members are recorded in the archive's watch dir; the work dir is deleted afterwards
+    let (state_dir, archive_key) = state_location(cfg, path);
+    let state = read_state(&state_dir);
+    let work_dir = std::env::temp_dir().join(format!("arkangel-archive-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&work_dir).context("creating archive work dir")?;
+    let result = (|| {
+        // Scrub and policy-check every member before uploading any of them
+        let mut files = Vec::with_capacity(members.len());
+        let mut already = 0;
+        for (name, content) in members {
+            let scrubbed = pii_scrubber::scrub_conversation_json(content)
+                .map_err(|e| anyhow!("scrubbing {}: {}", name, e))?;
+            if cfg.upload_policy.enabled {
+                if let Some(reason) = cfg.upload_policy.violation(scrubbed.as_bytes()) {
+                    let reason = format!("{}: {}", name, reason);
+                    if !cfg.dry_run {
+                        quarantine_file(path, &reason)?;
                     }
+                    return Err(anyhow!("withheld by upload policy: {}", reason));
                 }
             }
-        });
+            let state_key = format!("{}!/{}", archive_key, name);
+            if state.synced.get(&state_key).is_some_and(|r| r.md5 == md5_hex(scrubbed.as_bytes())) {
+                already += 1;
+                continue;
+            }
+            let file = work_dir.join(&name);
+            fs::create_dir_all(file.parent().unwrap_or(&work_dir))?;
+            fs::write(&file, scrubbed)?;
+            let target = UploadTarget { object_name: format!("{}/{}", prefix, name), state_dir: state_dir.clone(), state_key };
+            files.push((file, target));
+        }
+        if already > 0 {
+            println!("🔍 AWS Uploader: {} member(s) of {} already uploaded, skipping", already, path.display());
+        }
 
-        // Start periodic scan thread (fallback)
-        std::thread::spawn(move || {
-            println!("🔍 AWS Uploader: Background scan thread started, scanning every {} seconds", scan_secs);
-            loop {
-                println!("🔍 AWS Uploader: Starting scan cycle...");
-                if let Err(e) = uploader.scan_and_upload() {
-                    eprintln!("⚠️  AWS Uploader error: {e:?}");
+        let member_cfg = AwsConfig { synced: SyncedOutput::default(), ..cfg.clone() };
+        for (file, target) in &files {
+            process_file_as(client, &member_cfg, file, target, notifier)?;
+        }
+        Ok(files.len())
+    })();
+    let _ = fs::remove_dir_all(&work_dir);
+
+    let uploaded = result?;
+    if cfg.dry_run {
+        println!("🧪 dry run: would upload {} file(s) from archive {}", uploaded, path.display());
+        return Ok(uploaded);
+    }
+    mark_synced(path, &cfg.synced)?;
+    println!("✅ uploaded {} file(s) from archive {}", uploaded, path.display());
+    Ok(uploaded)
+}
+
+/// Read the `.json` members of a zip, enforcing the entry-count and size limits.
+/// Declared sizes are checked up front and actual reads are capped, so a member that
+/// lies about its size cannot expand past the limit either.
+fn extract_archive_members(path: &Path, limits: &ArchiveSettings) -> Result<Vec<(String, String)>> {
+    let file = fs::File::open(path).context("opening archive")?;
+    let mut archive = zip::ZipArchive::new(file).context("reading zip")?;
+    if archive.len() > limits.max_entries {
+        return Err(anyhow!("{} entries exceeds the limit of {}", archive.len(), limits.max_entries));
+    }
+
+    let mut members = Vec::new();
+    let mut total: u64 = 0;
+    for i in 0..archive.len() {
+        let entry = archive.by_index(i)?;
+        if entry.is_dir() {
+            continue;
+        }
+        // `enclosed_name` drops members whose path would escape the archive
+        let name = match entry.enclosed_name() {
+            Some(n) => slash_path(&n),
+            None => continue,
+        };
+        if !name.ends_with(".json") {
+            continue;
+        }
+        if entry.size() > limits.max_entry_bytes {
+            return Err(anyhow!("{} expands to {} bytes, over the per-file limit", name, entry.size()));
+        }
+        let mut content = String::new();
+        entry
+            .take(limits.max_entry_bytes + 1)
+            .read_to_string(&mut content)
+            .with_context(|| format!("extracting {}", name))?;
+        let len = content.len() as u64;
+        if len > limits.max_entry_bytes {
+            return Err(anyhow!("{} expands past the per-file limit", name));
+        }
+        total += len;
+        if total > limits.max_total_bytes {
+            return Err(anyhow!("archive expands past the total limit of {} bytes", limits.max_total_bytes));
+        }
+        members.push((name, content));
+    }
+    Ok(members)
+}
+
+// -------- public interface --------
+
+/// Outcome of one `scan_and_upload` pass
+#[derive(Serialize, Debug, Default, Clone, PartialEq)]
+pub struct ScanSummary {
+    pub scanned: usize,          // candidates found across the watch dirs
+    pub uploaded: usize,         // files (or whole archives) uploaded and marked synced
+    pub would_upload: usize,     // dry run only: files that passed and would have been uploaded
+    pub failed: usize,
+    pub errors: Vec<String>,     // "<filename>: <error>" per failure
+}
+
+pub struct AwsUploader {
+    config: AwsConfig,
+    client: Client,
+    notifier: Option<Arc<dyn UploadNotifier>>,
+}
+
+impl AwsUploader {
+    pub fn new() -> Result<Self> {
+        let config = AwsConfig::load()?;
+        for dir in config.watch_dir.paths() {
+            fs::create_dir_all(dir).ok();
+        }
+
+        let client = http_client(&config)?;
+        Ok(Self { config, client, notifier: None })
+    }
+
+    /// Report upload progress to `notifier`
+    pub fn with_notifier(mut self, notifier: Arc<dyn UploadNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn scan_and_upload(&self) -> Result<ScanSummary> {
+        // gather candidate files from every watch dir, skipping any the server already confirmed
+        let mut files: Vec<PathBuf> = Vec::new();
+        for watch_dir in self.config.watch_dir.paths() {
+            println!("🔍 AWS Uploader: Starting scan of directory: {}", watch_dir.display());
+            let mut states: HashMap<PathBuf, UploadState> = HashMap::new();
+            let depth = if self.config.recursive { usize::MAX } else { 1 };
+            let walk = WalkDir::new(watch_dir).max_depth(depth).into_iter().filter_entry(|e| {
+                e.depth() == 0 || !e.file_type().is_dir() || !self.is_excluded_dir(e.file_name())
+            });
+            for entry in walk {
+                let entry = match entry { Ok(e) => e, Err(_) => continue };
+                let p = entry.path().to_path_buf();
+                let (state_dir, state_key) = state_location(&self.config, &p);
+                let state = states.entry(state_dir).or_insert_with_key(|dir| self.load_state(dir));
+                if p.is_file() && is_complete_json(&p, &self.config) && state.is_synced(&state_key, &p) {
+                    println!("🔍 AWS Uploader: Already uploaded, skipping: {}", p.display());
+                    if self.config.dry_run {
+                        continue;
+                    }
+                    if let Err(e) = mark_synced(&p, &self.config.synced) {
+                        eprintln!("⚠️  failed marking {} synced: {e:#}", p.display());
+                    }
+                    continue;
                 }
-                println!("🔍 AWS Uploader: Scan cycle completed, sleeping for {} seconds", scan_secs);
-                thread::sleep(Duration::from_secs(scan_secs));
+                if p.is_file() && is_upload_candidate(&p, &self.config) {
+                    println!("🔍 AWS Uploader: Found file: {}", p.display());
+                    files.push(p);
+                }
+            }
+        }
+
+        if !files.is_empty() {
+            println!("🔍 AWS Uploader: Found {} file(s) to upload", files.len());
+        } else {
+            println!("🔍 AWS Uploader: No files found to upload");
+        }
+
+        // Up to `concurrency` workers pull from a shared queue; a failure only affects its own file
+        let summary = Mutex::new(ScanSummary { scanned: files.len(), ..ScanSummary::default() });
+        let workers = self.config.concurrency.unwrap_or(2).clamp(1, files.len().max(1));
+        let queue = Mutex::new(files.into_iter());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let next = queue.lock().unwrap().next();
+                    let Some(p) = next else { break };
+                    // Check if file still exists and is still a valid JSON (not already processed)
+                    if p.exists() && is_upload_candidate(&p, &self.config) {
+                        let outcome = process_candidate(&self.client, &self.config, &p, self.notifier.as_deref());
+                        let mut summary = summary.lock().unwrap();
+                        match outcome {
+                            Ok(()) if self.config.dry_run => summary.would_upload += 1,
+                            Ok(()) => summary.uploaded += 1,
+                            Err(e) => {
+                                eprintln!("⚠️  failed processing {}: {e:?}", p.display());
+                                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                                summary.failed += 1;
+                                summary.errors.push(format!("{}: {:#}", name, e));
+                            }
+                        }
+                    } else {
+                        println!("🔍 AWS Uploader: Skipping file (no longer valid): {}", p.display());
+                    }
+                });
             }
         });
 
-        Ok(())
+        let summary = summary.into_inner().unwrap();
+        if self.config.dry_run {
+            println!("🧪 AWS Uploader: Dry run: would upload {} file(s), {} would fail", summary.would_upload, summary.failed);
+            return Ok(summary);
+        }
+        println!(
+            "🔍 AWS Uploader: Scan finished: {} found, {} uploaded, {} failed",
+            summary.scanned, summary.uploaded, summary.failed
+        );
+        Ok(summary)
+    }
+
+    /// Folders a recursive scan never descends into: unscrubbed copies, quarantined files
+    /// and the synced-file archive
+    fn is_excluded_dir(&self, name: &std::ffi::OsStr) -> bool {
+        name == memory_store::RAW_DIR_NAME
+            || name == QUARANTINE_DIR_NAME
+            || (self.config.synced.mode == SyncedMode::Archive && name == self.config.synced.archive_dir.as_str())
+    }
+
+    /// Uploads from `watch_dir` confirmed in earlier runs (empty if the state file is
+    /// missing or unreadable)
+    pub fn load_state(&self, watch_dir: &Path) -> UploadState {
+        read_state(watch_dir)
+    }
+
+    /// Forget the recorded upload of the file at `path`, or of every file when `path` is
+    /// None, so the next scan uploads them again. Returns how many records were dropped.
+    pub fn forget_uploads(&self, path: Option<&Path>) -> Result<usize> {
+        // Held from read to write, so an upload recorded meanwhile isn't lost
+        let _guard = state_lock().lock().unwrap();
+        let targets: Vec<(PathBuf, Option<String>)> = match path {
+            Some(path) => {
+                let (dir, key) = state_location(&self.config, path);
+                vec![(dir, Some(key))]
+            }
+            None => self.config.watch_dir.paths().map(|dir| (dir.to_path_buf(), None)).collect(),
+        };
+        let mut forgotten = 0;
+        for (dir, key) in targets {
+            let mut state = read_state(&dir);
+            let before = state.synced.len();
+            match &key {
+                // An archive's members are recorded under `<archive>!/<member>`
+                Some(key) => state.synced.retain(|k, _| k != key && !k.starts_with(&format!("{}!/", key))),
+                None => state.synced.clear(),
+            }
+            if state.synced.len() != before {
+                write_state(&dir, &state)?;
+                forgotten += before - state.synced.len();
+            }
+        }
+        Ok(forgotten)
+    }
+
+    pub fn start_background_uploader(notifier: Arc<dyn UploadNotifier>) -> Result<()> {
+        let runtime = Self::start_with(AwsUploader::new()?.with_notifier(notifier));
+        let _ = uploader_runtime().set(runtime);
+        Ok(())
+    }
+
+    /// Start the watcher and periodic scan threads for `uploader`, returning the
+    /// handle used to control them at runtime
+    fn start_with(mut uploader: AwsUploader) -> Arc<UploaderRuntime> {
+        let config = Arc::new(RwLock::new(uploader.config.clone()));
+        let client = Arc::new(RwLock::new(uploader.client.clone()));
+        let notifier = uploader.notifier.clone();
+
+        let (tx, rx) = channel();
+        let runtime = Arc::new(UploaderRuntime {
+            config: config.clone(),
+            client: client.clone(),
+            events: Mutex::new(tx),
+            watcher: Mutex::new(None),
+        });
+        if let Err(e) = runtime.set_watcher_enabled(true) {
+            eprintln!("⚠️  Failed to start file watcher: {e:#}");
+        }
+
+        // Start file event thread; it keeps running while the watcher is disabled
+        let (event_config, event_client) = (config.clone(), client.clone());
+        std::thread::spawn(move || {
+            println!("🔍 AWS Uploader: File watcher thread started");
+            
+            // Tracks files in progress and recently synced ones to prevent duplicates
+            let events = FileEvents::new(POST_SYNC_COOLDOWN);
+            run_event_loop(&rx, &event_config, |path| {
+                let config = event_config.read().unwrap().clone();
+                let client = event_client.read().unwrap().clone();
+                events.handle(&client, &config, path, notifier.as_deref());
+            });
+        });
+
+        // Start periodic scan thread (fallback)
+        std::thread::spawn(move || {
+            println!("🔍 AWS Uploader: Background scan thread started");
+            run_scan_loop(&config, thread::sleep, |current| {
+                uploader.config = current.clone();
+                uploader.client = client.read().unwrap().clone();
+                println!("🔍 AWS Uploader: Starting scan cycle...");
+                if let Err(e) = uploader.scan_and_upload() {
+                    eprintln!("⚠️  AWS Uploader error: {e:?}");
+                }
+                ControlFlow::Continue(())
+            });
+        });
+
+        runtime
+    }
+}
+
+/// Call `cycle` with the current config, then `sleep` for its `scan_interval_secs`, until
+/// `cycle` breaks. The config is re-read before every cycle, so a reload applies to the
+/// next scan.
+fn run_scan_loop<S, F>(config: &RwLock<AwsConfig>, mut sleep: S, mut cycle: F)
+where
+    S: FnMut(Duration),
+    F: FnMut(&AwsConfig) -> ControlFlow<()>,
+{
+    loop {
+        let current = config.read().unwrap().clone();
+        if cycle(&current).is_break() {
+            return;
+        }
+        let scan_secs = current.scan_interval_secs.unwrap_or(60);
+        println!("🔍 AWS Uploader: Scan cycle completed, sleeping for {} seconds", scan_secs);
+        sleep(Duration::from_secs(scan_secs));
+    }
+}
+
+const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// Coalesces watcher events per path and releases a path only once its size has stayed
+/// the same for the whole debounce window, so chunked writes upload once and complete
+struct Debouncer {
+    window: Duration,
+    pending: HashMap<PathBuf, (Option<u64>, Instant)>, // last seen size, unchanged since
+}
+
+impl Debouncer {
+    fn new(window: Duration) -> Self {
+        Self { window, pending: HashMap::new() }
+    }
+
+    fn touch(&mut self, path: &Path) {
+        let size = fs::metadata(path).ok().map(|m| m.len());
+        self.pending.insert(path.to_path_buf(), (size, Instant::now()));
+    }
+
+    /// Paths that have settled; files that disappeared in the meantime are dropped
+    fn ready(&mut self) -> Vec<PathBuf> {
+        let window = self.window;
+        let mut ready = Vec::new();
+        self.pending.retain(|path, (size, since)| {
+            let current = match fs::metadata(path) {
+                Ok(m) => Some(m.len()),
+                Err(_) => return false,
+            };
+            if current != *size {
+                *size = current;
+                *since = Instant::now();
+                true
+            } else if since.elapsed() >= window {
+                ready.push(path.clone());
+                false
+            } else {
+                true
+            }
+        });
+        ready
+    }
+}
+
+/// Feed watcher events through a `Debouncer`, calling `handle` once per settled file.
+/// The window follows the config's `debounce_ms`, so a reload applies to the next event.
+/// Returns when the event channel closes.
+fn run_event_loop<F>(rx: &std::sync::mpsc::Receiver<notify::Result<notify::Event>>, config: &RwLock<AwsConfig>, mut handle: F)
+where
+    F: FnMut(&Path),
+{
+    let mut debouncer = Debouncer::new(Duration::ZERO);
+    loop {
+        debouncer.window = Duration::from_millis(config.read().unwrap().debounce_ms.unwrap_or(DEFAULT_DEBOUNCE_MS));
+        let tick = (debouncer.window / 4).clamp(Duration::from_millis(25), Duration::from_millis(250));
+        match rx.recv_timeout(tick) {
+            Ok(Ok(event)) => {
+                if let EventKind::Create(_) | EventKind::Modify(_) = event.kind {
+                    let config = config.read().unwrap();
+                    for path in event.paths.iter().filter(|p| is_upload_candidate(p, &config)) {
+                        debouncer.touch(path);
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("⚠️  File watcher error: {}", e),
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {}
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                eprintln!("⚠️  File watcher channel closed");
+                break;
+            }
+        }
+        for path in debouncer.ready() {
+            handle(&path);
+        }
+    }
+}
+
+/// How long a just-synced path ignores further watcher events. Editors that save via
+/// rename often touch the file again right after, which would otherwise re-upload it.
+const POST_SYNC_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// De-duplicates watcher events: skips files already being processed and files
+/// synced within the cooldown window
+struct FileEvents {
+    processing: Mutex<HashSet<PathBuf>>,
+    recently_synced: Mutex<HashMap<PathBuf, Instant>>,
+    cooldown: Duration,
+}
+
+impl FileEvents {
+    fn new(cooldown: Duration) -> Self {
+        Self { processing: Mutex::new(HashSet::new()), recently_synced: Mutex::new(HashMap::new()), cooldown }
+    }
+
+    fn handle(&self, client: &Client, config: &AwsConfig, path: &Path, notifier: Option<&dyn UploadNotifier>) {
+        let path_buf = path.to_path_buf();
+
+        // Skip trailing events for a file that was just uploaded
+        {
+            let mut recent = self.recently_synced.lock().unwrap();
+            recent.retain(|_, synced_at| synced_at.elapsed() < self.cooldown);
+            if recent.contains_key(&path_buf) {
+                println!("🔍 AWS Uploader: Skipping recently synced file: {}", path_buf.display());
+                return;
+            }
+        }
+        
+        // Check if file is already being processed
+        {
+            let mut processing = self.processing.lock().unwrap();
+            if processing.contains(&path_buf) {
+                println!("🔍 AWS Uploader: Skipping already processing file: {}", path_buf.display());
+                return;
+            }
+            // Mark file as being processed
+            processing.insert(path_buf.clone());
+        }
+        
+        println!("🔍 AWS Uploader: File event detected: {}", path_buf.display());
+        
+        // Double-check file still exists and is valid before processing
+        if !path_buf.exists() || !is_upload_candidate(&path_buf, config) {
+            println!("🔍 AWS Uploader: File no longer valid, skipping: {}", path_buf.display());
+            self.processing.lock().unwrap().remove(&path_buf);
+            return;
+        }
+        
+        // Process the file
+        match process_candidate(client, config, &path_buf, notifier) {
+            Ok(()) => {
+                self.recently_synced.lock().unwrap().insert(path_buf.clone(), Instant::now());
+            }
+            Err(e) => eprintln!("⚠️  Event-triggered upload failed: {}", e),
+        }
+        
+        // Remove file from processing set
+        self.processing.lock().unwrap().remove(&path_buf);
+    }
+}
+
+/// Runtime control over a started background uploader. The periodic scan always runs;
+/// the live `notify` watcher can be torn down and recreated on demand.
+pub struct UploaderRuntime {
+    config: Arc<RwLock<AwsConfig>>,    // shared with the scan and event threads
+    client: Arc<RwLock<Client>>,       // built from `config`; replaced with it on reload
+    events: Mutex<std::sync::mpsc::Sender<notify::Result<notify::Event>>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl UploaderRuntime {
+    /// Create or drop the live watcher. Disabling leaves uploads to the periodic scan.
+    pub fn set_watcher_enabled(&self, enabled: bool) -> Result<()> {
+        let mut watcher = self.watcher.lock().unwrap();
+        if !enabled {
+            if watcher.take().is_some() {
+                println!("🔍 AWS Uploader: File watcher disabled; relying on periodic scans");
+            }
+            return Ok(());
+        }
+        if watcher.is_some() {
+            return Ok(());
+        }
+        let tx = self.events.lock().unwrap().clone();
+        let mut w: RecommendedWatcher = notify::recommended_watcher(move |res| {
+            let _ = tx.send(res);
+        })
+        .context("creating file watcher")?;
+        let config = self.config.read().unwrap();
+        let mode = if config.recursive { RecursiveMode::Recursive } else { RecursiveMode::NonRecursive };
+        for dir in config.watch_dir.paths() {
+            w.watch(dir, mode)
+                .with_context(|| format!("watching directory {}", dir.display()))?;
+            println!("🔍 AWS Uploader: Watching directory: {}", dir.display());
+        }
+        *watcher = Some(w);
+        Ok(())
+    }
+
+    pub fn is_watcher_enabled(&self) -> bool {
+        self.watcher.lock().unwrap().is_some()
+    }
+
+    /// Swap in `config`, along with an HTTP client rebuilt for its TLS settings. Scans use
+    /// them from their next cycle and the watcher from its next event (debounce included);
+    /// an enabled watcher is recreated so it follows the new directories. A config whose
+    /// client can't be built is rejected and the running one kept. The notifier isn't part
+    /// of the config and stays as started.
+    pub fn reload(&self, config: AwsConfig) -> Result<()> {
+        let client = http_client(&config)?;
+        for dir in config.watch_dir.paths() {
+            fs::create_dir_all(dir).ok();
+        }
+        *self.client.write().unwrap() = client;
+        *self.config.write().unwrap() = config;
+        if self.is_watcher_enabled() {
+            self.set_watcher_enabled(false)?;
+            self.set_watcher_enabled(true)?;
+        }
+        println!("🔍 AWS Uploader: Config reloaded");
+        Ok(())
+    }
+}
+
+/// HTTP client for `config`: its TLS settings and sensible timeouts
+fn http_client(config: &AwsConfig) -> Result<Client> {
+    config.tls.client_builder()?
+        .timeout(Duration::from_secs(20))
+        .build()
+        .context("building http client")
+}
+
+/// Handle to the uploader started by `start_background_uploader`, if any
+pub fn uploader_runtime() -> &'static OnceLock<Arc<UploaderRuntime>> {
+    static RUNTIME: OnceLock<Arc<UploaderRuntime>> = OnceLock::new();
+    &RUNTIME
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arkangel-aws-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn test_config(api_url: &str, watch_dir: &Path) -> AwsConfig {
+        AwsConfig {
+            backend: UploadBackend::Presign,
+            api_url: api_url.to_string(),
+            bucket: None,
+            region: None,
+            s3_endpoint: None,
+            device_id: "test-device".to_string(),
+            watch_dir: WatchDirs::One(watch_dir.to_string_lossy().to_string()),
+            scan_interval_secs: Some(60),
+            concurrency: Some(2),
+            debounce_ms: Some(100),
+            upload_policy: UploadPolicy::default(),
+            tls: TlsSettings::default(),
+            synced: SyncedOutput::default(),
+            archives: ArchiveSettings::default(),
+            dry_run: false,
+            recursive: false,
+        }
+    }
+
+    /// Spawn a local HTTP server; each request is handed to `handler` on its own thread.
+    /// Returns the base URL (e.g. http://127.0.0.1:1234).
+    fn spawn_mock<F>(handler: F) -> String
+    where
+        F: Fn(tiny_http::Request) + Send + Sync + 'static,
+    {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+        let handler = Arc::new(handler);
+        thread::spawn(move || {
+            for request in server.incoming_requests() {
+                let handler = handler.clone();
+                thread::spawn(move || handler(request));
+            }
+        });
+        base
+    }
+
+    /// Responds to the presign POST with a PUT URL on the same server
+    fn presign_response(base: &str, request: tiny_http::Request) {
+        let body = format!(r#"{{"url":"{}/put","key":"test-device/upload.json"}}"#, base);
+        let _ = request.respond(tiny_http::Response::from_string(body));
+    }
+
+    /// Spawn a presign backend: each POST is answered by `presign_response` and its
+    /// filename recorded; every other request (the PUT) goes to `on_put`. Returns the base
+    /// URL and the filenames presigned so far.
+    fn mock_presign_server<F>(on_put: F) -> (String, Arc<Mutex<Vec<String>>>)
+    where
+        F: Fn(tiny_http::Request) + Send + Sync + 'static,
+    {
+        let presigned = Arc::new(Mutex::new(Vec::new()));
+        let names = presigned.clone();
+        let base = Arc::new(OnceLock::<String>::new());
+        let server_base = base.clone();
+        let url = spawn_mock(move |mut request| {
+            if request.method() == &tiny_http::Method::Post {
+                let mut body = String::new();
+                request.as_reader().read_to_string(&mut body).unwrap();
+                let req: serde_json::Value = serde_json::from_str(&body).unwrap();
+                names.lock().unwrap().push(req["filename"].as_str().unwrap().to_string());
+                presign_response(server_base.get().unwrap(), request);
+            } else {
+                on_put(request);
+            }
+        });
+        base.set(url.clone()).unwrap();
+        (url, presigned)
+    }
+
+    /// Write a zip archive at `path` holding `entries` as (name, contents)
+    fn write_zip(path: &Path, entries: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, body) in entries {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            std::io::Write::write_all(&mut zip, body.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /// Spawn a local HTTPS server presenting tests/fixtures/tls/server.cert.pem that
+    /// answers every request with an empty 200. Returns the base URL.
+    fn spawn_tls_mock() -> String {
+        let identity = native_tls::Identity::from_pkcs8(
+            include_bytes!("../tests/fixtures/tls/server.cert.pem"),
+            include_bytes!("../tests/fixtures/tls/server.key.pem"),
+        )
+        .unwrap();
+        let acceptor = native_tls::TlsAcceptor::new(identity).unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("https://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let acceptor = acceptor.clone();
+                thread::spawn(move || {
+                    use std::io::Write;
+                    if let Ok(mut tls) = acceptor.accept(stream) {
+                        let mut buf = [0u8; 8192];
+                        let _ = tls.read(&mut buf);
+                        let _ = tls.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                    }
+                });
+            }
+        });
+        base
+    }
+
+    fn pinned_client(dir: &Path, pem: &[u8]) -> Client {
+        let pin = dir.join("pin.pem");
+        fs::write(&pin, pem).unwrap();
+        let tls = TlsSettings { min_version: Some("1.2".to_string()), pinned_certs: vec![pin.to_string_lossy().to_string()] };
+        tls.client_builder().unwrap().build().unwrap()
+    }
+
+    #[test]
+    fn test_raw_dir_is_never_uploaded() {
+        let cfg = test_config("http://127.0.0.1:9/ingest/new", Path::new("/home/raw/project"));
+        assert!(is_complete_json(Path::new("/home/raw/project/conv.json"), &cfg), "raw/ above the watch dir is fine");
+        assert!(!is_complete_json(Path::new("/home/raw/project/raw/conv.json"), &cfg));
+        assert!(!is_complete_json(Path::new("/home/raw/project/quarantine/conv.json"), &cfg));
+        assert!(!is_complete_json(Path::new("project/raw/conv.json"), &cfg));
+    }
+
+    #[test]
+    fn test_cancel_in_flight_upload_leaves_file_pending() {
+        let dir = temp_dir();
+        let file = dir.join("slow.json");
+        let twin = dir.join("nested").join("slow.json");
+        fs::create_dir_all(twin.parent().unwrap()).unwrap();
+        fs::write(&file, r#"{"content":"hello"}"#).unwrap();
+
+        let (presign_seen_tx, presign_seen_rx) = channel();
+        let (resume_tx, resume_rx) = channel::<()>();
+        let (presign_seen_tx, resume_rx) = (Mutex::new(presign_seen_tx), Mutex::new(resume_rx));
+        let puts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let put_count = puts.clone();
+        let url = spawn_mock(move |request| {
+            if request.method() == &tiny_http::Method::Post {
+                // Hold the presign response until the test has cancelled
+                presign_seen_tx.lock().unwrap().send(()).unwrap();
+                let _ = resume_rx.lock().unwrap().recv();
+                presign_response("http://127.0.0.1:9", request);
+            } else {
+                put_count.fetch_add(1, Ordering::SeqCst);
+                let _ = request.respond(tiny_http::Response::empty(200));
+            }
+        });
+
+        let cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        let path = file.clone();
+        let worker = thread::spawn(move || process_file(&Client::new(), &cfg, &path, None));
+
+        presign_seen_rx.recv_timeout(Duration::from_secs(10)).expect("presign never started");
+        assert!(!cancel_upload("slow.json"), "keyed by full path, not file name");
+        assert!(!cancel_upload(&twin.to_string_lossy()), "a same-named file elsewhere is not affected");
+        assert!(cancel_upload(&file.to_string_lossy()));
+        resume_tx.send(()).unwrap();
+
+        let result = worker.join().unwrap();
+        assert!(result.unwrap_err().is::<UploadCancelled>());
+        assert_eq!(puts.load(Ordering::SeqCst), 0, "nothing is sent after cancelling");
+        assert!(file.exists(), "cancelled file must stay pending");
+        assert!(!dir.join("slow.json.synced").exists());
+        assert!(!cancel_upload(&file.to_string_lossy()), "state is released after the upload ends");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_cancel_aborts_body_mid_stream() {
+        let state = Arc::new(AtomicU8::new(RUNNING));
+        let mut body = CancellableBody { bytes: Arc::new(b"{\"pad\":\"xxxx\"}".to_vec()), pos: 0, state: state.clone() };
+        let mut chunk = [0u8; 4];
+        assert_eq!(body.read(&mut chunk).unwrap(), 4);
+        assert_eq!(state.compare_exchange(RUNNING, CANCELLED, Ordering::SeqCst, Ordering::SeqCst), Ok(RUNNING));
+        let err = body.read(&mut chunk).unwrap_err();
+        assert!(err.get_ref().is_some_and(|e| e.is::<UploadCancelled>()));
+        assert_eq!(body.pos, 4, "the rest of the body is never handed over");
+    }
+
+    #[test]
+    fn test_cancel_refused_once_body_is_sent() {
+        let state = Arc::new(AtomicU8::new(RUNNING));
+        let mut body = CancellableBody { bytes: Arc::new(b"{}".to_vec()), pos: 0, state: state.clone() };
+        let mut sent = Vec::new();
+        body.read_to_end(&mut sent).unwrap();
+        assert_eq!(sent, b"{}");
+        assert_eq!(state.load(Ordering::SeqCst), COMMITTED);
+
+        let path = temp_dir().join("sent.json");
+        let upload = InFlightUpload::register(&path);
+        upload.state.store(COMMITTED, Ordering::SeqCst);
+        assert!(!cancel_upload(&path.to_string_lossy()), "the object may already exist");
+        assert!(upload.check().is_ok());
+    }
+
+    struct ChannelNotifier(std::sync::mpsc::Sender<UploadEvent>);
+
+    impl UploadNotifier for ChannelNotifier {
+        fn upload_event(&self, event: &UploadEvent) -> Result<()> {
+            self.0.send(event.clone())?;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_upload_events_fire_in_order() {
+        let dir = temp_dir();
+        let (url, _) = mock_presign_server(move |request| {
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        let (tx, rx) = channel();
+        let notifier = ChannelNotifier(tx);
+        let good = dir.join("good.json");
+        fs::write(&good, r#"{"a":1}"#).unwrap();
+        process_file(&Client::new(), &test_config(&format!("{}/ingest/new", url), &dir), &good, Some(&notifier)).unwrap();
+
+        // Withheld by the upload policy, so it fails without any network retries
+        let bad = dir.join("bad.json");
+        fs::write(&bad, r#"{"content":"reach me at john@example.com"}"#).unwrap();
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.upload_policy.enabled = true;
+        assert!(process_file(&Client::new(), &cfg, &bad, Some(&notifier)).is_err());
+
+        let events: Vec<UploadEvent> = rx.try_iter().collect();
+        assert_eq!(events.len(), 4, "{:?}", events);
+        assert_eq!(events[0], UploadEvent::Started { filename: "good.json".to_string(), path: good.to_string_lossy().to_string() });
+        assert_eq!(events[1], UploadEvent::Succeeded { filename: "good.json".to_string(), key: "test-device/upload.json".to_string() });
+        assert_eq!(events[2], UploadEvent::Started { filename: "bad.json".to_string(), path: bad.to_string_lossy().to_string() });
+        match &events[3] {
+            UploadEvent::Failed { filename, error } => {
+                assert_eq!(filename, "bad.json");
+                assert!(error.contains("upload policy"), "{}", error);
+            }
+            other => panic!("expected a failure event, got {:?}", other),
+        }
+        assert_eq!(events[3].name(), UPLOAD_FAILED_EVENT);
+        assert_eq!(serde_json::to_value(&events[1]).unwrap(), serde_json::json!({"filename": "good.json", "key": "test-device/upload.json"}));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_policy_violation_is_quarantined_without_upload() {
+        let dir = temp_dir();
+        let file = dir.join("leaky.json");
+        fs::write(&file, r#"{"content":"reach me at john@example.com"}"#).unwrap();
+
+        let requests = Arc::new(Mutex::new(0));
+        let counter = requests.clone();
+        let url = spawn_mock(move |request| {
+            *counter.lock().unwrap() += 1;
+            let _ = request.respond(tiny_http::Response::empty(500));
+        });
+
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.upload_policy.enabled = true;
+
+        let err = process_file(&Client::new(), &cfg, &file, None).unwrap_err();
+        assert!(err.to_string().contains("upload policy"));
+        assert!(!file.exists());
+        assert!(dir.join(QUARANTINE_DIR_NAME).join("leaky.json").exists());
+        assert_eq!(*requests.lock().unwrap(), 0, "no HTTP calls for withheld files");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_policy_category_limits_from_toml() {
+        let cfg: AwsConfig = toml::from_str(r#"
+            api_url = "https://example.com/ingest/new"
+            device_id = "dev001"
+            watch_dir = "memory"
+
+            [upload_policy]
+            enabled = true
+            category_limits = { date = 1 }
+        "#).unwrap();
+        assert!(cfg.upload_policy.violation(br#"{"a":"on 2023-01-15"}"#).is_none());
+        assert!(cfg.upload_policy.violation(br#"{"a":"on 2023-01-15 and 2023-02-01"}"#).is_some());
+    }
+
+    #[test]
+    fn test_watch_dir_accepts_string_or_list() {
+        let one: AwsConfig = toml::from_str(r#"
+            api_url = "https://example.com/ingest/new"
+            device_id = "dev001"
+            watch_dir = "memory"
+        "#).unwrap();
+        assert_eq!(one.watch_dir, WatchDirs::One("memory".to_string()));
+
+        let many: AwsConfig = toml::from_str(r#"
+            api_url = "https://example.com/ingest/new"
+            device_id = "dev001"
+            watch_dir = ["memory", "/data/uploads"]
+        "#).unwrap();
+        assert_eq!(many.watch_dir.all(), ["memory".to_string(), "/data/uploads".to_string()]);
+
+        let root = temp_dir();
+        let config_path = root.join("src-tauri").join("config.toml");
+        let empty = "api_url = \"https://example.com\"\ndevice_id = \"dev001\"\nwatch_dir = []";
+        assert!(AwsConfig::from_toml(empty, &config_path).is_err());
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_scan_uploads_from_every_watch_dir() {
+        let (first, second) = (temp_dir(), temp_dir());
+        let (url, _) = mock_presign_server(move |request| {
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        fs::write(first.join("conversation.json"), r#"{"a":1}"#).unwrap();
+        fs::write(second.join("document.json"), r#"{"b":2}"#).unwrap();
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &first);
+        cfg.watch_dir = WatchDirs::Many(vec![
+            first.to_string_lossy().to_string(),
+            second.to_string_lossy().to_string(),
+        ]);
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        uploader.scan_and_upload().unwrap();
+
+        assert!(first.join("conversation.json.synced").exists());
+        assert!(second.join("document.json.synced").exists());
+        assert!(uploader.load_state(&second).synced.contains_key("document.json"));
+        fs::remove_dir_all(&first).ok();
+        fs::remove_dir_all(&second).ok();
+    }
+
+    #[test]
+    fn test_scan_summary_counts_uploads_and_failures() {
+        let dir = temp_dir();
+        let (url, _) = mock_presign_server(move |request| {
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        fs::write(dir.join("good.json"), r#"{"content":"hello"}"#).unwrap();
+        fs::write(dir.join("bad.json"), r#"{"content":"reach me at john@example.com"}"#).unwrap();
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.upload_policy.enabled = true;
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        let summary = uploader.scan_and_upload().unwrap();
+
+        assert_eq!((summary.scanned, summary.uploaded, summary.failed), (2, 1, 1));
+        assert_eq!(summary.errors.len(), 1);
+        assert!(summary.errors[0].starts_with("bad.json: withheld by upload policy"), "{:?}", summary.errors);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_dry_run_makes_no_requests_and_renames_nothing() {
+        let dir = temp_dir();
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let seen = requests.clone();
+        let url = spawn_mock(move |request| {
+            seen.fetch_add(1, Ordering::SeqCst);
+            let _ = request.respond(tiny_http::Response::empty(500));
+        });
+
+        fs::write(dir.join("one.json"), r#"{"a":1}"#).unwrap();
+        fs::write(dir.join("two.json"), r#"{"b":2}"#).unwrap();
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.dry_run = true;
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        let summary = uploader.scan_and_upload().unwrap();
+
+        assert_eq!((summary.scanned, summary.would_upload, summary.uploaded, summary.failed), (2, 2, 0, 0));
+        assert_eq!(requests.load(Ordering::SeqCst), 0, "no HTTP calls in dry run");
+        assert!(dir.join("one.json").exists() && dir.join("two.json").exists());
+        let synced = fs::read_dir(&dir).unwrap().flatten().filter(|e| e.file_name().to_string_lossy().ends_with(".synced")).count();
+        assert_eq!(synced, 0);
+        assert!(uploader.load_state(&dir).synced.is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_content_type_from_extension_or_bytes() {
+        assert_eq!(content_type_for("conversation.json", b"{}"), "application/json");
+        assert_eq!(content_type_for("notes.TXT", b"hello"), "text/plain; charset=utf-8");
+        assert_eq!(content_type_for("report.pdf", b"%PDF-1.4"), "application/pdf");
+        assert_eq!(content_type_for("bundle.json.gz", &[0x1f, 0x8b, 0x08]), "application/gzip");
+        assert_eq!(content_type_for("export", b"%PDF-1.7"), "application/pdf", "sniffed without an extension");
+        assert_eq!(content_type_for("blob.bin", &[0, 1, 2]), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_recursive_scan_keeps_relative_paths() {
+        let dir = temp_dir();
+        let (url, presigned) = mock_presign_server(move |request| {
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        let nested = dir.join("2024").join("june");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(nested.join("chat.json"), r#"{"a":1}"#).unwrap();
+        fs::write(nested.join("old.json.synced"), r#"{"b":2}"#).unwrap();
+        fs::create_dir_all(dir.join(QUARANTINE_DIR_NAME)).unwrap();
+        fs::write(dir.join(QUARANTINE_DIR_NAME).join("held.json"), r#"{"c":3}"#).unwrap();
+
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.recursive = true;
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        let summary = uploader.scan_and_upload().unwrap();
+
+        assert_eq!((summary.scanned, summary.uploaded), (1, 1), "{:?}", summary);
+        assert_eq!(*presigned.lock().unwrap(), vec!["2024/june/chat.json"]);
+        assert!(nested.join("chat.json.synced").exists());
+        assert!(uploader.load_state(&dir).synced.contains_key("2024/june/chat.json"));
+
+        // A second pass finds nothing new at any depth
+        assert_eq!(uploader.scan_and_upload().unwrap().scanned, 0);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_stable_size_waits_for_writer_to_finish() {
+        let dir = temp_dir();
+        let file = dir.join("growing.json");
+        fs::write(&file, "{").unwrap();
+        let writer_path = file.clone();
+        let writer = thread::spawn(move || {
+            for _ in 0..5 {
+                thread::sleep(Duration::from_millis(60));
+                let mut f = fs::OpenOptions::new().append(true).open(&writer_path).unwrap();
+                std::io::Write::write_all(&mut f, b"\"x\":1,").unwrap();
+            }
+        });
+
+        let started = Instant::now();
+        let size = wait_for_stable_size(&file, Duration::from_millis(150), Duration::from_secs(5)).unwrap();
+        writer.join().unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300), "returned while the file was still growing");
+        assert_eq!(size, fs::metadata(&file).unwrap().len());
+
+        let err = wait_for_stable_size(&dir.join("missing.json"), Duration::from_millis(1), Duration::from_secs(1));
+        assert!(err.is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_direct_s3_backend_signs_put_to_bucket() {
+        let dir = temp_dir();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let url = spawn_mock(move |request| {
+            let auth = request
+                .headers()
+                .iter()
+                .find(|h| h.field.equiv("authorization"))
+                .map(|h| h.value.to_string())
+                .unwrap_or_default();
+            recorder.lock().unwrap().push((request.method().to_string(), request.url().to_string(), auth));
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        let credentials = sigv4::Credentials {
+            access_key_id: "AKIDTEST".to_string(),
+            secret_access_key: "secret".to_string(),
+            session_token: None,
+        };
+        let file = dir.join("conv.json");
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        let mut cfg = test_config("", &dir);
+        cfg.backend = UploadBackend::DirectS3;
+        cfg.bucket = Some("my-bucket".to_string());
+        cfg.region = Some("us-west-2".to_string());
+        cfg.s3_endpoint = Some(url);
+        let target = UploadTarget::for_file(&cfg, &file);
+        let key = upload_file_signed(&Client::new(), &cfg, &file, "conv.json", &target, Some(credentials)).unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert_eq!(seen.len(), 1, "no presign call for direct uploads");
+        let (method, path, auth) = &seen[0];
+        assert_eq!(method, "PUT");
+        assert_eq!(path, &format!("/my-bucket/{}", key));
+        assert!(key.starts_with("uploads/test-device/") && key.ends_with("_conv.json"), "{}", key);
+        assert!(auth.starts_with("AWS4-HMAC-SHA256 Credential=AKIDTEST/"), "{}", auth);
+        assert!(auth.contains("/us-west-2/s3/aws4_request, SignedHeaders=content-type;host;x-amz-content-sha256;x-amz-date,"));
+        assert!(dir.join("conv.json.synced").exists());
+
+        let missing_bucket = "backend = \"direct_s3\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"\nregion = \"us-west-2\"";
+        assert!(AwsConfig::from_toml(missing_bucket, &dir.join("src-tauri").join("config.toml")).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_config_validation_names_bad_field() {
+        let config_path = temp_dir().join("src-tauri").join("config.toml");
+        let error = |text: &str| AwsConfig::from_toml(text, &config_path).unwrap_err().to_string();
+
+        let no_device = "api_url = \"https://example.com/ingest\"\nwatch_dir = \"memory\"";
+        assert!(error(no_device).contains("`device_id` is missing"), "{}", error(no_device));
+        let no_watch_dir = "api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"";
+        assert!(error(no_watch_dir).contains("`watch_dir` is missing"), "{}", error(no_watch_dir));
+
+        let empty_device = "api_url = \"https://example.com/ingest\"\ndevice_id = \" \"\nwatch_dir = \"memory\"";
+        assert!(error(empty_device).contains("`device_id` is empty"), "{}", error(empty_device));
+        let empty_watch_dir = "api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = [\"memory\", \"\"]";
+        assert!(error(empty_watch_dir).contains("`watch_dir` contains an empty path"), "{}", error(empty_watch_dir));
+
+        let no_url = "device_id = \"dev001\"\nwatch_dir = \"memory\"";
+        assert_eq!(error(no_url), "config.toml: `api_url` is required for the presign backend");
+        let no_bucket = "backend = \"direct_s3\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"\nregion = \"us-west-2\"";
+        assert_eq!(error(no_bucket), "config.toml: `bucket` and `region` are required for the direct_s3 backend");
+        let bad_url = "api_url = \"example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"";
+        assert!(error(bad_url).contains("`api_url` \"example.com/ingest\" is not a valid http(s) URL"), "{}", error(bad_url));
+        let ftp_url = "api_url = \"ftp://example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"";
+        assert!(error(ftp_url).contains("unsupported scheme \"ftp\""), "{}", error(ftp_url));
+
+        let valid = "api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"";
+        assert!(AwsConfig::from_toml(valid, &config_path).is_ok());
+    }
+
+    #[test]
+    fn test_env_overrides_config_file() {
+        let config_path = temp_dir().join("src-tauri").join("config.toml");
+        let text = "api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = \"/data/memory\"\nconcurrency = 4";
+        let env: HashMap<&str, &str> = HashMap::from([
+            ("ARKANGEL_DEVICE_ID", "laptop-07"),
+            ("ARKANGEL_SCAN_INTERVAL_SECS", "15"),
+            ("ARKANGEL_API_URL", "  "),
+        ]);
+        let cfg = AwsConfig::from_toml_with_env(text, &config_path, |name| env.get(name).map(|v| v.to_string())).unwrap();
+        assert_eq!(cfg.device_id, "laptop-07");
+        assert_eq!(cfg.scan_interval_secs, Some(15));
+        assert_eq!(cfg.api_url, "https://example.com/ingest", "blank env values are ignored");
+        assert_eq!(cfg.concurrency, Some(4));
+        assert_eq!(cfg.watch_dir, WatchDirs::One("/data/memory".to_string()));
+
+        // Nothing set anywhere: defaults
+        let cfg = AwsConfig::from_toml_with_env(text, &config_path, |_| None).unwrap();
+        assert_eq!((cfg.device_id.as_str(), cfg.scan_interval_secs), ("dev001", Some(60)));
+
+        // Env can supply fields the file lacks
+        let dirs = std::env::join_paths(["/data/a", "/data/b"]).unwrap().to_string_lossy().to_string();
+        let env = HashMap::from([("ARKANGEL_WATCH_DIR", dirs), ("ARKANGEL_CONCURRENCY", "8".to_string())]);
+        let cfg = AwsConfig::from_toml_with_env("api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"", &config_path, |name| env.get(name).cloned()).unwrap();
+        assert_eq!(cfg.watch_dir, WatchDirs::Many(vec!["/data/a".to_string(), "/data/b".to_string()]));
+        assert_eq!(cfg.concurrency, Some(8));
+
+        let err = AwsConfig::from_toml_with_env(text, &config_path, |name| (name == "ARKANGEL_CONCURRENCY").then(|| "lots".to_string())).unwrap_err();
+        assert_eq!(err.to_string(), "ARKANGEL_CONCURRENCY must be a whole number, got \"lots\"");
+    }
+
+    #[test]
+    fn test_upload_log_names_configured_bucket() {
+        let dir = temp_dir();
+        let base = Arc::new(OnceLock::<String>::new());
+        let server_base = base.clone();
+        let url = spawn_mock(move |mut request| {
+            if request.method() == &tiny_http::Method::Post {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let base = server_base.get().unwrap();
+                // The endpoint reports its own bucket for one file only
+                let resp = if body.contains("reported.json") {
+                    format!(r#"{{"url":"{}/put","key":"k/reported.json","bucket":"endpoint-bucket"}}"#, base)
+                } else {
+                    format!(r#"{{"url":"{}/put","key":"k/configured.json"}}"#, base)
+                };
+                let _ = request.respond(tiny_http::Response::from_string(resp));
+            } else {
+                let _ = request.respond(tiny_http::Response::empty(200));
+            }
+        });
+        base.set(url.clone()).unwrap();
+
+        let (tx, rx) = channel();
+        *upload_log_sink().lock().unwrap() = Some(tx);
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.bucket = Some("team-bucket".to_string());
+        for name in ["configured.json", "reported.json"] {
+            fs::write(dir.join(name), r#"{"a":1}"#).unwrap();
+            process_file(&Client::new(), &cfg, &dir.join(name), None).unwrap();
+        }
+        *upload_log_sink().lock().unwrap() = None;
+
+        // Other tests may upload concurrently; only look at our own files
+        let lines: Vec<String> = rx.try_iter().filter(|l| l.contains("k/configured.json") || l.contains("k/reported.json")).collect();
+        assert!(lines.iter().any(|l| l.contains("s3://team-bucket/k/configured.json")), "{:?}", lines);
+        assert!(lines.iter().any(|l| l.contains("s3://endpoint-bucket/k/reported.json")), "{:?}", lines);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_resolved_config_reports_source_and_absolute_watch_dir() {
+        let root = temp_dir();
+        let config_dir = root.join("src-tauri");
+        fs::create_dir_all(&config_dir).unwrap();
+        let config_path = config_dir.join("config.toml");
+        let text = r#"
+            api_url = "https://example.com/ingest/new"
+            device_id = "dev001"
+            watch_dir = "memory"
+        "#;
+        fs::write(&config_path, text).unwrap();
+
+        let cfg = AwsConfig::from_toml(text, &config_path).unwrap();
+        let resolved = ResolvedAwsConfig::from_config(cfg, &config_path);
+        assert_eq!(resolved.source_path, config_path.to_string_lossy());
+        assert_eq!(resolved.watch_dir.all().len(), 1);
+        assert!(Path::new(&resolved.watch_dir.all()[0]).is_absolute());
+        assert!(resolved.watch_dir.all()[0].ends_with("memory"));
+        assert_eq!(resolved.scan_interval_secs, 60);
+        fs::remove_dir_all(&root).ok();
+    }
+
+    #[test]
+    fn test_pinned_cert_mismatch_fails_upload() {
+        let dir = temp_dir();
+        let base = spawn_tls_mock();
+
+        let wrong = pinned_client(&dir, include_bytes!("../tests/fixtures/tls/other.cert.pem"));
+        let err = upload_with_put(&wrong, &format!("{}/put", base), b"{}".to_vec(), &md5_hex(b"{}"), "application/json", &[]).unwrap_err();
+        assert!(format!("{:#}", err).contains("TLS certificate rejected"), "unexpected error: {:#}", err);
+
+        let right = pinned_client(&dir, include_bytes!("../tests/fixtures/tls/server.cert.pem"));
+        upload_with_put(&right, &format!("{}/put", base), b"{}".to_vec(), &md5_hex(b"{}"), "application/json", &[]).unwrap();
+    }
+
+    #[test]
+    fn test_memory_dir_is_added_to_the_watch_dirs_once() {
+        let dir = temp_dir();
+        let memory = dir.join("memory");
+        fs::create_dir_all(&memory).unwrap();
+
+        let mut dirs = WatchDirs::One("/srv/uploads".to_string());
+        dirs.include(&memory);
+        assert_eq!(dirs, WatchDirs::Many(vec!["/srv/uploads".to_string(), memory.to_string_lossy().to_string()]));
+        dirs.include(&memory);
+        dirs.include(&dir.join("memory/../memory"));
+        assert_eq!(dirs.all().len(), 2, "a directory already watched isn't added again");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reload_rebuilds_the_client_or_rejects_the_config() {
+        let dir = temp_dir();
+        let base = spawn_tls_mock();
+        let cfg = test_config("http://127.0.0.1:9/ingest/new", &dir);
+        let (tx, _rx) = channel();
+        let runtime = UploaderRuntime {
+            config: Arc::new(RwLock::new(cfg.clone())),
+            client: Arc::new(RwLock::new(http_client(&cfg).unwrap())),
+            events: Mutex::new(tx),
+            watcher: Mutex::new(None),
+        };
+        let put = |runtime: &UploaderRuntime| {
+            let client = runtime.client.read().unwrap().clone();
+            upload_with_put(&client, &format!("{}/put", base), b"{}".to_vec(), &md5_hex(b"{}"), "application/json", &[])
+        };
+        assert!(put(&runtime).is_err(), "the test server's root isn't trusted yet");
+
+        let pin = dir.join("pin.pem");
+        fs::write(&pin, include_bytes!("../tests/fixtures/tls/server.cert.pem")).unwrap();
+        let mut pinned = cfg.clone();
+        pinned.tls.pinned_certs = vec![pin.to_string_lossy().to_string()];
+        runtime.reload(pinned).unwrap();
+        put(&runtime).unwrap();
+
+        // A config whose client can't be built changes nothing
+        let mut broken = cfg.clone();
+        broken.debounce_ms = Some(900);
+        broken.tls.pinned_certs = vec![dir.join("missing.pem").to_string_lossy().to_string()];
+        let err = runtime.reload(broken).unwrap_err();
+        assert!(format!("{:#}", err).contains("missing.pem"), "unexpected error: {:#}", err);
+        assert_eq!(runtime.config.read().unwrap().debounce_ms, cfg.debounce_ms);
+        put(&runtime).unwrap();
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_retry_succeeds_after_transient_failures() {
+        let mut calls = 0;
+        let result = retry(
+            || {
+                calls += 1;
+                if calls < 3 { Err(anyhow!("S3 blip")) } else { Ok(()) }
+            },
+            5,
+            10,
+        );
+        assert!(result.is_ok());
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let err = retry(
+            || {
+                calls += 1;
+                Err(anyhow!("ETag mismatch on attempt {}", calls))
+            },
+            3,
+            1,
+        )
+        .unwrap_err();
+        assert_eq!(format!("{:#}", err), "after 3 attempts: ETag mismatch on attempt 3", "the last error is kept");
+
+        for _ in 0..100 {
+            let delay = jittered(1000);
+            assert!((500..1500).contains(&delay), "{}", delay);
+        }
+        assert!(jittered(MAX_RETRY_DELAY_MS) <= MAX_RETRY_DELAY_MS);
+    }
+
+    #[test]
+    fn test_etag_must_match_uploaded_md5() {
+        let body = br#"{"a":1}"#.to_vec();
+        let md5 = md5_hex(&body);
+        assert!(verify_etag(&md5, &format!("\"{}\"", md5)));
+        assert!(verify_etag(&md5, &md5.to_uppercase()));
+        assert!(!verify_etag(&md5, "\"d41d8cd98f00b204e9800998ecf8427e\""));
+
+        let url = spawn_mock(|mut request| {
+            let mut received = Vec::new();
+            let _ = request.as_reader().read_to_end(&mut received);
+            // Simulate a truncated upload: the server hashes less than it was sent
+            let etag = format!("\"{}\"", md5_hex(&received[..received.len() - 1]));
+            let header = tiny_http::Header::from_bytes("ETag", etag).unwrap();
+            let _ = request.respond(tiny_http::Response::empty(200).with_header(header));
+        });
+        let err = upload_with_put(&Client::new(), &format!("{}/put", url), body, &md5, "application/json", &[]).unwrap_err();
+        assert!(err.to_string().contains("does not match local MD5"), "{}", err);
+    }
+
+    #[test]
+    fn test_put_sends_content_md5_and_accepts_non_md5_etags() {
+        let body = br#"{"a":1}"#.to_vec();
+        let md5 = md5_hex(&body);
+        assert_eq!(content_md5(&md5_hex(b"Check Integrity!")).unwrap(), "nwqq6b6ua/tTDk7B5M184w==");
+        assert_eq!(content_md5("not-a-digest"), None);
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let server_seen = seen.clone();
+        let url = spawn_mock(move |request| {
+            let digest = request.headers().iter().find(|h| h.field.equiv("Content-MD5")).map(|h| h.value.to_string());
+            server_seen.lock().unwrap().push(digest);
+            let response = match request.url() {
+                "/multipart" => tiny_http::Response::empty(200)
+                    .with_header(tiny_http::Header::from_bytes("ETag", "\"0123456789abcdef0123456789abcdef-2\"").unwrap()),
+                _ => tiny_http::Response::empty(200)
+                    .with_header(tiny_http::Header::from_bytes("ETag", "\"0123456789abcdef0123456789abcdef\"").unwrap())
+                    .with_header(tiny_http::Header::from_bytes("x-amz-server-side-encryption", "aws:kms").unwrap()),
+            };
+            let _ = request.respond(response);
+        });
+        for path in ["multipart", "kms"] {
+            upload_with_put(&Client::new(), &format!("{}/{}", url, path), body.clone(), &md5, "application/json", &[]).unwrap();
+        }
+        let expected = content_md5(&md5);
+        assert_eq!(*seen.lock().unwrap(), vec![expected.clone(), expected]);
+    }
+
+    #[test]
+    fn test_recorded_upload_skipped_after_rename_reverted() {
+        let dir = temp_dir();
+        let puts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let put_count = puts.clone();
+        let (url, _) = mock_presign_server(move |request| {
+            put_count.fetch_add(1, Ordering::SeqCst);
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        let file = dir.join("conv.json");
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        let uploader = AwsUploader { config: test_config(&format!("{}/ingest/new", url), &dir), client: Client::new(), notifier: None };
+        uploader.scan_and_upload().unwrap();
+        assert_eq!(puts.load(Ordering::SeqCst), 1);
+        assert_eq!(uploader.load_state(&dir).synced["conv.json"].key, "test-device/upload.json");
+
+        // Undo the rename, as if it had failed; the state file alone must prevent a re-upload
+        fs::rename(dir.join("conv.json.synced"), &file).unwrap();
+        uploader.scan_and_upload().unwrap();
+        assert_eq!(puts.load(Ordering::SeqCst), 1, "recorded file must not be uploaded again");
+        assert!(dir.join("conv.json.synced").exists());
+
+        // New contents under the same name are a new upload
+        fs::rename(dir.join("conv.json.synced"), &file).unwrap();
+        fs::write(&file, r#"{"a":2}"#).unwrap();
+        uploader.scan_and_upload().unwrap();
+        assert_eq!(puts.load(Ordering::SeqCst), 2);
+
+        write_state(&dir, &UploadState::default()).unwrap();
+        assert!(uploader.load_state(&dir).synced.is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_settled_files_skip_hashing_and_forget_by_path() {
+        let (first, second) = (temp_dir(), temp_dir());
+        let mut cfg = test_config("http://127.0.0.1:9/ingest/new", &first);
+        cfg.watch_dir = WatchDirs::Many(vec![first.to_string_lossy().to_string(), second.to_string_lossy().to_string()]);
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+
+        let file = first.join("conv.json");
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        let past = std::time::SystemTime::now() - Duration::from_secs(60);
+        fs::File::options().write(true).open(&file).unwrap().set_modified(past).unwrap();
+        let (size, modified_ns) = file_stamp(&fs::metadata(&file).unwrap()).unwrap();
+
+        // The MD5 is deliberately wrong: a matching size and mtime must short-circuit hashing
+        let record = SyncedRecord { key: "k".to_string(), md5: "not-hashed".to_string(), size: Some(size), modified_ns: Some(modified_ns) };
+        for dir in [&first, &second] {
+            record_synced(dir, "conv.json", record.clone()).unwrap();
+        }
+        assert!(uploader.load_state(&first).is_synced("conv.json", &file));
+        fs::write(&file, r#"{"a":2}"#).unwrap();
+        assert!(!uploader.load_state(&first).is_synced("conv.json", &file), "a rewrite changes the mtime");
+        assert!(file_stamp(&fs::metadata(&file).unwrap()).is_none(), "fresh mtimes aren't trusted");
+
+        // Same name in two watch dirs: forgetting one leaves the other
+        assert_eq!(uploader.forget_uploads(Some(&second.join("conv.json"))).unwrap(), 1);
+        assert!(uploader.load_state(&first).synced.contains_key("conv.json"));
+        assert!(uploader.load_state(&second).synced.is_empty());
+        assert_eq!(uploader.forget_uploads(None).unwrap(), 1);
+        fs::remove_dir_all(&first).ok();
+        fs::remove_dir_all(&second).ok();
+    }
+
+    #[test]
+    fn test_unknown_min_tls_version_rejected() {
+        let tls = TlsSettings { min_version: Some("1.1".to_string()), pinned_certs: Vec::new() };
+        assert!(tls.client_builder().is_err());
+
+        let config_path = temp_dir().join("src-tauri").join("config.toml");
+        let text = "api_url = \"https://example.com/ingest\"\ndevice_id = \"dev001\"\nwatch_dir = \"memory\"\n[tls]\nmin_version = \"1.3\"";
+        let error = AwsConfig::from_toml(text, &config_path).unwrap_err().to_string();
+        assert!(error.contains("\"1.3\" is not supported"), "{}", error);
+    }
+
+    #[test]
+    fn test_disabled_watcher_defers_upload_to_next_scan() {
+        let dir = temp_dir();
+        let puts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let put_count = puts.clone();
+        let (url, _) = mock_presign_server(move |request| {
+            put_count.fetch_add(1, Ordering::SeqCst);
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+
+        let cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        let (tx, _rx) = channel();
+        let runtime = UploaderRuntime {
+            config: Arc::new(RwLock::new(cfg.clone())),
+            client: Arc::new(RwLock::new(http_client(&cfg).unwrap())),
+            events: Mutex::new(tx),
+            watcher: Mutex::new(None),
+        };
+        runtime.set_watcher_enabled(true).unwrap();
+        assert!(runtime.is_watcher_enabled());
+        runtime.set_watcher_enabled(false).unwrap();
+        assert!(!runtime.is_watcher_enabled());
+
+        // With the watcher gone a new file waits for the next scan cycle
+        fs::write(dir.join("later.json"), r#"{"a":1}"#).unwrap();
+        assert_eq!(puts.load(Ordering::SeqCst), 0);
+        assert!(!dir.join("later.json.synced").exists());
+
+        // One cycle, as the scan thread runs it
+        let uploader = AwsUploader {
+            config: runtime.config.read().unwrap().clone(),
+            client: runtime.client.read().unwrap().clone(),
+            notifier: None,
+        };
+        let summary = uploader.scan_and_upload().unwrap();
+        assert_eq!(summary.uploaded, 1);
+        assert!(dir.join("later.json.synced").exists(), "the scan should upload the file");
+        assert_eq!(puts.load(Ordering::SeqCst), 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_scan_uploads_files_concurrently() {
+        use std::sync::atomic::AtomicUsize;
+
+        let dir = temp_dir();
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let (server_running, server_peak) = (running.clone(), peak.clone());
+        let (url, _) = mock_presign_server(move |mut request| {
+            let mut body = String::new();
+            let _ = request.as_reader().read_to_string(&mut body);
+            let now = server_running.fetch_add(1, Ordering::SeqCst) + 1;
+            server_peak.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(300));
+            server_running.fetch_sub(1, Ordering::SeqCst);
+            // One file is rejected; the rest of the batch must still go through
+            let status = if body.contains("\"fail\"") { 500 } else { 200 };
+            let _ = request.respond(tiny_http::Response::empty(status));
+        });
+
+        for i in 0..6 {
+            fs::write(dir.join(format!("conv{}.json", i)), format!(r#"{{"n":{}}}"#, i)).unwrap();
+        }
+        fs::write(dir.join("broken.json"), r#"{"fail":true}"#).unwrap();
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.concurrency = Some(3);
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        uploader.scan_and_upload().unwrap();
+
+        let peak = peak.load(Ordering::SeqCst);
+        assert!(peak <= 3, "at most `concurrency` uploads at a time, saw {}", peak);
+        assert!(peak > 1, "uploads should overlap, saw {}", peak);
+        for i in 0..6 {
+            assert!(dir.join(format!("conv{}.json.synced", i)).exists(), "conv{} not synced", i);
+        }
+        assert!(dir.join("broken.json").exists(), "failed upload stays pending");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_scan_loop_picks_up_reloaded_config() {
+        let dir = temp_dir();
+        let mut cfg = test_config("http://127.0.0.1:9/ingest/new", &dir);
+        cfg.scan_interval_secs = Some(30);
+        let config = RwLock::new(cfg);
+
+        let mut sleeps = Vec::new();
+        let mut seen = Vec::new();
+        run_scan_loop(&config, |d| sleeps.push(d), |current| {
+            seen.push((current.scan_interval_secs, current.watch_dir.clone()));
+            if seen.len() == 1 {
+                // A reload landing mid-cycle applies from the next one
+                let mut reloaded = config.write().unwrap();
+                reloaded.scan_interval_secs = Some(5);
+                reloaded.watch_dir = WatchDirs::One("/elsewhere".to_string());
+            }
+            if seen.len() == 3 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+
+        assert_eq!(sleeps, [Duration::from_secs(30), Duration::from_secs(5)]);
+        assert_eq!(seen[1], (Some(5), WatchDirs::One("/elsewhere".to_string())));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_rapid_modify_events_processed_once() {
+        use notify::event::{DataChange, ModifyKind};
+
+        let dir = temp_dir();
+        let file = dir.join("chunked.json");
+        let mut cfg = test_config("http://127.0.0.1:9/ingest/new", &dir);
+        cfg.debounce_ms = Some(300);
+        let (tx, rx) = channel();
+        let (done_tx, done_rx) = channel();
+        let worker = thread::spawn(move || {
+            run_event_loop(&rx, &RwLock::new(cfg), |path| {
+                done_tx.send(path.to_path_buf()).unwrap();
+            });
+        });
+
+        // An editor writing in three chunks, each firing its own modify event
+        for chunk in [r#"{"a""#, r#"{"a":1"#, r#"{"a":1}"#] {
+            fs::write(&file, chunk).unwrap();
+            let event = notify::Event::new(EventKind::Modify(ModifyKind::Data(DataChange::Content))).add_path(file.clone());
+            tx.send(Ok(event)).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(done_rx.recv_timeout(Duration::from_millis(150)).is_err(), "processed before the file settled");
+        assert_eq!(done_rx.recv_timeout(Duration::from_secs(5)).unwrap(), file);
+
+        thread::sleep(Duration::from_millis(500));
+        drop(tx);
+        worker.join().unwrap();
+        assert_eq!(done_rx.try_iter().count(), 0, "file must be processed exactly once");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_trailing_modify_after_sync_is_suppressed() {
+        let dir = temp_dir();
+        let puts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let put_count = puts.clone();
+        let (url, _) = mock_presign_server(move |request| {
+            put_count.fetch_add(1, Ordering::SeqCst);
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+        let cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        let client = Client::new();
+        let events = FileEvents::new(POST_SYNC_COOLDOWN);
+        let file = dir.join("note.json");
+
+        // Editor saves via rename: the watcher reports a create...
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        events.handle(&client, &cfg, &file, None);
+        assert!(dir.join("note.json.synced").exists());
+
+        // ...then touches the file again, producing a trailing modify
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        events.handle(&client, &cfg, &file, None);
+
+        assert_eq!(puts.load(Ordering::SeqCst), 1, "trailing event must not re-upload");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_archive_mode_moves_synced_file() {
+        let dir = temp_dir();
+        let file = dir.join("done.json");
+        fs::write(&file, r#"{"a":1}"#).unwrap();
+        let output: SyncedOutput = toml::from_str("mode = \"archive\"\narchive_dir = \"uploaded\"").unwrap();
+
+        mark_synced(&file, &output).unwrap();
+
+        assert!(!file.exists());
+        assert!(!dir.join("done.json.synced").exists());
+        assert_eq!(fs::read_to_string(dir.join("uploaded").join("done.json.synced")).unwrap(), r#"{"a":1}"#);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_list_and_clear_quarantine() {
+        let dir = temp_dir();
+        for name in ["a.json", "b.json"] {
+            let file = dir.join(name);
+            fs::write(&file, "{}").unwrap();
+            quarantine_file(&file, "residual PII detected (Email=1)").unwrap();
+        }
+
+        let entries = list_quarantine(&dir).unwrap();
+        assert_eq!(entries.iter().map(|e| e.filename.as_str()).collect::<Vec<_>>(), vec!["a.json", "b.json"]);
+        assert_eq!(entries[0].reason, "residual PII detected (Email=1)");
+
+        assert!(clear_quarantine(&dir, Some("../a.json")).is_err());
+        assert!(clear_quarantine(&dir, Some("a.json.reason.txt")).is_err());
+        assert_eq!(clear_quarantine(&dir, Some("a.json")).unwrap(), 1);
+        assert_eq!(list_quarantine(&dir).unwrap().len(), 1);
+        assert_eq!(clear_quarantine(&dir, None).unwrap(), 1);
+        assert!(list_quarantine(&dir).unwrap().is_empty());
+        assert_eq!(fs::read_dir(dir.join(QUARANTINE_DIR_NAME)).unwrap().count(), 0, "reason files removed too");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_resubmit_quarantine_rescrubs_into_watch_dir() {
+        let dir = temp_dir();
+        let file = dir.join("leaky.json");
+        fs::write(&file, r#"{"content":"reach me at john@example.com"}"#).unwrap();
+        quarantine_file(&file, "residual PII detected (Email=1)").unwrap();
+
+        let mut cfg = test_config("http://127.0.0.1:9/ingest/new", &dir);
+        cfg.upload_policy.enabled = true;
+        let dest = resubmit_quarantine(&cfg, "leaky.json").unwrap();
+
+        assert_eq!(dest, dir.join("leaky.json"));
+        assert!(fs::read_to_string(&dest).unwrap().contains("reach me at BLOCKED"));
+        assert!(is_complete_json(&dest, &cfg));
+        assert!(list_quarantine(&dir).unwrap().is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_zip_archive_members_are_uploaded_individually() {
+        let dir = temp_dir();
+        let archive = dir.join("bundle.zip");
+        write_zip(&archive, &[("one.json", r#"{"content":"hi"}"#), ("two.json", r#"{"content":"there"}"#), ("notes.txt", "skip")]);
+
+        let puts = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let put_count = puts.clone();
+        let (url, presigned) = mock_presign_server(move |request| {
+            put_count.fetch_add(1, Ordering::SeqCst);
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        assert!(!is_upload_candidate(&archive, &cfg), "archives are opt-in");
+        cfg.archives.enabled = true;
+        assert!(is_upload_candidate(&archive, &cfg));
+
+        assert_eq!(process_archive(&Client::new(), &cfg, &archive, None).unwrap(), 2);
+
+        assert_eq!(puts.load(Ordering::SeqCst), 2);
+        let mut names = presigned.lock().unwrap().clone();
+        names.sort();
+        assert_eq!(names, vec!["bundle/one.json", "bundle/two.json"]);
+        assert!(dir.join("bundle.zip.synced").exists());
+        let recorded: Vec<String> = read_state(&dir).synced.into_keys().collect();
+        assert_eq!(recorded, ["bundle.zip!/one.json", "bundle.zip!/two.json"], "members are recorded in the watch dir");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_archive_retry_skips_uploaded_members() {
+        let dir = temp_dir();
+        let archive = dir.join("bundle.zip");
+        let members = [("a/x.json", r#"{"content":"first"}"#), ("b/x.json", r#"{"content":"second"}"#)];
+        write_zip(&archive, &members);
+
+        let (url, presigned) = mock_presign_server(move |request| {
+            let _ = request.respond(tiny_http::Response::empty(200));
+        });
+        let mut cfg = test_config(&format!("{}/ingest/new", url), &dir);
+        cfg.archives.enabled = true;
+
+        // An earlier pass uploaded a/x.json before b/x.json failed
+        let scrubbed = pii_scrubber::scrub_conversation_json(members[0].1.to_string()).unwrap();
+        let record = SyncedRecord { key: "k".to_string(), md5: md5_hex(scrubbed.as_bytes()), size: None, modified_ns: None };
+        record_synced(&dir, "bundle.zip!/a/x.json", record).unwrap();
+
+        assert_eq!(process_archive(&Client::new(), &cfg, &archive, None).unwrap(), 1);
+        assert_eq!(*presigned.lock().unwrap(), vec!["bundle/b/x.json"], "same-named members keep their folders");
+        let recorded: Vec<String> = read_state(&dir).synced.into_keys().collect();
+        assert_eq!(recorded, ["bundle.zip!/a/x.json", "bundle.zip!/b/x.json"]);
+
+        let uploader = AwsUploader { config: cfg, client: Client::new(), notifier: None };
+        assert_eq!(uploader.forget_uploads(Some(&archive)).unwrap(), 2, "forgetting an archive drops its members");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_oversized_archive_is_quarantined() {
+        let dir = temp_dir();
+        let archive = dir.join("bomb.zip");
+        write_zip(&archive, &[("big.json", &" ".repeat(4096))]);
+        let mut cfg = test_config("http://127.0.0.1:9/ingest/new", &dir);
+        cfg.archives.enabled = true;
+        cfg.archives.max_entry_bytes = 1024;
+
+        assert!(process_archive(&Client::new(), &cfg, &archive, None).is_err());
+        assert!(!archive.exists());
+        assert_eq!(list_quarantine(&dir).unwrap()[0].filename, "bomb.zip");
+        fs::remove_dir_all(&dir).ok();
     }
 }
@@ -1,10 +1,17 @@
 use anyhow::{anyhow, Result};
+use calamine::{Reader, Xlsx};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
 use uuid::Uuid;
 use chrono::Utc;
 
+use crate::pii_scrubber;
+use crate::upload_index::{self, IndexBackend, UploadIndex};
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileInfo {
     pub id: String,                    // UUID for unique identification
@@ -16,11 +23,358 @@ pub struct FileInfo {
     pub is_context_enabled: bool,      // Toggle for LLM context
     #[serde(default)]
     pub summary: String,               // Brief summary for prompts
+    #[serde(default)]
+    pub priority: i32,                 // Higher survives context budgeting first
+    #[serde(default)]
+    pub content_hash: Option<String>,  // SHA-256 of the raw bytes (absent on older entries)
+    #[serde(default)]
+    pub detected_type: Option<String>, // Type sniffed from the leading bytes, when recognized
+}
+
+impl FileInfo {
+    /// The type extraction runs as: what the content looks like, else the extension
+    fn extraction_type(&self) -> &str {
+        self.detected_type.as_deref().unwrap_or(&self.file_type)
+    }
+}
+
+/// How text is pulled out of a given file type
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Extractor {
+    PlainText,                         // direct read
+    Code,                              // direct read with syntax preservation
+    Pdf,                               // pdf-extract
+    Ocr,                               // tesseract, with the `ocr` feature
+    Spreadsheet,                       // calamine, one block per sheet
+    Html,                              // html2text, markup stripped to visible text
+}
+
+/// Extensions with built-in text extraction
+const EXTRACTORS: &[(&str, Extractor)] = &[
+    // Text files
+    ("txt", Extractor::PlainText),
+    ("md", Extractor::PlainText),
+    ("json", Extractor::PlainText),
+    ("csv", Extractor::PlainText),
+    ("xml", Extractor::PlainText),
+    ("yaml", Extractor::PlainText),
+    ("log", Extractor::PlainText),
+    // Code files
+    ("py", Extractor::Code),
+    ("js", Extractor::Code),
+    ("ts", Extractor::Code),
+    ("java", Extractor::Code),
+    ("cpp", Extractor::Code),
+    ("c", Extractor::Code),
+    ("go", Extractor::Code),
+    ("rs", Extractor::Code),
+    ("php", Extractor::Code),
+    ("html", Extractor::Html),
+    ("css", Extractor::Code),
+    ("sql", Extractor::Code),
+    // Documents
+    ("pdf", Extractor::Pdf),
+    // Images
+    ("png", Extractor::Ocr),
+    ("jpg", Extractor::Ocr),
+    ("jpeg", Extractor::Ocr),
+    ("tiff", Extractor::Ocr),
+    // Spreadsheets
+    ("xlsx", Extractor::Spreadsheet),
+];
+
+fn extractor_for(file_type: &str) -> Option<Extractor> {
+    EXTRACTORS
+        .iter()
+        .find(|(ext, _)| *ext == file_type)
+        .map(|(_, extractor)| *extractor)
+}
+
+/// File type from well-known leading bytes. ZIP containers keep an Office extension
+/// when the name has one; anything unrecognized is None.
+fn sniff_file_type(bytes: &[u8], extension: &str) -> Option<String> {
+    let detected = if bytes.starts_with(b"%PDF") {
+        "pdf"
+    } else if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        "png"
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        if extension == "jpeg" { "jpeg" } else { "jpg" }
+    } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        "tiff"
+    } else if bytes.starts_with(b"PK\x03\x04") {
+        match extension {
+            "docx" | "xlsx" | "pptx" => extension,
+            _ => "zip",
+        }
+    } else {
+        return None;
+    };
+    Some(detected.to_string())
+}
+
+/// Upload capabilities for one file extension, for keeping the UI in sync
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct FileTypeInfo {
+    pub extension: String,
+    pub extractable: bool,             // text content is extracted for context
+    pub requires_ocr: bool,            // extraction relies on OCR
+}
+
+impl FileTypeInfo {
+    pub fn for_extension(extension: &str) -> Self {
+        let extension = extension.to_lowercase();
+        let extractor = extractor_for(&extension);
+        let requires_ocr = extractor == Some(Extractor::Ocr);
+        Self {
+            extension,
+            extractable: extractor.is_some() && (!requires_ocr || cfg!(feature = "ocr")),
+            requires_ocr,
+        }
+    }
+}
+
+/// Every extension with a registered extractor
+pub fn supported_file_types() -> Vec<FileTypeInfo> {
+    EXTRACTORS
+        .iter()
+        .map(|(ext, _)| FileTypeInfo::for_extension(ext))
+        .collect()
+}
+
+/// A saved copy of index.json, see `FileStorage::snapshot_index`
+#[derive(Debug, Serialize, Clone)]
+pub struct IndexSnapshot {
+    pub id: String,
+    pub created_at: String,            // ISO 8601 timestamp
+    pub file_count: usize,             // Entries in the saved index
+    pub includes_files: bool,          // Whether the uploaded files were copied too
+}
+
+/// One page of the index, see `FileStorage::list_files_page`
+#[derive(Debug, Serialize, Clone)]
+pub struct FilePage {
+    pub files: Vec<FileInfo>,
+    pub total: usize,                  // Entries in the whole index
+    pub offset: usize,
+}
+
+/// Which steps `FileStorage::reprocess_all` runs
+#[derive(Debug, Deserialize, Clone)]
+pub struct ReprocessOptions {
+    #[serde(default = "default_true")]
+    pub reextract: bool,               // Re-read stored files with the current extractors
+    #[serde(default = "default_true")]
+    pub scrub: bool,                   // Run the PII scrubber over the content
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl Default for ReprocessOptions {
+    fn default() -> Self {
+        Self { reextract: true, scrub: true }
+    }
+}
+
+/// Progress after each file handled by `reprocess_all`
+#[derive(Debug, Serialize, Clone)]
+pub struct ReprocessProgress {
+    pub done: usize,
+    pub total: usize,
+    pub file_id: String,
+    pub name: String,
+}
+
+/// Outcome of `reprocess_all`
+#[derive(Debug, Serialize, Default)]
+pub struct ReprocessReport {
+    pub processed: usize,
+    pub content_changed: Vec<String>,  // ids whose content differs after reprocessing
+    pub summary_changed: Vec<String>,  // ids whose summary differs after reprocessing
+    pub failed: Vec<ReprocessFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReprocessFailure {
+    pub file_id: String,
+    pub error: String,
+}
+
+/// Subdirectory of uploads/ holding index snapshots
+const SNAPSHOTS_DIR_NAME: &str = "snapshots";
+
+/// Settings from the optional `[uploads]` table of config.toml
+#[derive(Deserialize)]
+struct UploadsConfig {
+    /// PDF extractions allowed to run at once; further uploads queue for a slot
+    #[serde(default = "default_max_concurrent_pdf_extractions")]
+    max_concurrent_pdf_extractions: usize,
+    /// Largest file accepted for upload
+    #[serde(default = "default_max_upload_bytes")]
+    max_upload_bytes: u64,
+    /// Return the existing entry when the same bytes are uploaded again
+    #[serde(default = "default_true")]
+    dedupe: bool,
+    /// Keep the index in index.json (default) or an index.db SQLite database
+    #[serde(default)]
+    index_backend: IndexBackend,
+    /// Rows extracted per spreadsheet sheet; the rest are dropped
+    #[serde(default = "default_max_spreadsheet_rows")]
+    max_spreadsheet_rows: usize,
+}
+
+fn default_max_concurrent_pdf_extractions() -> usize {
+    2
+}
+
+fn default_max_upload_bytes() -> u64 {
+    DEFAULT_MAX_UPLOAD_BYTES
+}
+
+fn default_max_spreadsheet_rows() -> usize {
+    DEFAULT_MAX_SPREADSHEET_ROWS
+}
+
+#[derive(Deserialize)]
+struct ConfigFile {
+    uploads: Option<UploadsConfig>,
+}
+
+/// Counting semaphore bounding concurrent PDF extractions
+struct ExtractionLimiter {
+    limit: usize,
+    active: Mutex<usize>,
+    released: Condvar,
 }
 
+/// Held while an extraction runs; frees the slot on drop
+struct ExtractionPermit<'a>(&'a ExtractionLimiter);
+
+impl ExtractionLimiter {
+    fn new(limit: usize) -> Self {
+        Self { limit: limit.max(1), active: Mutex::new(0), released: Condvar::new() }
+    }
+
+    fn acquire(&self) -> ExtractionPermit<'_> {
+        let mut active = self.active.lock().unwrap();
+        while *active >= self.limit {
+            active = self.released.wait(active).unwrap();
+        }
+        *active += 1;
+        ExtractionPermit(self)
+    }
+}
+
+impl Drop for ExtractionPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// The `[uploads]` table, if config.toml has one
+fn uploads_config() -> Option<UploadsConfig> {
+    crate::aws_uploader::read_config_file()
+        .ok()
+        .and_then(|(text, _)| toml::from_str::<ConfigFile>(&text).ok())
+        .and_then(|file| file.uploads)
+}
+
+/// Process-wide PDF limiter, sized from config.toml on first use
+fn pdf_limiter() -> &'static ExtractionLimiter {
+    static LIMITER: OnceLock<ExtractionLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| {
+        let limit = uploads_config()
+            .map(|uploads| uploads.max_concurrent_pdf_extractions)
+            .unwrap_or_else(default_max_concurrent_pdf_extractions);
+        ExtractionLimiter::new(limit)
+    })
+}
+
+#[cfg(feature = "ocr")]
+fn ocr_image(file_path: &Path) -> Result<String> {
+    let mut tess = leptess::LepTess::new(None, "eng").map_err(|e| anyhow!("Failed to start tesseract: {:?}", e))?;
+    tess.set_image(file_path).map_err(|e| anyhow!("Failed to load image: {:?}", e))?;
+    tess.get_utf8_text().map_err(|e| anyhow!("Failed to read OCR output: {:?}", e))
+}
+
+#[cfg(not(feature = "ocr"))]
+fn ocr_image(_file_path: &Path) -> Result<String> {
+    Err(anyhow!("OCR support is not built in (enable the `ocr` feature)"))
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Approximate size of a context chunk handed out by `get_context_content_token_budgeted`
+const CONTEXT_CHUNK_TOKENS: usize = 256;
+
+/// Rough token count: about four tokens for every three whitespace-separated words
+fn estimate_tokens(text: &str) -> usize {
+    (text.split_whitespace().count() * 4).div_ceil(3)
+}
+
+/// Whitespace-separated words that fit in `tokens` by `estimate_tokens`
+fn words_for_tokens(tokens: usize) -> usize {
+    tokens * 3 / 4
+}
+
+/// Byte offset just past each word, so a prefix of the words keeps its original layout
+fn word_end_offsets(text: &str) -> Vec<usize> {
+    let mut ends = Vec::new();
+    let mut in_word = false;
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if in_word {
+                ends.push(i);
+            }
+            in_word = false;
+        } else {
+            in_word = true;
+        }
+    }
+    if in_word {
+        ends.push(text.len());
+    }
+    ends
+}
+
+/// "1.5 MB"-style size for error messages
+fn human_size(bytes: u64) -> String {
+    const MB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= MB {
+        format!("{:.1} MB", bytes as f64 / MB)
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
+}
+
+/// Serializes read-modify-write updates of the index. Tauri commands build a fresh
+/// `FileStorage` per call, so the lock is process-wide rather than per instance.
+fn index_lock() -> &'static Mutex<()> {
+    static LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    LOCK.get_or_init(|| Mutex::new(()))
+}
+
+/// Hold the index lock; a panic in another holder doesn't leave the index unusable
+fn lock_index() -> MutexGuard<'static, ()> {
+    index_lock().lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Largest file accepted for upload unless `[uploads] max_upload_bytes` says otherwise (50 MB)
+pub const DEFAULT_MAX_UPLOAD_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Rows kept per sheet unless `[uploads] max_spreadsheet_rows` says otherwise
+pub const DEFAULT_MAX_SPREADSHEET_ROWS: usize = 1000;
+
 pub struct FileStorage {
     uploads_dir: PathBuf,              // ./uploads/ directory path
-    index_path: PathBuf,               // ./uploads/index.json path
+    index: UploadIndex,                // ./uploads/index.json or ./uploads/index.db
+    max_upload_bytes: u64,             // uploads above this are rejected before writing
+    dedupe: bool,                      // identical uploads map to one entry
+    max_spreadsheet_rows: usize,       // rows extracted per sheet
 }
 
 impl FileStorage {
@@ -31,56 +385,225 @@ impl FileStorage {
             .ok_or_else(|| anyhow!("Failed to get project root"))?
             .to_path_buf();
         
-        let uploads_dir = project_root.join("uploads");
-        let index_path = uploads_dir.join("index.json");
-        
+        Self::with_uploads_dir(project_root.join("uploads"))
+    }
+    
+    /// Storage rooted at an explicit uploads directory
+    pub fn with_uploads_dir(uploads_dir: PathBuf) -> Result<Self> {
         // Create uploads directory if it doesn't exist
         fs::create_dir_all(&uploads_dir)?;
         
+        let config = uploads_config();
+        let backend = config.as_ref().map(|c| c.index_backend).unwrap_or_default();
         Ok(Self {
+            index: UploadIndex::open(&uploads_dir, backend)?,
             uploads_dir,
-            index_path,
+            max_upload_bytes: config.as_ref().map(|c| c.max_upload_bytes).unwrap_or(DEFAULT_MAX_UPLOAD_BYTES),
+            dedupe: config.as_ref().map(|c| c.dedupe).unwrap_or(true),
+            max_spreadsheet_rows: config.as_ref().map(|c| c.max_spreadsheet_rows).unwrap_or(DEFAULT_MAX_SPREADSHEET_ROWS),
         })
     }
     
+    fn check_upload_size(&self, name: &str, size: u64) -> Result<()> {
+        if size > self.max_upload_bytes {
+            return Err(anyhow!(
+                "File '{}' is {} bytes ({}); the upload limit is {} bytes ({})",
+                name, size, human_size(size), self.max_upload_bytes, human_size(self.max_upload_bytes)
+            ));
+        }
+        Ok(())
+    }
+    
     pub fn upload_file(&self, file_data: Vec<u8>, filename: String) -> Result<FileInfo> {
+        self.check_upload_size(&filename, file_data.len() as u64)?;
+        
+        let content_hash = sha256_hex(&file_data);
+        // Fast path; `index_new_upload` checks again under the lock before anything is indexed
+        if let Some(existing) = self.find_duplicate(&self.list_files()?, &content_hash, "") {
+            println!("[uploads] '{}' has the same content as id={} name='{}'; reusing it", filename, existing.id, existing.name);
+            return Ok(existing);
+        }
+        
         // 1. Generate unique UUID
         let file_id = Uuid::new_v4().to_string();
         
-        // 2. Determine file type from extension
+        // 2. Determine file type from extension, and from the content where it's recognizable
         let file_type = self.get_file_type(&filename);
+        let detected_type = sniff_file_type(&file_data, &file_type);
+        if let Some(detected) = detected_type.as_deref().filter(|d| *d != file_type) {
+            println!("[uploads] '{}' looks like {} despite its .{} extension", filename, detected, file_type);
+        }
         
         // 3. Create file path with UUID
         let file_path = self.uploads_dir.join(&file_id);
         
-        // 4. Write raw file data
+        // 4. Write raw file data under a hidden name, which `gc_orphans` leaves alone; it
+        //    takes the id as its name once the entry is indexed
+        let partial_path = self.uploads_dir.join(format!(".{}.partial", file_id));
         let file_size = file_data.len() as u64;
-        fs::write(&file_path, &file_data)?;
-        
-        // 5. Extract text content based on file type
-        let content = self.extract_text_content(&file_path, &file_type)?;
+        fs::write(&partial_path, &file_data)?;
         
-        // 6. Create metadata record (compute brief summary)
-        let summary = Self::summarize(&filename, &file_type, file_size, &content);
-        println!("[uploads] New file uploaded: name='{}' type='{}' size={} id={} summary='{}'", filename, file_type, file_size, file_id, summary);
-        
-        let file_info = FileInfo {
+        let mut file_info = FileInfo {
             id: file_id,
             name: filename,
             file_type,
             size: file_size,
             upload_date: Utc::now().to_rfc3339(),
-            content,
+            content: String::new(),
             is_context_enabled: true, // Default to enabled
-            summary,
+            priority: 0,
+            summary: String::new(),
+            content_hash: Some(content_hash),
+            detected_type,
+        };
+        
+        // PDF extraction may queue behind other uploads, so record the metadata first
+        let throttled = extractor_for(file_info.extraction_type()) == Some(Extractor::Pdf);
+        if throttled {
+            file_info.summary = Self::summarize(&file_info.name, &file_info.file_type, file_size, "");
+            if let Some(existing) = self.index_new_upload(&file_info, &partial_path)? {
+                return Ok(existing);
+            }
+        }
+        
+        // 5. Extract text content based on file type
+        let raw_path = if throttled { &file_path } else { &partial_path };
+        let content = match self.extract_text_content(raw_path, file_info.extraction_type()) {
+            Ok(content) => content,
+            Err(e) => {
+                if throttled {
+                    let _ = self.delete_file(&file_info.id);
+                } else {
+                    let _ = fs::remove_file(&partial_path);
+                }
+                return Err(e);
+            }
         };
         
+        // 6. Create metadata record (compute brief summary)
+        file_info.summary = Self::summarize(&file_info.name, &file_info.file_type, file_size, &content);
+        file_info.content = content;
+        println!("[uploads] New file uploaded: name='{}' type='{}' size={} id={} summary='{}'", file_info.name, file_info.file_type, file_size, file_info.id, file_info.summary);
+        
         // 7. Save to JSON index
-        self.save_file_to_index(&file_info)?;
+        if throttled {
+            self.save_file_to_index(&file_info)?;
+        } else if let Some(existing) = self.index_new_upload(&file_info, &partial_path)? {
+            return Ok(existing);
+        }
         
         Ok(file_info)
     }
     
+    /// Entry other than `own_id` holding the same content, when dedupe is on
+    fn find_duplicate(&self, files: &[FileInfo], content_hash: &str, own_id: &str) -> Option<FileInfo> {
+        if !self.dedupe {
+            return None;
+        }
+        files.iter().find(|f| f.id != own_id && f.content_hash.as_deref() == Some(content_hash)).cloned()
+    }
+    
+    /// Add a new upload's entry and move its raw file from `partial_path` to its id,
+    /// unless an identical upload was indexed while this one was being written; then the
+    /// raw file is removed and the existing entry returned. Everything happens under one
+    /// hold of the index lock, so `gc_orphans` never sees the file unindexed.
+    fn index_new_upload(&self, file_info: &FileInfo, partial_path: &Path) -> Result<Option<FileInfo>> {
+        let _guard = lock_index();
+        let hash = file_info.content_hash.as_deref().unwrap_or_default();
+        if let Some(existing) = self.find_duplicate(&self.list_files_locked()?, hash, &file_info.id) {
+            println!("[uploads] '{}' has the same content as id={} name='{}'; reusing it", file_info.name, existing.id, existing.name);
+            let _ = fs::remove_file(partial_path);
+            return Ok(Some(existing));
+        }
+        self.index.upsert(file_info)?;
+        if let Err(e) = fs::rename(partial_path, self.uploads_dir.join(&file_info.id)) {
+            let _ = self.index.remove(&file_info.id);
+            let _ = fs::remove_file(partial_path);
+            return Err(e.into());
+        }
+        Ok(None)
+    }
+    
+    /// Import a local file by path, reading it here instead of over IPC
+    pub fn upload_file_from_path(&self, path: &Path) -> Result<FileInfo> {
+        let path = path
+            .canonicalize()
+            .map_err(|e| anyhow!("Cannot access '{}': {}", path.display(), e))?;
+        let metadata = fs::metadata(&path)?;
+        if !metadata.is_file() {
+            return Err(anyhow!("'{}' is not a regular file", path.display()));
+        }
+        self.check_upload_size(&path.display().to_string(), metadata.len())?;
+        let filename = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .ok_or_else(|| anyhow!("'{}' has no file name", path.display()))?;
+        
+        self.upload_file(fs::read(&path)?, filename)
+    }
+    
+    /// Stream a stored file's raw bytes to `dest`. A directory destination receives the
+    /// original filename; a file path without an extension gets the original one appended.
+    /// Existing files are never overwritten. Returns the path written.
+    pub fn export_file_to_path(&self, file_id: &str, dest: &Path) -> Result<PathBuf> {
+        let file = self.list_files()?
+            .into_iter()
+            .find(|f| f.id == file_id)
+            .ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        let source = self.uploads_dir.join(&file.id);
+        if !source.is_file() {
+            return Err(anyhow!("Stored file data missing for: {}", file_id));
+        }
+        
+        let original = Path::new(&file.name);
+        let mut target = if dest.is_dir() {
+            dest.join(original.file_name().ok_or_else(|| anyhow!("Stored file has no name: {}", file_id))?)
+        } else {
+            dest.to_path_buf()
+        };
+        if target.extension().is_none() {
+            if let Some(ext) = original.extension() {
+                target.set_extension(ext);
+            }
+        }
+        
+        let parent = match target.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let parent = parent
+            .canonicalize()
+            .map_err(|e| anyhow!("Destination folder '{}' is not accessible: {}", parent.display(), e))?;
+        let uploads_dir = self.uploads_dir.canonicalize().unwrap_or_else(|_| self.uploads_dir.clone());
+        if parent.starts_with(&uploads_dir) {
+            return Err(anyhow!("Cannot export into the uploads folder"));
+        }
+        let target = parent.join(target.file_name().ok_or_else(|| anyhow!("Invalid destination: {}", dest.display()))?);
+        if target.exists() {
+            return Err(anyhow!("'{}' already exists", target.display()));
+        }
+        
+        // Copy under a temp name so a failed export never leaves a truncated file behind
+        let tmp = parent.join(format!(".{}.part", Uuid::new_v4()));
+        let copied = (|| -> Result<u64> {
+            let mut reader = fs::File::open(&source)?;
+            let mut writer = fs::File::create(&tmp)?;
+            let n = std::io::copy(&mut reader, &mut writer)?;
+            writer.sync_all()?;
+            Ok(n)
+        })();
+        let copied = match copied {
+            Ok(n) => n,
+            Err(e) => {
+                let _ = fs::remove_file(&tmp);
+                return Err(e);
+            }
+        };
+        fs::rename(&tmp, &target)?;
+        println!("[uploads] Exported id={} name='{}' ({} bytes) to {}", file.id, file.name, copied, target.display());
+        Ok(target)
+    }
+    
     fn get_file_type(&self, filename: &str) -> String {
         Path::new(filename)
             .extension()
@@ -90,23 +613,25 @@ impl FileStorage {
     }
     
     fn extract_text_content(&self, file_path: &Path, file_type: &str) -> Result<String> {
-        match file_type {
-            // Text files - direct read
-            "txt" | "md" | "json" | "csv" | "xml" | "yaml" | "log" => {
+        match extractor_for(file_type) {
+            Some(Extractor::PlainText) | Some(Extractor::Code) => {
                 let content = fs::read_to_string(file_path)?;
                 Ok(content)
             }
-            // Code files - direct read with syntax preservation
-            "py" | "js" | "ts" | "java" | "cpp" | "c" | "go" | "rs" | "php" | "html" | "css" | "sql" => {
-                let content = fs::read_to_string(file_path)?;
-                Ok(content)
-            }
-            // PDF files - extract text content
-            "pdf" => {
+            Some(Extractor::Pdf) => {
                 self.extract_pdf_text(file_path)
             }
-            // Unsupported types - return empty (future: DOCX, OCR)
-            _ => {
+            Some(Extractor::Ocr) => {
+                Ok(self.extract_image_text(file_path))
+            }
+            Some(Extractor::Spreadsheet) => {
+                self.extract_spreadsheet_text(file_path)
+            }
+            Some(Extractor::Html) => {
+                self.extract_html_text(file_path)
+            }
+            // Unsupported types - return empty (future: DOCX)
+            None => {
                 Ok("".to_string())
             }
         }
@@ -117,7 +642,8 @@ impl FileStorage {
         // Read the PDF file as bytes
         let pdf_bytes = fs::read(file_path)?;
         
-        // Extract text using pdf-extract
+        // Extract text using pdf-extract, waiting for a free slot
+        let _permit = pdf_limiter().acquire();
         match pdf_extract::extract_text_from_mem(&pdf_bytes) {
             Ok(text) => {
                 // Clean up the extracted text
@@ -137,37 +663,103 @@ impl FileStorage {
         }
     }
     
-    fn save_file_to_index(&self, new_file: &FileInfo) -> Result<()> {
-        let mut files = self.list_files()?;
+    /// Extract each sheet as a "## Sheet: <name>" header followed by tab-separated rows,
+    /// keeping at most `max_spreadsheet_rows` rows per sheet
+    fn extract_spreadsheet_text(&self, file_path: &Path) -> Result<String> {
+        let mut workbook: Xlsx<_> = calamine::open_workbook(file_path)
+            .map_err(|e| anyhow!("Failed to open spreadsheet: {}", e))?;
         
-        // Check if file already exists and update it, otherwise add new
-        let existing_index = files.iter().position(|f| f.id == new_file.id);
-        match existing_index {
-            Some(index) => {
-                files[index] = new_file.clone();
+        let mut sections = Vec::new();
+        for (name, range) in workbook.worksheets() {
+            let mut lines = vec![format!("## Sheet: {}", name)];
+            let total = range.height();
+            for row in range.rows().take(self.max_spreadsheet_rows) {
+                let cells: Vec<String> = row.iter().map(|cell| cell.to_string()).collect();
+                let line = cells.join("\t");
+                if !line.trim().is_empty() {
+                    lines.push(line);
+                }
             }
-            None => {
-                files.push(new_file.clone());
+            if total > self.max_spreadsheet_rows {
+                println!("[uploads] Sheet '{}' in {} truncated to {} of {} rows", name, file_path.display(), self.max_spreadsheet_rows, total);
+                lines.push(format!("[{} more rows omitted]", total - self.max_spreadsheet_rows));
             }
+            sections.push(lines.join("\n"));
         }
-        
-        self.save_index(&files)
+        Ok(sections.join("\n\n"))
+    }
+    
+    /// Render HTML to plain text: scripts and styles are dropped, headings, lists and
+    /// links keep a light markdown-like structure
+    fn extract_html_text(&self, file_path: &Path) -> Result<String> {
+        let html = fs::read(file_path)?;
+        // Wide enough that paragraphs aren't hard-wrapped
+        let text = html2text::config::plain()
+            .string_from_read(html.as_slice(), 10_000)
+            .map_err(|e| anyhow!("Failed to convert HTML to text: {}", e))?;
+        Ok(text
+            .lines()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+            .trim()
+            .to_string())
+    }
+    
+    /// OCR an image. A failed (or unavailable) OCR leaves the upload with no content
+    /// rather than failing it.
+    fn extract_image_text(&self, file_path: &Path) -> String {
+        match ocr_image(file_path) {
+            Ok(text) => text
+                .lines()
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            Err(e) => {
+                println!("[uploads] ⚠️ OCR failed for {}: {}", file_path.display(), e);
+                String::new()
+            }
+        }
+    }
+    
+    fn save_file_to_index(&self, new_file: &FileInfo) -> Result<()> {
+        let _guard = lock_index();
+        // Updates an existing entry in place, otherwise adds it
+        self.index.upsert(new_file)
     }
     
     fn save_index(&self, files: &[FileInfo]) -> Result<()> {
-        // Serialize to pretty JSON for human readability
-        let index_content = serde_json::to_string_pretty(files)?;
-        fs::write(&self.index_path, index_content)?;
-        Ok(())
+        self.index.replace_all(files)
     }
     
     pub fn list_files(&self) -> Result<Vec<FileInfo>> {
-        if !self.index_path.exists() {
-            return Ok(vec![]);
-        }
-        
-        let index_content = fs::read_to_string(&self.index_path)?;
-        let mut files: Vec<FileInfo> = serde_json::from_str(&index_content)?;
+        let _guard = lock_index();
+        self.list_files_locked()
+    }
+    
+    /// Entries `offset..offset + limit` of `list_files`, with `content` left empty unless
+    /// `include_content` is set (the summary still describes each file)
+    pub fn list_files_page(&self, offset: usize, limit: usize, include_content: bool) -> Result<FilePage> {
+        let files = self.list_files()?;
+        let total = files.len();
+        let files = files
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|mut f| {
+                if !include_content {
+                    f.content = String::new();
+                }
+                f
+            })
+            .collect();
+        Ok(FilePage { files, total, offset })
+    }
+    
+    /// `list_files` for callers already holding the index lock
+    fn list_files_locked(&self) -> Result<Vec<FileInfo>> {
+        let mut files = self.index.load()?;
         
         // Backfill summaries for older entries missing the new field
         let mut changed = false;
@@ -186,10 +778,9 @@ impl FileStorage {
     }
     
     pub fn delete_file(&self, file_id: &str) -> Result<()> {
-        let mut files = self.list_files()?;
-        
+        let _guard = lock_index();
         // Find and remove the file
-        if let Some(index) = files.iter().position(|f| f.id == file_id) {
+        if self.index.get(file_id)?.is_some() {
             // Remove the file from filesystem
             let file_path = self.uploads_dir.join(file_id);
             if file_path.exists() {
@@ -197,8 +788,7 @@ impl FileStorage {
             }
             
             // Remove from index
-            files.remove(index);
-            self.save_index(&files)?;
+            self.index.remove(file_id)?;
         }
         
         Ok(())
@@ -206,14 +796,14 @@ impl FileStorage {
 
     /// Delete all uploaded files and clear the index
     pub fn wipe_all(&self) -> Result<()> {
-        // Remove all files in uploads_dir except the index.json itself
+        // Remove all files in uploads_dir except the index itself
         if self.uploads_dir.exists() {
             for entry in fs::read_dir(&self.uploads_dir)? {
                 let entry = entry?;
                 let path = entry.path();
                 if path.is_file() {
-                    // Keep index.json handling for last
-                    if path.file_name().and_then(|n| n.to_str()) == Some("index.json") {
+                    // Keep index handling for last
+                    if UploadIndex::is_index_file(&path) {
                         continue;
                     }
                     let _ = fs::remove_file(&path);
@@ -221,50 +811,1033 @@ impl FileStorage {
             }
         }
 
-        // Clear index.json to an empty array
+        // Clear the index
+        let _guard = lock_index();
         self.save_index(&[])
     }
+
+    /// Delete files in uploads_dir that no index entry refers to, leaving the index's
+    /// own files, snapshots and hidden temp files alone. Returns how many were removed.
+    pub fn gc_orphans(&self) -> Result<usize> {
+        let _guard = lock_index();
+        let ids: HashSet<String> = self.index.load()?.into_iter().map(|f| f.id).collect();
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.uploads_dir)? {
+            let path = entry?.path();
+            if !path.is_file() || UploadIndex::is_index_file(&path) {
+                continue;
+            }
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            if name.starts_with('.') || ids.contains(name) {
+                continue;
+            }
+            fs::remove_file(&path)?;
+            println!("[uploads] Removed orphaned file {}", name);
+            removed += 1;
+        }
+        Ok(removed)
+    }
+
+    /// Save a copy of index.json (and optionally the uploaded files) before bulk
+    /// operations. Returns the snapshot id for `restore_index`.
+    pub fn snapshot_index(&self, include_files: bool) -> Result<String> {
+        let files = self.list_files()?;
+        let id = format!("{}-{}", Utc::now().format("%Y%m%dT%H%M%S%3fZ"), &Uuid::new_v4().to_string()[..8]);
+        let dir = self.snapshots_dir().join(&id);
+        fs::create_dir_all(&dir)?;
+        
+        upload_index::write_json(&dir.join(upload_index::JSON_INDEX_NAME), &files)?;
+        if include_files {
+            let files_dir = dir.join("files");
+            fs::create_dir_all(&files_dir)?;
+            for f in &files {
+                let src = self.uploads_dir.join(&f.id);
+                if src.is_file() {
+                    fs::copy(&src, files_dir.join(&f.id))?;
+                }
+            }
+        }
+        println!("[uploads] Snapshot {} saved ({} entries, files: {})", id, files.len(), include_files);
+        Ok(id)
+    }
+    
+    /// Roll the index back to a snapshot, restoring any stored files that are missing
+    pub fn restore_index(&self, snapshot_id: &str) -> Result<()> {
+        let dir = self.snapshot_dir(snapshot_id)?;
+        let content = fs::read_to_string(dir.join("index.json"))?;
+        let files: Vec<FileInfo> = serde_json::from_str(&content)?;
+        
+        // Held while copying, so `gc_orphans` can't remove files restored ahead of the index
+        let _guard = lock_index();
+        let files_dir = dir.join("files");
+        if files_dir.is_dir() {
+            for f in &files {
+                let src = files_dir.join(&f.id);
+                let dest = self.uploads_dir.join(&f.id);
+                if src.is_file() && !dest.exists() {
+                    fs::copy(&src, &dest)?;
+                }
+            }
+        }
+        self.save_index(&files)?;
+        println!("[uploads] Restored snapshot {} ({} entries)", snapshot_id, files.len());
+        Ok(())
+    }
+    
+    /// Available snapshots, newest first
+    pub fn list_index_snapshots(&self) -> Result<Vec<IndexSnapshot>> {
+        let dir = self.snapshots_dir();
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let index_path = path.join("index.json");
+            if !index_path.is_file() {
+                continue;
+            }
+            let files: Vec<FileInfo> = match fs::read_to_string(&index_path).ok().and_then(|c| serde_json::from_str(&c).ok()) {
+                Some(files) => files,
+                None => continue,
+            };
+            let created_at = fs::metadata(&index_path)?
+                .modified()
+                .map(|t| chrono::DateTime::<Utc>::from(t).to_rfc3339())
+                .unwrap_or_default();
+            snapshots.push(IndexSnapshot {
+                id: path.file_name().unwrap().to_string_lossy().to_string(),
+                created_at,
+                file_count: files.len(),
+                includes_files: path.join("files").is_dir(),
+            });
+        }
+        snapshots.sort_by(|a, b| b.id.cmp(&a.id));
+        Ok(snapshots)
+    }
+    
+    fn snapshots_dir(&self) -> PathBuf {
+        self.uploads_dir.join(SNAPSHOTS_DIR_NAME)
+    }
+    
+    fn snapshot_dir(&self, snapshot_id: &str) -> Result<PathBuf> {
+        let valid = !snapshot_id.is_empty()
+            && snapshot_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        let dir = self.snapshots_dir().join(snapshot_id);
+        if !valid || !dir.join("index.json").is_file() {
+            return Err(anyhow!("Snapshot not found: {}", snapshot_id));
+        }
+        Ok(dir)
+    }
     
     pub fn toggle_context(&self, file_id: &str) -> Result<FileInfo> {
+        let _guard = lock_index();
+        let mut file_info = self.index.get(file_id)?.ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        file_info.is_context_enabled = !file_info.is_context_enabled;
+        self.index.upsert(&file_info)?;
+        Ok(file_info)
+    }
+    
+    pub fn set_priority(&self, file_id: &str, priority: i32) -> Result<FileInfo> {
+        let _guard = lock_index();
+        let mut file_info = self.index.get(file_id)?.ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        file_info.priority = priority;
+        self.index.upsert(&file_info)?;
+        Ok(file_info)
+    }
+    
+    /// Re-run text extraction on the stored raw bytes and refresh `content`/`summary`
+    pub fn reextract(&self, file_id: &str) -> Result<FileInfo> {
+        let file = self.list_files()?
+            .into_iter()
+            .find(|f| f.id == file_id)
+            .ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        
+        let file_path = self.uploads_dir.join(file_id);
+        if !file_path.exists() {
+            return Err(anyhow!("Stored file data missing for: {}", file_id));
+        }
+        
+        // Extraction can be slow (PDF, OCR), so only the index update holds the lock
+        let content = self.extract_text_content(&file_path, file.extraction_type())?;
+        let _guard = lock_index();
+        let mut file_info = self.index.get(file_id)?.ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        file_info.summary = Self::summarize(&file_info.name, &file_info.file_type, file_info.size, &content);
+        file_info.content = content;
+        println!("[uploads] Re-extracted id={} name='{}' ({} chars)", file_info.id, file_info.name, file_info.content.len());
+        
+        self.index.upsert(&file_info)?;
+        Ok(file_info)
+    }
+    
+    /// Re-scrub one file's stored content with only `categories`, leaving everything else as is
+    pub fn scrub_categories(&self, file_id: &str, categories: &[pii_scrubber::Category], options: &pii_scrubber::ScrubOptions) -> Result<FileInfo> {
+        if categories.is_empty() {
+            return Err(anyhow!("No scrub categories selected"));
+        }
+        let _guard = lock_index();
+        let mut file = self.index.get(file_id)?.ok_or_else(|| anyhow!("File not found: {}", file_id))?;
+        
+        let (content, report) = pii_scrubber::scrub_text_categories(&file.content, categories, options);
+        file.summary = Self::summarize(&file.name, &file.file_type, file.size, &content);
+        file.content = content;
+        println!("[uploads] Scrubbed id={} name='{}' for {:?}: {} match(es)", file.id, file.name, categories, report.total);
+        
+        self.index.upsert(&file)?;
+        Ok(file)
+    }
+    
+    /// Re-extract and/or re-scrub (with `scrub_options`) every stored file, refreshing
+    /// summaries. Files that fail keep their previous content and are listed in the report.
+    pub fn reprocess_all<F>(&self, options: &ReprocessOptions, scrub_options: &pii_scrubber::ScrubOptions, mut on_progress: F) -> Result<ReprocessReport>
+    where
+        F: FnMut(&ReprocessProgress),
+    {
         let mut files = self.list_files()?;
+        let total = files.len();
+        let mut report = ReprocessReport::default();
         
-        if let Some(index) = files.iter().position(|f| f.id == file_id) {
-            files[index].is_context_enabled = !files[index].is_context_enabled;
-            let file_info = files[index].clone();
-            self.save_index(&files)?;
-            Ok(file_info)
+        for (i, file) in files.iter_mut().enumerate() {
+            match self.reprocess_content(file, options, scrub_options) {
+                Ok(content) => {
+                    let summary = Self::summarize(&file.name, &file.file_type, file.size, &content);
+                    if content != file.content {
+                        report.content_changed.push(file.id.clone());
+                    }
+                    if summary != file.summary {
+                        report.summary_changed.push(file.id.clone());
+                    }
+                    file.content = content;
+                    file.summary = summary;
+                    report.processed += 1;
+                }
+                Err(e) => report.failed.push(ReprocessFailure { file_id: file.id.clone(), error: e.to_string() }),
+            }
+            on_progress(&ReprocessProgress { done: i + 1, total, file_id: file.id.clone(), name: file.name.clone() });
+        }
+        
+        // Merge into the current index so entries added or changed meanwhile aren't lost
+        let _guard = lock_index();
+        let mut current = self.list_files_locked()?;
+        for entry in current.iter_mut() {
+            if let Some(file) = files.iter().find(|f| f.id == entry.id) {
+                entry.content = file.content.clone();
+                entry.summary = file.summary.clone();
+            }
+        }
+        self.save_index(&current)?;
+        println!(
+            "[uploads] Reprocessed {} file(s): {} content changed, {} failed",
+            report.processed, report.content_changed.len(), report.failed.len()
+        );
+        Ok(report)
+    }
+    
+    fn reprocess_content(&self, file: &FileInfo, options: &ReprocessOptions, scrub_options: &pii_scrubber::ScrubOptions) -> Result<String> {
+        let content = if options.reextract {
+            let file_path = self.uploads_dir.join(&file.id);
+            if !file_path.exists() {
+                return Err(anyhow!("Stored file data missing for: {}", file.id));
+            }
+            self.extract_text_content(&file_path, file.extraction_type())?
         } else {
-            Err(anyhow!("File not found: {}", file_id))
+            file.content.clone()
+        };
+        Ok(if options.scrub { pii_scrubber::scrub_text_string(&content, scrub_options) } else { content })
+    }
+    
+    /// Files whose name or content mention any word of `query` (case-insensitive), most
+    /// matches first; upload order breaks ties
+    pub fn search(&self, query: &str) -> Result<Vec<FileInfo>> {
+        let terms: Vec<String> = query.split_whitespace().map(|t| t.to_lowercase()).collect();
+        if terms.is_empty() {
+            return Ok(vec![]);
         }
+        
+        let mut hits: Vec<(usize, FileInfo)> = self.list_files()?
+            .into_iter()
+            .filter_map(|f| {
+                let name = f.name.to_lowercase();
+                let content = f.content.to_lowercase();
+                let count: usize = terms
+                    .iter()
+                    .map(|t| name.matches(t.as_str()).count() + content.matches(t.as_str()).count())
+                    .sum();
+                (count > 0).then_some((count, f))
+            })
+            .collect();
+        hits.sort_by_key(|(count, _)| std::cmp::Reverse(*count));
+        Ok(hits.into_iter().map(|(_, f)| f).collect())
+    }
+    
+    /// Enabled files, highest priority first; older uploads break ties
+    fn context_files(&self) -> Result<Vec<FileInfo>> {
+        let mut files: Vec<FileInfo> = self.list_files()?
+            .into_iter()
+            .filter(|f| f.is_context_enabled)
+            .collect();
+        files.sort_by(|a, b| b.priority.cmp(&a.priority).then_with(|| a.upload_date.cmp(&b.upload_date)));
+        Ok(files)
     }
     
     pub fn get_context_content(&self) -> Result<Vec<String>> {
-        let files = self.list_files()?;
-        
-        // Filter enabled files and extract content
-        let context_content: Vec<String> = files
+        let context_content: Vec<String> = self.context_files()?
             .iter()
-            .filter(|f| f.is_context_enabled)
             .map(|f| format!("File: {}\nContent:\n{}", f.name, f.content))
             .collect();
         
         Ok(context_content)
     }
+    
+    /// Like `get_context_content`, but keeps the total under `max_chars`. Files are
+    /// considered in `context_files` order; any that would
+    /// overflow the budget are dropped whole.
+    pub fn get_context_content_budgeted(&self, max_chars: usize) -> Result<Vec<String>> {
+        let files = self.context_files()?;
+        
+        let mut used = 0;
+        let mut context_content = Vec::new();
+        for f in files {
+            let entry = format!("File: {}\nContent:\n{}", f.name, f.content);
+            let len = entry.chars().count();
+            if used + len > max_chars {
+                println!("[uploads] Context budget: dropped '{}' ({} chars, priority {})", f.name, len, f.priority);
+                continue;
+            }
+            used += len;
+            context_content.push(entry);
+        }
+        
+        Ok(context_content)
+    }
+    
+    /// Fit enabled files into roughly `max_tokens`. Content is handed out in rounds of at
+    /// most `CONTEXT_CHUNK_TOKENS` per file, so every file's opening is included before
+    /// any file's later chunks; headers go highest priority first. A file too big for
+    /// what's left is cut short rather than dropped.
+    pub fn get_context_content_token_budgeted(&self, max_tokens: usize) -> Result<Vec<String>> {
+        let files = self.context_files()?;
+        
+        let headers: Vec<String> = files.iter().map(|f| format!("File: {}\nContent:\n", f.name)).collect();
+        let word_ends: Vec<Vec<usize>> = files.iter().map(|f| word_end_offsets(&f.content)).collect();
+        let chunk_words = words_for_tokens(CONTEXT_CHUNK_TOKENS).max(1);
+        let mut remaining = words_for_tokens(max_tokens);
+        let mut taken: Vec<Option<usize>> = vec![None; files.len()];
+        
+        // Every file's header first, then content a round at a time so each file's
+        // opening words land before any file gets more
+        for (i, header) in headers.iter().enumerate() {
+            let header_words = header.split_whitespace().count();
+            if remaining < header_words {
+                println!("[uploads] Token budget: no room for '{}'", files[i].name);
+                continue;
+            }
+            remaining -= header_words;
+            taken[i] = Some(0);
+        }
+        loop {
+            let wanting = (0..files.len()).filter(|&i| taken[i].is_some_and(|n| n < word_ends[i].len())).count();
+            if wanting == 0 || remaining == 0 {
+                break;
+            }
+            let share = chunk_words.min(remaining / wanting).max(1);
+            for (i, ends) in word_ends.iter().enumerate() {
+                let Some(n) = taken[i].as_mut() else { continue };
+                let more = share.min(remaining).min(ends.len() - *n);
+                *n += more;
+                remaining -= more;
+            }
+        }
+        
+        let mut context_content = Vec::new();
+        for (i, f) in files.iter().enumerate() {
+            let Some(n) = taken[i] else { continue };
+            let end = if n == 0 { 0 } else { word_ends[i][n - 1] };
+            if n < word_ends[i].len() {
+                println!("[uploads] Token budget: truncated '{}' to about {} tokens", f.name, estimate_tokens(&f.content[..end]));
+            }
+            context_content.push(format!("{}{}", headers[i], &f.content[..end]));
+        }
+        Ok(context_content)
+    }
+}
+
+/// Longest summary snippet, in bytes
+const SUMMARY_MAX_LEN: usize = 400;
+
+/// Import/include lines that make up a code file's preamble
+const IMPORT_PREFIXES: &[&str] = &[
+    "import ", "from ", "use ", "#include", "package ", "using ", "extern crate", "require", "@import",
+];
+
+/// Lines of `block` with comment/docstring markers (and markdown heading hashes) removed
+fn block_text(block: &[&str], strip_markers: bool) -> String {
+    block
+        .iter()
+        .map(|line| {
+            let mut line = line.trim();
+            if strip_markers {
+                for marker in ["\"\"\"", "'''", "/**", "/*", "*/", "///", "//!", "//", "#", "*", "--"] {
+                    line = line.trim_start_matches(marker).trim();
+                }
+                line = line.trim_end_matches("*/").trim_end_matches("\"\"\"").trim_end_matches("'''").trim();
+            }
+            line
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// A block made of comment lines, or one delimited docstring / block comment whose inner
+/// lines carry no marker of their own
+fn is_comment_block(block: &[&str]) -> bool {
+    let (first, last) = (block[0].trim(), block[block.len() - 1].trim());
+    let delimited = [("\"\"\"", "\"\"\""), ("'''", "'''"), ("/*", "*/")]
+        .iter()
+        .any(|(open, close)| first.starts_with(open) && last.ends_with(close) && (block.len() > 1 || first.len() >= open.len() + close.len()));
+    delimited
+        || block.iter().all(|line| {
+            let line = line.trim_start();
+            ["#", "//", "/*", "*", "\"\"\"", "'''", "--"].iter().any(|marker| line.starts_with(marker))
+        })
+}
+
+fn is_boilerplate(block: &[&str], text: &str, is_code: bool) -> bool {
+    if text.is_empty() {
+        return true;
+    }
+    if !is_code {
+        return false;
+    }
+    let lower = text.to_lowercase();
+    (is_comment_block(block) && ["copyright", "license", "all rights reserved"].iter().any(|w| lower.contains(w)))
+        || block.iter().all(|line| IMPORT_PREFIXES.iter().any(|p| line.trim_start().starts_with(p)))
+}
+
+/// The part of `content` worth summarizing: shebangs, encoding lines and blank lines are
+/// skipped, as are license comments and import blocks in code. Code prefers a describing
+/// comment or docstring ahead of its first code; other text runs from its first
+/// meaningful paragraph (a markdown title included).
+fn summary_text(file_type: &str, content: &str) -> String {
+    let is_code = extractor_for(file_type) == Some(Extractor::Code);
+    let strip_markers = is_code || matches!(file_type, "md" | "markdown");
+    // Shebang and editor modeline ("# -*- coding: utf-8 -*-")
+    let is_preamble = |line: &&str| line.starts_with("#!") || (line.starts_with('#') && line.contains("-*-"));
+    let lines: Vec<&str> = content.lines().filter(|line| !is_preamble(line)).collect();
+    let blocks: Vec<&[&str]> = lines
+        .split(|line| line.trim().is_empty())
+        .filter(|block| !block.is_empty())
+        .collect();
+    let meaningful: Vec<(usize, String)> = blocks
+        .iter()
+        .enumerate()
+        .map(|(i, block)| (i, block_text(block, strip_markers)))
+        .filter(|(i, text)| !is_boilerplate(blocks[*i], text, is_code))
+        .collect();
+
+    let Some((first, text)) = meaningful.first() else {
+        return String::new();
+    };
+    // Only a comment ahead of the first code describes the file; one further down
+    // describes whatever follows it
+    if is_code && is_comment_block(blocks[*first]) {
+        return text.clone();
+    }
+    blocks[*first..]
+        .iter()
+        .map(|block| block_text(block, strip_markers))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 impl FileStorage {
     fn summarize(name: &str, file_type: &str, size: u64, content: &str) -> String {
-        // Non-LLM, cheap summary: header + trimmed snippet
-        let mut snippet = content.trim();
-        if snippet.len() > 400 {
-            snippet = &snippet[..400];
-        }
-        let cleaned = snippet
-            .replace('\r', " ")
-            .replace('\n', " ")
+        // Non-LLM, cheap summary: header + the first meaningful text
+        let mut cleaned = summary_text(file_type, content)
             .split_whitespace()
             .collect::<Vec<_>>()
             .join(" ");
+        if cleaned.len() > SUMMARY_MAX_LEN {
+            let mut end = SUMMARY_MAX_LEN;
+            while !cleaned.is_char_boundary(end) {
+                end -= 1;
+            }
+            cleaned.truncate(end);
+        }
         format!("{} [{} | {} bytes] — {}", name, file_type, size, cleaned)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_storage() -> (FileStorage, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("arkangel-uploads-{}", Uuid::new_v4()));
+        (FileStorage::with_uploads_dir(dir.clone()).unwrap(), dir)
+    }
+
+    #[test]
+    fn test_gc_removes_only_orphans() {
+        let (storage, dir) = temp_storage();
+        let kept = storage.upload_file(b"keep me".to_vec(), "kept.txt".to_string()).unwrap();
+        let orphan = dir.join(Uuid::new_v4().to_string());
+        fs::write(&orphan, "left behind").unwrap();
+        fs::write(dir.join(".export.part"), "in flight").unwrap();
+
+        assert_eq!(storage.gc_orphans().unwrap(), 1);
+        assert!(!orphan.exists());
+        assert!(dir.join(&kept.id).exists());
+        assert!(dir.join(".export.part").exists());
+        assert_eq!(storage.list_files().unwrap().len(), 1);
+        assert_eq!(storage.gc_orphans().unwrap(), 0);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_gc_during_uploads_keeps_in_flight_files() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let (storage, dir) = temp_storage();
+        let done = Arc::new(AtomicBool::new(false));
+        let collector = {
+            let (dir, done) = (dir.clone(), done.clone());
+            std::thread::spawn(move || {
+                let storage = FileStorage::with_uploads_dir(dir).unwrap();
+                let mut removed = 0;
+                while !done.load(Ordering::SeqCst) {
+                    removed += storage.gc_orphans().unwrap();
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                removed
+            })
+        };
+        // Large enough that extraction takes a while, with the raw file already on disk
+        let uploaded: Vec<FileInfo> = (0..4)
+            .map(|i| storage.upload_file(format!("note {}\n", i).repeat(1 << 16).into_bytes(), format!("n{}.txt", i)).unwrap())
+            .collect();
+        done.store(true, Ordering::SeqCst);
+
+        assert_eq!(collector.join().unwrap(), 0, "nothing being uploaded is an orphan");
+        for info in &uploaded {
+            assert!(dir.join(&info.id).is_file(), "raw file of {} kept", info.name);
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), uploaded.len() + 1, "no partial files left behind");
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_list_files_page_bounds_and_content() {
+        let (storage, dir) = temp_storage();
+        for i in 0..5 {
+            storage.upload_file(format!("notes {}", i).into_bytes(), format!("n{}.txt", i)).unwrap();
+        }
+
+        let page = storage.list_files_page(1, 2, false).unwrap();
+        assert_eq!((page.total, page.offset, page.files.len()), (5, 1, 2));
+        assert_eq!(page.files[0].name, "n1.txt");
+        assert!(page.files.iter().all(|f| f.content.is_empty() && !f.summary.is_empty()));
+
+        let page = storage.list_files_page(3, 10, true).unwrap();
+        let names: Vec<&str> = page.files.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, ["n3.txt", "n4.txt"]);
+        assert_eq!(page.files[1].content, "notes 4");
+
+        let page = storage.list_files_page(9, 10, true).unwrap();
+        assert!(page.files.is_empty());
+        assert_eq!(page.total, 5);
+        assert!(storage.list_files_page(0, 0, false).unwrap().files.is_empty());
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_summary_skips_license_and_imports() {
+        let source = "#!/usr/bin/env python3\n# -*- coding: utf-8 -*-\n# Copyright 2024 Example Inc.\n# Licensed under the Apache License, Version 2.0\n\nimport os\nimport sys\nfrom pathlib import Path\n\n\"\"\"Sync invoices from the billing API into the ledger.\n\nRuns nightly from cron.\n\"\"\"\n\ndef main():\n    pass\n";
+        let summary = FileStorage::summarize("sync.py", "py", source.len() as u64, source);
+        assert_eq!(summary, format!("sync.py [py | {} bytes] — Sync invoices from the billing API into the ledger.", source.len()));
+    }
+
+    #[test]
+    fn test_summary_takes_comments_only_before_code() {
+        let docstring = "import os\n\n\"\"\"Sync invoices from the billing API\ninto the ledger, nightly.\n\"\"\"\n\ndef main():\n    pass\n";
+        assert_eq!(summary_text("py", docstring), "Sync invoices from the billing API into the ledger, nightly.");
+        let block_comment = "/* Ledger sync\n   for the billing API */\n\nint main(void);\n";
+        assert_eq!(summary_text("c", block_comment), "Ledger sync for the billing API");
+
+        let late_comment = "import os\n\ndef main():\n    pass\n\n# Helper for retries\ndef retry():\n    pass\n";
+        assert_eq!(summary_text("py", late_comment), "def main(): pass Helper for retries def retry(): pass");
+    }
+
+    #[test]
+    fn test_summary_keeps_markdown_title_and_first_paragraph() {
+        let doc = "\n\n# Project Atlas\n\nAtlas maps customer accounts to regions.\nIt replaces the old spreadsheet.\n\n## Setup\n\nRun make.\n";
+        let summary = FileStorage::summarize("README.md", "md", 10, doc);
+        assert!(
+            summary.ends_with("— Project Atlas Atlas maps customer accounts to regions. It replaces the old spreadsheet. Setup Run make."),
+            "{}",
+            summary
+        );
+
+        let long = "é".repeat(300);
+        assert!(FileStorage::summarize("a.txt", "txt", 600, &long).ends_with(&"é".repeat(200)));
+    }
+
+    #[test]
+    fn test_reextract_restores_empty_content() {
+        let (storage, dir) = temp_storage();
+        let info = storage.upload_file(b"quarterly revenue notes".to_vec(), "notes.txt".to_string()).unwrap();
+
+        // Simulate an upload whose extraction originally failed
+        let mut files = storage.list_files().unwrap();
+        files[0].content.clear();
+        storage.save_index(&files).unwrap();
+
+        let refreshed = storage.reextract(&info.id).unwrap();
+        assert_eq!(refreshed.content, "quarterly revenue notes");
+        assert!(refreshed.summary.contains("quarterly revenue notes"));
+        assert_eq!(storage.list_files().unwrap()[0].content, "quarterly revenue notes");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reextract_picks_up_new_extractor_output() {
+        let (storage, dir) = temp_storage();
+        let info = storage.upload_file(b"draft v1".to_vec(), "plan.md".to_string()).unwrap();
+
+        // Stand in for an improved extractor by changing what it reads from the stored path
+        fs::write(dir.join(&info.id), "final plan v2").unwrap();
+        let refreshed = storage.reextract(&info.id).unwrap();
+
+        assert_eq!(refreshed.content, "final plan v2");
+        assert!(refreshed.summary.ends_with("final plan v2"));
+        let stored = &storage.list_files().unwrap()[0];
+        assert_eq!(stored.content, "final plan v2");
+        assert_eq!(stored.summary, refreshed.summary);
+        assert!(storage.reextract("missing-id").is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_context_content_ordered_by_priority() {
+        let (storage, dir) = temp_storage();
+        storage.upload_file(b"first upload".to_vec(), "first.txt".to_string()).unwrap();
+        storage.upload_file(b"second upload".to_vec(), "second.txt".to_string()).unwrap();
+        let urgent = storage.upload_file(b"urgent upload".to_vec(), "urgent.txt".to_string()).unwrap();
+        storage.set_priority(&urgent.id, 3).unwrap();
+
+        let context = storage.get_context_content().unwrap();
+        let names: Vec<&str> = context.iter().map(|entry| entry.lines().next().unwrap()).collect();
+        assert_eq!(names, ["File: urgent.txt", "File: first.txt", "File: second.txt"]);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_budgeted_context_keeps_higher_priority_file() {
+        let (storage, dir) = temp_storage();
+        storage.upload_file(b"low priority background notes".to_vec(), "low.txt".to_string()).unwrap();
+        let high = storage.upload_file(b"critical meeting agenda".to_vec(), "high.txt".to_string()).unwrap();
+        storage.set_priority(&high.id, 5).unwrap();
+
+        // Room for exactly one of the two entries
+        let context = storage.get_context_content_budgeted(60).unwrap();
+        assert_eq!(context, vec!["File: high.txt\nContent:\ncritical meeting agenda".to_string()]);
+        assert_eq!(storage.get_context_content_budgeted(1000).unwrap().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_upload_from_path_matches_byte_upload() {
+        let (storage, dir) = temp_storage();
+        let source = dir.join("source");
+        fs::create_dir_all(&source).unwrap();
+        let path = source.join("report.md");
+        fs::write(&path, "# Findings\nall clear").unwrap();
+
+        let by_path = storage.upload_file_from_path(&path).unwrap();
+        let by_bytes = storage.upload_file(fs::read(&path).unwrap(), "report.md".to_string()).unwrap();
+
+        assert_eq!(by_path.name, by_bytes.name);
+        assert_eq!(by_path.file_type, by_bytes.file_type);
+        assert_eq!(by_path.size, by_bytes.size);
+        assert_eq!(by_path.content, by_bytes.content);
+        assert_eq!(by_path.summary, by_bytes.summary);
+        assert!(storage.upload_file_from_path(&source).is_err(), "directories are rejected");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_export_streams_original_bytes() {
+        let (storage, dir) = temp_storage();
+        let bytes: Vec<u8> = (0..=255u8).cycle().take(100_000).collect();
+        let info = storage.upload_file(bytes.clone(), "data.bin".to_string()).unwrap();
+        let out_dir = std::env::temp_dir().join(format!("arkangel-export-{}", Uuid::new_v4()));
+        fs::create_dir_all(&out_dir).unwrap();
+
+        let into_dir = storage.export_file_to_path(&info.id, &out_dir).unwrap();
+        assert_eq!(into_dir, out_dir.canonicalize().unwrap().join("data.bin"));
+        assert_eq!(fs::read(&into_dir).unwrap(), bytes);
+
+        let renamed = storage.export_file_to_path(&info.id, &out_dir.join("copy")).unwrap();
+        assert_eq!(renamed.file_name().unwrap(), "copy.bin");
+        assert_eq!(fs::read(&renamed).unwrap(), bytes);
+
+        assert!(storage.export_file_to_path(&info.id, &out_dir).is_err(), "never overwrites");
+        assert!(storage.export_file_to_path(&info.id, &dir).is_err(), "never exports into uploads");
+        fs::remove_dir_all(&out_dir).ok();
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_scrub_categories_only_touches_selected() {
+        let (storage, dir) = temp_storage();
+        let info = storage.upload_file(b"reach jane@example.com or 555-123-4567".to_vec(), "contact.txt".to_string()).unwrap();
+
+        let options = pii_scrubber::ScrubOptions::default();
+        let updated = storage.scrub_categories(&info.id, &[pii_scrubber::Category::Email], &options).unwrap();
+        assert_eq!(updated.content, "reach BLOCKED or 555-123-4567");
+        assert_eq!(storage.list_files().unwrap()[0].content, "reach BLOCKED or 555-123-4567");
+        assert!(storage.scrub_categories(&info.id, &[], &options).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_restore_snapshot_after_wipe() {
+        let (storage, dir) = temp_storage();
+        let info = storage.upload_file(b"keep me".to_vec(), "keep.txt".to_string()).unwrap();
+        let snapshot = storage.snapshot_index(true).unwrap();
+
+        storage.wipe_all().unwrap();
+        assert!(storage.list_files().unwrap().is_empty());
+
+        storage.restore_index(&snapshot).unwrap();
+        let files = storage.list_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].id, info.id);
+        assert_eq!(fs::read(dir.join(&info.id)).unwrap(), b"keep me");
+
+        let snapshots = storage.list_index_snapshots().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].id, snapshot);
+        assert!(snapshots[0].includes_files);
+        assert!(storage.restore_index("../index").is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reprocess_all_updates_content_and_summary() {
+        let (storage, dir) = temp_storage();
+        let a = storage.upload_file(b"contact jane@example.com".to_vec(), "a.txt".to_string()).unwrap();
+        let b = storage.upload_file(b"plain notes".to_vec(), "b.txt".to_string()).unwrap();
+
+        // Simulate a stale index entry from an older extractor
+        let mut files = storage.list_files().unwrap();
+        files[1].content = "stale".to_string();
+        files[1].summary = FileStorage::summarize(&files[1].name, &files[1].file_type, files[1].size, "stale");
+        storage.save_index(&files).unwrap();
+
+        let mut progress = Vec::new();
+        let report = storage.reprocess_all(&ReprocessOptions::default(), &pii_scrubber::ScrubOptions::default(), |p| progress.push(p.done)).unwrap();
+
+        assert_eq!(progress, vec![1, 2]);
+        assert_eq!(report.processed, 2);
+        assert_eq!(report.content_changed, vec![a.id.clone(), b.id.clone()]);
+        assert_eq!(report.summary_changed, vec![a.id.clone(), b.id.clone()]);
+        let files = storage.list_files().unwrap();
+        assert_eq!(files[0].content, "contact BLOCKED");
+        assert!(files[0].summary.ends_with("contact BLOCKED"));
+        assert_eq!(files[1].content, "plain notes");
+        assert!(files[1].summary.ends_with("plain notes"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_reprocess_all_uses_the_given_scrub_options() {
+        let (storage, dir) = temp_storage();
+        storage.upload_file(b"contact jane@example.com".to_vec(), "a.txt".to_string()).unwrap();
+
+        let options = pii_scrubber::ScrubOptions { replacement: "[REDACTED]".to_string(), ..Default::default() };
+        storage.reprocess_all(&ReprocessOptions::default(), &options, |_| {}).unwrap();
+        let files = storage.list_files().unwrap();
+        assert_eq!(files[0].content, "contact [REDACTED]");
+        assert!(files[0].summary.ends_with("contact [REDACTED]"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_pdf_extractions_limited_to_n() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let limiter = Arc::new(ExtractionLimiter::new(2));
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let workers: Vec<_> = (0..6)
+            .map(|_| {
+                let (limiter, running, peak) = (limiter.clone(), running.clone(), peak.clone());
+                std::thread::spawn(move || {
+                    let _permit = limiter.acquire();
+                    let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                    peak.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    running.fetch_sub(1, Ordering::SeqCst);
+                })
+            })
+            .collect();
+        for w in workers {
+            w.join().unwrap();
+        }
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_unreadable_image_uploads_with_empty_content() {
+        let (storage, dir) = temp_storage();
+        let info = storage.upload_file(b"not really a png".to_vec(), "screenshot.png".to_string()).unwrap();
+        assert_eq!(info.file_type, "png");
+        assert!(info.content.is_empty());
+        assert!(FileTypeInfo::for_extension("PNG").requires_ocr);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "ocr")]
+    #[test]
+    fn test_ocr_extracts_fixture_text() {
+        let (storage, dir) = temp_storage();
+        let image = include_bytes!("../tests/fixtures/ocr/hello-ocr-42.png").to_vec();
+        let info = storage.upload_file(image, "hello.png".to_string()).unwrap();
+        assert!(info.content.contains("HELLO OCR 42"), "{:?}", info.content);
+        assert!(info.summary.contains("HELLO OCR 42"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_upload_size_limit() {
+        let (mut storage, dir) = temp_storage();
+        storage.max_upload_bytes = 1024;
+
+        let info = storage.upload_file(vec![b'a'; 1024], "at-limit.txt".to_string()).unwrap();
+        assert_eq!(info.size, 1024);
+
+        let err = storage.upload_file(vec![b'a'; 1025], "over.txt".to_string()).unwrap_err().to_string();
+        assert!(err.contains("1025 bytes") && err.contains("limit is 1024 bytes"), "{}", err);
+        assert_eq!(storage.list_files().unwrap().len(), 1, "nothing written for the rejected upload");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "only the first file and index.json");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_concurrent_uploads_all_survive() {
+        let (storage, dir) = temp_storage();
+        drop(storage);
+
+        // Each thread gets its own FileStorage, like each Tauri command invocation
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    let storage = FileStorage::with_uploads_dir(dir).unwrap();
+                    let info = storage.upload_file(format!("note {}", i).into_bytes(), format!("note-{}.txt", i)).unwrap();
+                    storage.toggle_context(&info.id).unwrap();
+                    info.id
+                })
+            })
+            .collect();
+        let ids: Vec<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        let files = FileStorage::with_uploads_dir(dir.clone()).unwrap().list_files().unwrap();
+        assert_eq!(files.len(), ids.len());
+        for id in &ids {
+            let file = files.iter().find(|f| &f.id == id).expect("entry kept");
+            assert!(!file.is_context_enabled, "toggle applied to {}", id);
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_identical_upload_reuses_entry() {
+        let (storage, dir) = temp_storage();
+        let first = storage.upload_file(b"same bytes".to_vec(), "a.txt".to_string()).unwrap();
+        let second = storage.upload_file(b"same bytes".to_vec(), "copy of a.txt".to_string()).unwrap();
+
+        assert_eq!(second.id, first.id);
+        assert_eq!(first.content_hash.as_deref(), Some(sha256_hex(b"same bytes").as_str()));
+        assert_eq!(storage.list_files().unwrap().len(), 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "one stored file plus index.json");
+
+        storage.upload_file(b"other bytes".to_vec(), "b.txt".to_string()).unwrap();
+        assert_eq!(storage.list_files().unwrap().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_concurrent_identical_uploads_share_one_entry() {
+        let (storage, dir) = temp_storage();
+        drop(storage);
+
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let dir = dir.clone();
+                std::thread::spawn(move || {
+                    let storage = FileStorage::with_uploads_dir(dir).unwrap();
+                    storage.upload_file(b"same bytes".to_vec(), format!("copy-{}.txt", i)).unwrap().id
+                })
+            })
+            .collect();
+        let ids: HashSet<String> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+        assert_eq!(ids.len(), 1, "every upload reuses one entry: {:?}", ids);
+        assert_eq!(FileStorage::with_uploads_dir(dir.clone()).unwrap().list_files().unwrap().len(), 1);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "one stored file plus index.json");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_dedupe_can_be_disabled() {
+        let (mut storage, dir) = temp_storage();
+        storage.dedupe = false;
+        storage.upload_file(b"same bytes".to_vec(), "a.txt".to_string()).unwrap();
+        storage.upload_file(b"same bytes".to_vec(), "a.txt".to_string()).unwrap();
+        assert_eq!(storage.list_files().unwrap().len(), 2);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_sqlite_backed_storage() {
+        let dir = std::env::temp_dir().join(format!("arkangel-uploads-{}", Uuid::new_v4()));
+        let json = FileStorage::with_uploads_dir(dir.clone()).unwrap();
+        let kept = json.upload_file(b"from the json index".to_vec(), "old.txt".to_string()).unwrap();
+
+        let storage = FileStorage { index: UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap(), ..json };
+        assert_eq!(storage.list_files().unwrap()[0].id, kept.id, "index.json is imported");
+
+        let added = storage.upload_file(b"new upload".to_vec(), "new.txt".to_string()).unwrap();
+        assert!(!storage.toggle_context(&kept.id).unwrap().is_context_enabled);
+        storage.delete_file(&added.id).unwrap();
+
+        let files = storage.list_files().unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(!files[0].is_context_enabled);
+        storage.wipe_all().unwrap();
+        assert!(dir.join(upload_index::SQLITE_INDEX_NAME).exists(), "wipe keeps the database");
+        assert!(storage.list_files().unwrap().is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_search_matches_name_and_content() {
+        let (storage, dir) = temp_storage();
+        let by_name = storage.upload_file(b"nothing relevant".to_vec(), "Budget-2024.txt".to_string()).unwrap();
+        let by_content = storage.upload_file(b"The budget review moved to Friday".to_vec(), "notes.txt".to_string()).unwrap();
+        storage.upload_file(b"lunch menu".to_vec(), "menu.txt".to_string()).unwrap();
+
+        let ids: Vec<String> = storage.search("BUDGET").unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec![by_name.id.clone(), by_content.id.clone()]);
+        assert!(storage.search("   ").unwrap().is_empty());
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_search_ranks_by_match_count() {
+        let (storage, dir) = temp_storage();
+        let once = storage.upload_file(b"one mention of the roadmap".to_vec(), "a.txt".to_string()).unwrap();
+        let thrice = storage.upload_file(b"roadmap: roadmap owners and roadmap dates".to_vec(), "b.txt".to_string()).unwrap();
+        let twice = storage.upload_file(b"Q3 roadmap and hiring plan".to_vec(), "roadmap.md".to_string()).unwrap();
+
+        let ids: Vec<String> = storage.search("roadmap").unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec![thrice.id, twice.id, once.id]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_token_budget_respected_and_starts_kept() {
+        let (storage, dir) = temp_storage();
+        let long: String = (0..2000).map(|i| format!("alpha{} ", i)).collect();
+        storage.upload_file(long.into_bytes(), "long.txt".to_string()).unwrap();
+        storage.upload_file(b"short note\nwith two lines".to_vec(), "short.txt".to_string()).unwrap();
+
+        for budget in [20, 300, 1000] {
+            let context = storage.get_context_content_token_budgeted(budget).unwrap();
+            assert!(estimate_tokens(&context.join("\n")) <= budget, "budget {}", budget);
+            assert_eq!(context.len(), 2, "both files keep their opening words at budget {}", budget);
+            assert!(context[0].starts_with("File: long.txt\nContent:\nalpha0 alpha1"));
+        }
+        let context = storage.get_context_content_token_budgeted(1000).unwrap();
+        assert!(context[1].ends_with("short note\nwith two lines"), "small file included whole with its layout");
+        assert!(!context[0].contains("alpha1999"), "the large file is truncated");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_pdf_named_txt_extracted_as_pdf() {
+        let (storage, dir) = temp_storage();
+        let pdf = include_bytes!("../tests/fixtures/pdf/quarterly-report.pdf").to_vec();
+        let info = storage.upload_file(pdf, "report.txt".to_string()).unwrap();
+
+        assert_eq!(info.file_type, "txt");
+        assert_eq!(info.detected_type.as_deref(), Some("pdf"));
+        assert!(info.content.contains("Quarterly report body text"), "{:?}", info.content);
+        assert!(!info.content.contains("%PDF"));
+        assert_eq!(storage.reextract(&info.id).unwrap().content, info.content);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_xlsx_sheets_extracted() {
+        let (mut storage, dir) = temp_storage();
+        let workbook = include_bytes!("../tests/fixtures/xlsx/budget.xlsx").to_vec();
+        let info = storage.upload_file(workbook.clone(), "budget.xlsx".to_string()).unwrap();
+
+        assert!(info.content.contains("## Sheet: Budget\nItem\tAmount\nServers\t1200\nLicenses\t350.5"), "{}", info.content);
+        assert!(info.content.contains("## Sheet: Staff\nName\tRole\nAda\tEngineer"), "{}", info.content);
+        assert!(info.summary.contains("Servers 1200"), "{}", info.summary);
+
+        storage.max_spreadsheet_rows = 2;
+        storage.dedupe = false;
+        let capped = storage.upload_file(workbook, "budget-capped.xlsx".to_string()).unwrap();
+        assert!(capped.content.contains("Servers\t1200\n[1 more rows omitted]"), "{}", capped.content);
+        assert!(!capped.content.contains("Licenses"));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_html_upload_stripped_to_text() {
+        let (storage, dir) = temp_storage();
+        let html = b"<html><head><title>Notes</title><style>p { color: red; }</style>\
+            <script>trackVisitor('secret-token');</script></head>\
+            <body><h1>Roadmap</h1><p>Ship the <b>beta</b> in March.</p><ul><li>Hire two engineers</li></ul></body></html>";
+        let info = storage.upload_file(html.to_vec(), "notes.html".to_string()).unwrap();
+
+        assert!(info.content.contains("Roadmap"), "{}", info.content);
+        assert!(info.content.contains("Ship the beta in March."), "{}", info.content);
+        assert!(info.content.contains("Hire two engineers"), "{}", info.content);
+        for markup in ["<p>", "trackVisitor", "secret-token", "color: red"] {
+            assert!(!info.content.contains(markup), "{} left in {}", markup, info.content);
+        }
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_sniff_file_type() {
+        assert_eq!(sniff_file_type(b"\x89PNG\r\n\x1a\n....", "txt").as_deref(), Some("png"));
+        assert_eq!(sniff_file_type(&[0xFF, 0xD8, 0xFF, 0xE0], "jpeg").as_deref(), Some("jpeg"));
+        assert_eq!(sniff_file_type(b"PK\x03\x04rest", "docx").as_deref(), Some("docx"));
+        assert_eq!(sniff_file_type(b"PK\x03\x04rest", "txt").as_deref(), Some("zip"));
+        assert_eq!(sniff_file_type(b"plain words", "txt"), None);
+    }
+
+    #[test]
+    fn test_supported_file_types() {
+        let types = supported_file_types();
+        for ext in ["pdf", "txt"] {
+            let info = types.iter().find(|t| t.extension == ext).unwrap();
+            assert!(info.extractable);
+        }
+        assert!(!types.iter().any(|t| t.extension == "xyz"));
+        assert!(!FileTypeInfo::for_extension("xyz").extractable);
+    }
+}
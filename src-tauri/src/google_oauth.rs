@@ -3,16 +3,17 @@ use rand::{distributions::Alphanumeric, Rng};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::io::{Read, Write};
-use std::net::TcpListener;
+use std::net::{TcpListener, TcpStream};
 use std::time::Duration;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use base64::Engine;
 use tauri::Manager;
 use chrono::DateTime;
+use crate::sealed::{self, KeySource};
 
 #[derive(Serialize, Deserialize, Debug)]
-struct GoogleTokens {
+pub(crate) struct GoogleTokens {
   access_token: String,
   expires_in: Option<u64>,
   refresh_token: Option<String>,
@@ -22,10 +23,73 @@ struct GoogleTokens {
   obtained_at_ms: u128,
 }
 
+/// Token endpoint response, for both the code exchange and refreshes
+#[derive(Deserialize)]
+struct TokenResp {
+  access_token: String,
+  expires_in: Option<u64>,
+  refresh_token: Option<String>,
+  scope: Option<String>,
+  token_type: Option<String>,
+  id_token: Option<String>,
+}
+
+fn now_ms() -> u128 {
+  std::time::SystemTime::now()
+    .duration_since(std::time::UNIX_EPOCH)
+    .map(|d| d.as_millis())
+    .unwrap_or(0)
+}
+
 fn b64_url_no_pad(input: &[u8]) -> String {
   base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(input)
 }
 
+/// Scopes requested when `connect_google_suite` isn't given any (broad access for MCP tools)
+const DEFAULT_SCOPES: &[&str] = &[
+  // Gmail
+  "https://www.googleapis.com/auth/gmail.readonly",
+  "https://www.googleapis.com/auth/gmail.modify",
+  "https://www.googleapis.com/auth/gmail.send",
+  "https://www.googleapis.com/auth/gmail.compose",
+  "https://www.googleapis.com/auth/gmail.labels",
+  // Calendar
+  "https://www.googleapis.com/auth/calendar",
+  "https://www.googleapis.com/auth/calendar.readonly",
+  "https://www.googleapis.com/auth/calendar.events",
+  // Drive
+  "https://www.googleapis.com/auth/drive",
+  "https://www.googleapis.com/auth/drive.file",
+  "https://www.googleapis.com/auth/drive.readonly",
+  // Docs
+  "https://www.googleapis.com/auth/documents",
+  "https://www.googleapis.com/auth/documents.readonly",
+  // Sheets
+  "https://www.googleapis.com/auth/spreadsheets",
+  "https://www.googleapis.com/auth/spreadsheets.readonly",
+  // Slides
+  "https://www.googleapis.com/auth/presentations",
+  "https://www.googleapis.com/auth/presentations.readonly",
+  // Tasks
+  "https://www.googleapis.com/auth/tasks",
+  "https://www.googleapis.com/auth/tasks.readonly",
+  // Forms
+  "https://www.googleapis.com/auth/forms.body",
+  "https://www.googleapis.com/auth/forms.body.readonly",
+  "https://www.googleapis.com/auth/forms.responses.readonly",
+  // Chat (user-level scopes)
+  "https://www.googleapis.com/auth/chat.messages",
+  "https://www.googleapis.com/auth/chat.messages.readonly",
+  "https://www.googleapis.com/auth/chat.memberships",
+  "https://www.googleapis.com/auth/chat.memberships.readonly",
+  "https://www.googleapis.com/auth/chat.spaces",
+  "https://www.googleapis.com/auth/chat.spaces.readonly",
+  // OpenID / user info
+  "openid",
+  "https://www.googleapis.com/auth/userinfo.email",
+  "https://www.googleapis.com/auth/userinfo.profile",
+];
+
 fn generate_pkce_pair() -> (String, String) {
   // code_verifier must be 43-128 chars
   let verifier: String = rand::thread_rng()
@@ -42,32 +106,513 @@ fn generate_pkce_pair() -> (String, String) {
   (verifier, challenge)
 }
 
+/// Consent page URL (v2 endpoint); `scopes` are space-joined into a single `scope` param
+fn build_auth_url(client_id: &str, redirect_uri: &str, scopes: &[String], code_challenge: &str, state: &str) -> String {
+  format!(
+    "https://accounts.google.com/o/oauth2/v2/auth?response_type=code&client_id={client_id}&redirect_uri={redirect_uri}&scope={}&access_type=offline&prompt=consent&code_challenge={code_challenge}&code_challenge_method=S256&state={state}",
+    urlencoding::encode(&scopes.join(" ")),
+  )
+}
+
+/// How long to wait for the browser to come back from the consent page
+const REDIRECT_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Wait up to `timeout` for the OAuth redirect connection. The listener is consumed and
+/// dropped either way, so the port is released once this returns.
+fn accept_redirect(listener: TcpListener, timeout: Duration) -> Result<TcpStream> {
+  listener.set_nonblocking(true)?;
+  let deadline = std::time::Instant::now() + timeout;
+  loop {
+    match listener.accept() {
+      Ok((stream, _)) => {
+        stream.set_nonblocking(false)?;
+        return Ok(stream);
+      }
+      Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+        if std::time::Instant::now() >= deadline {
+          return Err(anyhow!("Google authorization timed out after {}s; please try connecting again", timeout.as_secs()));
+        }
+        std::thread::sleep(Duration::from_millis(100));
+      }
+      Err(e) => return Err(e.into()),
+    }
+  }
+}
+
+/// Opaque value round-tripped through the consent page so a redirect can be tied to
+/// the flow that started it
+fn random_state() -> String {
+  rand::thread_rng()
+    .sample_iter(&Alphanumeric)
+    .take(32)
+    .map(char::from)
+    .collect()
+}
+
+/// Authorization code from the redirect request line (`GET /?code=...&state=... HTTP/1.1`).
+/// A redirect whose `state` doesn't match the one we sent is rejected, since it wasn't
+/// started by this flow (CSRF).
+fn redirect_code(first_line: &str, expected_state: &str) -> Result<String> {
+  let query = first_line
+    .split_whitespace()
+    .nth(1)
+    .and_then(|path| path.split_once('?'))
+    .map(|(_, q)| q)
+    .unwrap_or("");
+  let param = |name: &str| {
+    query.split('&').find_map(|kv| {
+      let (k, v) = kv.split_once('=').unwrap_or((kv, ""));
+      if k == name { urlencoding::decode(v).ok().map(|v| v.to_string()) } else { None }
+    })
+  };
+
+  match param("state") {
+    Some(state) if state == expected_state => {}
+    Some(_) => return Err(anyhow!("OAuth state mismatch in redirect; ignoring a request this flow did not start")),
+    None => return Err(anyhow!("OAuth state missing from redirect")),
+  }
+  param("code").ok_or_else(|| anyhow!("Authorization code not found in redirect"))
+}
+
 fn load_env(var: &str) -> Result<String> {
   std::env::var(var).map_err(|_| anyhow!("Missing environment variable: {}", var))
 }
 
-fn tokens_path(app: &tauri::AppHandle) -> Result<PathBuf> {
+/// Normalize a redirect-listener host, allowing only loopback addresses.
+/// Returns the form usable both for binding and inside the redirect URI.
+fn loopback_bind_host(host: &str) -> Result<&'static str> {
+  match host.trim().to_lowercase().as_str() {
+    "127.0.0.1" => Ok("127.0.0.1"),
+    "localhost" => Ok("localhost"),
+    "::1" | "[::1]" => Ok("[::1]"),
+    other => Err(anyhow!("OAuth bind host must be loopback (localhost, 127.0.0.1 or [::1]), got: {}", other)),
+  }
+}
+
+/// Extract the host from a URL string like http://localhost:3000/path
+fn parse_host(uri: &str) -> Option<&str> {
+  let after_scheme = uri.split("://").nth(1)?; // localhost:3000/path
+  let host_port = after_scheme.split('/').next()?; // localhost:3000
+  if host_port.starts_with('[') {
+    host_port.split(']').next().map(|h| &host_port[..h.len() + 1]) // [::1]
+  } else {
+    host_port.split(':').next()
+  }
+}
+
+/// Load .env from current dir, then try explicit src-tauri paths
+fn load_dotenv() {
+  let _ = dotenvy::dotenv();
+  let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+  let env_candidates = [
+    manifest_dir.join(".env"),
+    manifest_dir.join("../.env"),
+    manifest_dir.join("../src-tauri/.env"),
+  ];
+  for path in env_candidates.iter() {
+    if path.exists() {
+      if dotenvy::from_path(path).is_ok() {
+        println!("[OAuth] Loaded env from {:?}", path);
+      }
+    }
+  }
+}
+
+/// HTTP client for Google endpoints, honoring the optional GOOGLE_OAUTH_MIN_TLS_VERSION
+/// and GOOGLE_OAUTH_PINNED_CERTS (comma-separated PEM paths) settings
+fn http_client() -> Result<reqwest::blocking::Client> {
+  let tls = crate::aws_uploader::TlsSettings {
+    min_version: std::env::var("GOOGLE_OAUTH_MIN_TLS_VERSION").ok().filter(|v| !v.trim().is_empty()),
+    pinned_certs: std::env::var("GOOGLE_OAUTH_PINNED_CERTS")
+      .map(|v| v.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect())
+      .unwrap_or_default(),
+  };
+  Ok(tls.client_builder()?.build()?)
+}
+
+/// Backoff between token exchange attempts; the authorization code is single-use
+/// from the user's side, so a transient server error shouldn't force a new consent
+const TOKEN_RETRY_DELAYS_MS: [u64; 3] = [500, 1000, 2000];
+
+/// POST to the token endpoint, retrying 5xx responses and timeouts. 4xx responses
+/// are returned as-is since retrying can't fix them.
+fn post_token_request(
+  client: &reqwest::blocking::Client,
+  endpoint: &str,
+  form: &[(&str, &str)],
+  delays_ms: &[u64],
+) -> Result<reqwest::blocking::Response> {
+  let mut attempt = 0;
+  loop {
+    let transient = match client.post(endpoint).form(form).send() {
+      Ok(r) if r.status().is_server_error() => format!("status {}", r.status()),
+      Ok(r) => return Ok(r),
+      Err(e) if e.is_timeout() => e.to_string(),
+      Err(e) => return Err(crate::aws_uploader::describe_send_error(e, "token request")),
+    };
+    match delays_ms.get(attempt) {
+      Some(delay) => {
+        println!("[OAuth][Connect] Token endpoint transient failure ({}), retrying in {}ms", transient, delay);
+        std::thread::sleep(Duration::from_millis(*delay));
+        attempt += 1;
+      }
+      None => return Err(anyhow!("token request failed after {} attempts: {}", attempt + 1, transient)),
+    }
+  }
+}
+
+fn google_token_endpoint() -> String {
+  std::env::var("GOOGLE_TOKEN_ENDPOINT")
+    .ok()
+    .filter(|v| !v.trim().is_empty())
+    .unwrap_or_else(|| "https://oauth2.googleapis.com/token".to_string())
+}
+
+/// Exchange `current.refresh_token` for a new access token. Google usually omits the
+/// refresh token (and sometimes the scope) on refresh, so those carry over.
+fn refresh_tokens(
+  client: &reqwest::blocking::Client,
+  endpoint: &str,
+  client_id: &str,
+  client_secret: Option<&str>,
+  current: &GoogleTokens,
+) -> Result<GoogleTokens> {
+  let refresh_token = current
+    .refresh_token
+    .as_deref()
+    .ok_or_else(|| anyhow!("no refresh token stored; reconnect Google"))?;
+  let mut form = vec![
+    ("grant_type", "refresh_token"),
+    ("refresh_token", refresh_token),
+    ("client_id", client_id),
+  ];
+  if let Some(secret) = client_secret {
+    form.push(("client_secret", secret));
+  }
+
+  let resp = post_token_request(client, endpoint, &form, &TOKEN_RETRY_DELAYS_MS)?;
+  if !resp.status().is_success() {
+    let status = resp.status();
+    let text = resp.text().unwrap_or_default();
+    if text.contains("invalid_grant") {
+      return Err(anyhow!("refresh token was revoked or has expired (invalid_grant); reconnect Google"));
+    }
+    return Err(anyhow!("token refresh failed with status {}: {}", status, text));
+  }
+  let token_resp: TokenResp = resp.json()?;
+  Ok(GoogleTokens {
+    access_token: token_resp.access_token,
+    expires_in: token_resp.expires_in,
+    refresh_token: token_resp.refresh_token.or_else(|| current.refresh_token.clone()),
+    scope: token_resp.scope.or_else(|| current.scope.clone()),
+    token_type: token_resp.token_type.or_else(|| current.token_type.clone()),
+    id_token: token_resp.id_token.or_else(|| current.id_token.clone()),
+    obtained_at_ms: now_ms(),
+  })
+}
+
+/// Refresh `email`'s stored access token, saving it to its tokens.json and the MCP credential files
+pub(crate) fn refresh_access_token(app: &tauri::AppHandle, email: &str) -> Result<GoogleTokens> {
+  let (email, current) = load_tokens(app, Some(email))?.ok_or_else(|| anyhow!("Google account {} is not connected", email))?;
+  load_dotenv();
+  let client_id = load_env("GOOGLE_CLIENT_ID")?;
+  let client_secret = std::env::var("GOOGLE_CLIENT_SECRET").ok().filter(|s| !s.is_empty());
+
+  let client = http_client()?;
+  let tokens = refresh_tokens(&client, &google_token_endpoint(), &client_id, client_secret.as_deref(), &current)?;
+  save_tokens(app, &email, &tokens)?;
+  println!("[OAuth][Refresh] Access token refreshed for {} (expires_in: {:?})", email, tokens.expires_in);
+  Ok(tokens)
+}
+
+/// Refresh this long before expiry; GOOGLE_TOKEN_REFRESH_THRESHOLD_SECS overrides the 5 minute default
+fn refresh_threshold_ms() -> u128 {
+  let secs = std::env::var("GOOGLE_TOKEN_REFRESH_THRESHOLD_SECS")
+    .ok()
+    .and_then(|v| v.trim().parse::<u64>().ok())
+    .unwrap_or(300);
+  secs as u128 * 1000
+}
+/// Leading bytes of an encrypted tokens.json, and the key file used where there is no keychain
+/// Leading bytes of an encrypted tokens.json, and the key kept beside it
+const TOKENS_MAGIC: &[u8] = b"AKGT1";
+const TOKEN_KEY_FILE: &str = "tokens.key";
+const TOKENS_FILE: &str = "tokens.json";
+/// Names the account whose tokens go to the shared ~/.calendar-mcp and ~/.gmail-mcp files
+const PRIMARY_ACCOUNT_FILE: &str = "primary";
+/// Account name used when neither the id token nor userinfo reveal the email
+const UNKNOWN_ACCOUNT_EMAIL: &str = "default@example.com";
+
+/// How often the background refresher looks at tokens.json
+const REFRESH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Whether the access token expires within `threshold_ms` of `now_ms`. Tokens without a
+/// recorded lifetime are left alone.
+fn expires_within(tokens: &GoogleTokens, now_ms: u128, threshold_ms: u128) -> bool {
+  match tokens.expires_in {
+    Some(expires_in) => tokens.obtained_at_ms + (expires_in as u128 * 1000) <= now_ms + threshold_ms,
+    None => false,
+  }
+}
+
+/// Run `refresh` only if `current` is about to expire
+fn refresh_if_due<F>(current: &GoogleTokens, now_ms: u128, threshold_ms: u128, refresh: F) -> Result<Option<GoogleTokens>>
+where
+  F: FnOnce(&GoogleTokens) -> Result<GoogleTokens>,
+{
+  if !expires_within(current, now_ms, threshold_ms) {
+    return Ok(None);
+  }
+  refresh(current).map(Some)
+}
+
+/// Outcome of refreshing every connected account
+#[derive(Debug, Default, PartialEq)]
+struct RefreshSummary {
+  refreshed: usize,
+  /// Accounts whose refresh failed, with the error
+  failed: Vec<(String, String)>,
+}
+
+/// Run `refresh` for each of `emails`. A failing account (e.g. a revoked refresh token) is
+/// logged and recorded, and the rest are still refreshed.
+fn refresh_accounts<F>(emails: &[String], mut refresh: F) -> RefreshSummary
+where
+  F: FnMut(&str) -> Result<bool>,
+{
+  let mut summary = RefreshSummary::default();
+  for email in emails {
+    match refresh(email) {
+      Ok(true) => summary.refreshed += 1,
+      Ok(false) => {}
+      Err(e) => {
+        eprintln!("[OAuth][Refresh] Failed for {}: {:#}", email, e);
+        summary.failed.push((email.clone(), format!("{:#}", e)));
+      }
+    }
+  }
+  summary
+}
+
+/// Refresh every account whose tokens are near expiry
+fn refresh_if_expiring(app: &tauri::AppHandle) -> Result<RefreshSummary> {
+  let emails = connected_accounts(app)?;
+  Ok(refresh_accounts(&emails, |email| {
+    let Some((email, current)) = load_tokens(app, Some(email))? else {
+      return Ok(false);
+    };
+    let refreshed = refresh_if_due(&current, now_ms(), refresh_threshold_ms(), |_| refresh_access_token(app, &email))?;
+    Ok(refreshed.is_some())
+  }))
+}
+
+/// Keep the stored access token (and the MCP credential files) fresh in the background
+pub fn start_token_refresher(app: tauri::AppHandle) {
+  std::thread::spawn(move || loop {
+    match refresh_if_expiring(&app) {
+      Ok(summary) if !summary.failed.is_empty() => {
+        eprintln!("[OAuth][Refresh] Background refresh failed for {} account(s)", summary.failed.len());
+      }
+      Ok(_) => {}
+      Err(e) => eprintln!("[OAuth][Refresh] Background refresh failed: {:#}", e),
+    }
+    std::thread::sleep(REFRESH_CHECK_INTERVAL);
+  });
+}
+
+/// Refresh `email`'s tokens, or every connected account's when no email is given
+#[tauri::command]
+pub fn refresh_google_tokens(app: tauri::AppHandle, email: Option<String>) -> Result<String, String> {
+  let emails = match email {
+    Some(email) => vec![email],
+    None => connected_accounts(&app).map_err(|e| e.to_string())?,
+  };
+  if emails.is_empty() {
+    return Err("Google is not connected".to_string());
+  }
+  for email in &emails {
+    refresh_access_token(&app, email).map_err(|e| {
+      eprintln!("[OAuth][Refresh] Failed for {}: {:#}", email, e);
+      format!("{:#}", e)
+    })?;
+  }
+  Ok("Google tokens refreshed".to_string())
+}
+
+/// `google_oauth` in the app data dir: one `<email>/tokens.json` per account
+fn accounts_dir(app: &tauri::AppHandle) -> Result<PathBuf> {
   let mut path = app
     .path()
     .app_data_dir()
     .map_err(|e| anyhow!("Failed to resolve app data dir: {}", e))?;
   path.push("google_oauth");
   fs::create_dir_all(&path).ok();
-  path.push("tokens.json");
   Ok(path)
 }
 
-fn save_tokens(app: &tauri::AppHandle, tokens: &GoogleTokens) -> Result<()> {
-  let path = tokens_path(app)?;
-  let json = serde_json::to_string_pretty(tokens)?;
-  fs::write(&path, json)?;
+fn account_tokens_path(dir: &Path, email: &str) -> Result<PathBuf> {
+  let email = email.trim();
+  if email.is_empty() || email.starts_with('.') || email.contains(['/', '\\']) {
+    return Err(anyhow!("Invalid Google account email: {:?}", email));
+  }
+  Ok(dir.join(email).join(TOKENS_FILE))
+}
+
+/// Emails of every account with stored tokens, sorted
+fn list_accounts(dir: &Path) -> Vec<String> {
+  let mut emails: Vec<String> = fs::read_dir(dir)
+    .map(|entries| {
+      entries
+        .flatten()
+        .filter(|e| e.path().join(TOKENS_FILE).is_file())
+        .filter_map(|e| e.file_name().to_str().map(|s| s.to_string()))
+        .collect()
+    })
+    .unwrap_or_default();
+  emails.sort();
+  emails
+}
+
+fn store_account_tokens(dir: &Path, key: &[u8; 32], email: &str, tokens: &GoogleTokens) -> Result<()> {
+  let path = account_tokens_path(dir, email)?;
+  fs::create_dir_all(path.parent().unwrap())?;
+  write_tokens_file(&path, key, tokens)
+}
+
+fn load_account_tokens(dir: &Path, key: &[u8; 32], email: &str) -> Result<Option<GoogleTokens>> {
+  let path = account_tokens_path(dir, email)?;
+  if !path.exists() {
+    return Ok(None);
+  }
+  read_tokens_file(&path, key).map(Some)
+}
+
+/// Delete an account's stored tokens. Returns whether there were any.
+fn remove_account(dir: &Path, email: &str) -> Result<bool> {
+  let path = account_tokens_path(dir, email)?;
+  let account_dir = path.parent().unwrap();
+  if !account_dir.exists() {
+    return Ok(false);
+  }
+  fs::remove_dir_all(account_dir)?;
+  Ok(true)
+}
+
+/// Whether `email` is the primary account. The first account to ask becomes primary
+/// while there is none, or the recorded one is no longer connected.
+fn claim_primary(dir: &Path, email: &str) -> Result<bool> {
+  let marker = dir.join(PRIMARY_ACCOUNT_FILE);
+  if let Ok(primary) = fs::read_to_string(&marker) {
+    let primary = primary.trim();
+    if primary == email {
+      return Ok(true);
+    }
+    if list_accounts(dir).iter().any(|account| account == primary) {
+      return Ok(false);
+    }
+  }
+  fs::write(&marker, email)?;
+  Ok(true)
+}
+
+/// Stop treating `email` as primary. Returns whether it was.
+fn release_primary(dir: &Path, email: &str) -> Result<bool> {
+  let marker = dir.join(PRIMARY_ACCOUNT_FILE);
+  if fs::read_to_string(&marker).map(|primary| primary.trim() == email).unwrap_or(false) {
+    fs::remove_file(&marker)?;
+    return Ok(true);
+  }
+  Ok(false)
+}
+
+/// Move a tokens.json written before accounts were keyed by email under its account
+fn migrate_legacy_tokens(dir: &Path, key: &[u8; 32], email_of: impl Fn(&GoogleTokens) -> String) -> Result<()> {
+  let legacy = dir.join(TOKENS_FILE);
+  if !legacy.exists() {
+    return Ok(());
+  }
+  let tokens = read_tokens_file(&legacy, key)?;
+  let email = email_of(&tokens);
+  if load_account_tokens(dir, key, &email)?.is_none() {
+    store_account_tokens(dir, key, &email, &tokens)?;
+  }
+  fs::remove_file(&legacy)?;
+  println!("[OAuth] Moved legacy tokens.json under account {}", email);
+  Ok(())
+}
+
+/// Emails of the connected Google accounts
+fn connected_accounts(app: &tauri::AppHandle) -> Result<Vec<String>> {
+  let dir = accounts_dir(app)?;
+  if dir.join(TOKENS_FILE).exists() {
+    migrate_legacy_tokens(&dir, &token_key(&dir)?, token_email)?;
+  }
+  Ok(list_accounts(&dir))
+}
+
+/// Key for the tokens files: `ARKANGEL_TOKEN_KEY` (base64, 32 bytes) if set, otherwise a
+/// key generated on first use and kept in the OS keychain. Only where there is no
+/// keychain does it live in a file in the accounts dir.
+fn token_key(dir: &Path) -> Result<[u8; 32]> {
+  sealed::load_or_create_key(&KeySource {
+    env_var: "ARKANGEL_TOKEN_KEY",
+    keychain_account: "google-tokens-key",
+    file: dir.join(TOKEN_KEY_FILE),
+  })
+}
+
+/// Encrypt `tokens` and write them to `path`, replacing it atomically
+fn write_tokens_file(path: &Path, key: &[u8; 32], tokens: &GoogleTokens) -> Result<()> {
+  let plaintext = serde_json::to_vec(tokens)?;
+  let out = sealed::seal(TOKENS_MAGIC, key, &plaintext).map_err(|_| anyhow!("encrypting Google tokens"))?;
+  sealed::write_private(path, &out)
+}
+
+/// Read an encrypted tokens file. Plaintext files written before encryption was added
+/// are still accepted; they are encrypted on the next save.
+fn read_tokens_file(path: &Path, key: &[u8; 32]) -> Result<GoogleTokens> {
+  let bytes = fs::read(path)?;
+  let plaintext = sealed::open(TOKENS_MAGIC, key, &bytes)
+    .map_err(|e| anyhow!("cannot decrypt {} ({})", path.display(), e))?;
+  let Some(plaintext) = plaintext else {
+    return serde_json::from_slice(&bytes).map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e));
+  };
+  Ok(serde_json::from_slice(&plaintext)?)
+}
+
+/// The stored tokens for `email` (or the first connected account) along with that
+/// account's email, or None if it isn't connected
+fn load_tokens(app: &tauri::AppHandle, email: Option<&str>) -> Result<Option<(String, GoogleTokens)>> {
+  let accounts = connected_accounts(app)?;
+  let Some(email) = email.map(|e| e.trim().to_string()).or_else(|| accounts.first().cloned()) else {
+    return Ok(None);
+  };
+  let dir = accounts_dir(app)?;
+  let tokens = load_account_tokens(&dir, &token_key(&dir)?, &email)?;
+  Ok(tokens.map(|t| (email, t)))
+}
+
+fn save_tokens(app: &tauri::AppHandle, email: &str, tokens: &GoogleTokens) -> Result<()> {
+  let dir = accounts_dir(app)?;
+  store_account_tokens(&dir, &token_key(&dir)?, email, tokens)?;
   
   // Automatically bridge tokens to MCP directories and credential store
-  let _ = bridge_tokens_to_mcp(app, tokens);
+  let primary = claim_primary(&dir, email)?;
+  let _ = bridge_tokens_to_mcp(email, tokens, primary);
   
   Ok(())
 }
 
+/// Account email for `tokens`: from the id token if present, else the userinfo endpoint
+fn token_email(tokens: &GoogleTokens) -> String {
+  tokens
+    .id_token
+    .as_deref()
+    .and_then(extract_email_from_id_token)
+    .or_else(|| get_user_email_from_api(&tokens.access_token))
+    .unwrap_or_else(|| UNKNOWN_ACCOUNT_EMAIL.to_string())
+}
+
 fn extract_email_from_id_token(id_token: &str) -> Option<String> {
   let parts: Vec<&str> = id_token.split('.').collect();
   if parts.len() != 3 { return None; }
@@ -80,7 +625,7 @@ fn extract_email_from_id_token(id_token: &str) -> Option<String> {
 }
 
 fn get_user_email_from_api(access_token: &str) -> Option<String> {
-  let client = reqwest::blocking::Client::new();
+  let client = http_client().ok()?;
   let resp = client
     .get("https://openidconnect.googleapis.com/v1/userinfo")
     .bearer_auth(access_token)
@@ -91,23 +636,28 @@ fn get_user_email_from_api(access_token: &str) -> Option<String> {
   v.get("email").and_then(|e| e.as_str()).map(|s| s.to_string())
 }
 
-fn bridge_tokens_to_mcp(_app: &tauri::AppHandle, tokens: &GoogleTokens) -> Result<()> {
-  // Determine user email
-  let user_email = if let Some(ref idt) = tokens.id_token {
-    extract_email_from_id_token(idt)
-  } else { None }
-  .or_else(|| get_user_email_from_api(&tokens.access_token))
-  .unwrap_or_else(|| "default@example.com".to_string());
-  println!("[OAuth][Bridge] Derived user email: {}", user_email);
+/// Write `contents` unless the file already holds exactly that, so MCP servers watching
+/// their credential files aren't triggered by no-op refreshes. Returns whether it wrote.
+fn write_if_changed(path: &std::path::Path, contents: &str) -> Result<bool> {
+  if fs::read(path).map(|existing| existing == contents.as_bytes()).unwrap_or(false) {
+    return Ok(false);
+  }
+  fs::write(path, contents)?;
+  Ok(true)
+}
+
+/// Write `tokens` to the MCP server's per-account store. Only the primary account also
+/// goes to the shared Calendar/Gmail MCP files, which hold a single account.
+fn bridge_tokens_to_mcp(user_email: &str, tokens: &GoogleTokens, primary: bool) -> Result<()> {
+  println!("[OAuth][Bridge] Bridging tokens for {}", user_email);
 
   // Compute expiry as ISO8601 naive string (YYYY-MM-DDTHH:MM:SS[.ffffff])
   let expiry_iso: Option<String> = if let Some(expires_in) = tokens.expires_in {
     let expiry_ms: u128 = tokens.obtained_at_ms + (expires_in as u128 * 1000);
     let secs = (expiry_ms / 1000) as i64;
     let nanos = ((expiry_ms % 1000) as u32) * 1_000_000;
-    if let Ok(dt) = DateTime::from_timestamp(secs, nanos) {
-      Some(dt.naive_utc().format("%Y-%m-%dT%H:%M:%S%.6f").to_string())
-    } else { None }
+    DateTime::from_timestamp(secs, nanos)
+      .map(|dt| dt.naive_utc().format("%Y-%m-%dT%H:%M:%S%.6f").to_string())
   } else { None };
 
   // Prepare credentials in the Python store format
@@ -128,24 +678,22 @@ fn bridge_tokens_to_mcp(_app: &tauri::AppHandle, tokens: &GoogleTokens) -> Resul
   });
 
   // Write to ~/.google_workspace_mcp/credentials/{email}.json (or GOOGLE_MCP_CREDENTIALS_DIR)
-  let base_dir = if let Ok(dir) = std::env::var("GOOGLE_MCP_CREDENTIALS_DIR") {
-    std::path::PathBuf::from(dir)
-  } else if let Some(home) = dirs::home_dir() {
-    home.join(".google_workspace_mcp").join("credentials")
-  } else {
-    std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-      .join(".credentials")
-  };
+  let base_dir = mcp_credentials_dir();
   fs::create_dir_all(&base_dir)?;
   let user_path = base_dir.join(format!("{}.json", user_email));
   let json_str = serde_json::to_string_pretty(&store_credentials)?;
-  fs::write(&user_path, json_str)?;
-  println!("[OAuth][Bridge] Wrote MCP credentials to {:?}", user_path);
+  if write_if_changed(&user_path, &json_str)? {
+    println!("[OAuth][Bridge] Wrote MCP credentials to {:?}", user_path);
+  } else {
+    println!("[OAuth][Bridge] MCP credentials unchanged at {:?}", user_path);
+  }
+
+  if !primary {
+    return Ok(());
+  }
 
   // Maintain existing legacy MCP outputs for Calendar/Gmail
-  let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
-  let calendar_config_dir = home_dir.join(".calendar-mcp");
-  let gmail_config_dir = home_dir.join(".gmail-mcp");
+  let [calendar_config_dir, gmail_config_dir] = legacy_mcp_dirs()?;
   fs::create_dir_all(&calendar_config_dir)?;
   fs::create_dir_all(&gmail_config_dir)?;
 
@@ -159,9 +707,11 @@ fn bridge_tokens_to_mcp(_app: &tauri::AppHandle, tokens: &GoogleTokens) -> Resul
   let calendar_creds_path = calendar_config_dir.join("credentials.json");
   let gmail_creds_path = gmail_config_dir.join("credentials.json");
   let legacy_json = serde_json::to_string_pretty(&legacy)?;
-  fs::write(&calendar_creds_path, &legacy_json)?;
-  fs::write(&gmail_creds_path, &legacy_json)?;
-  println!("[OAuth][Bridge] Wrote legacy credentials: {:?}, {:?}", calendar_creds_path, gmail_creds_path);
+  let calendar_written = write_if_changed(&calendar_creds_path, &legacy_json)?;
+  let gmail_written = write_if_changed(&gmail_creds_path, &legacy_json)?;
+  if calendar_written || gmail_written {
+    println!("[OAuth][Bridge] Wrote legacy credentials: {:?}, {:?}", calendar_creds_path, gmail_creds_path);
+  }
 
   if let Ok(client_id) = std::env::var("GOOGLE_CLIENT_ID") {
     let client_secret = std::env::var("GOOGLE_CLIENT_SECRET").unwrap_or_default();
@@ -177,14 +727,34 @@ fn bridge_tokens_to_mcp(_app: &tauri::AppHandle, tokens: &GoogleTokens) -> Resul
     let calendar_oauth_path = calendar_config_dir.join("gcp-oauth.keys.json");
     let gmail_oauth_path = gmail_config_dir.join("gcp-oauth.keys.json");
     let oauth_json = serde_json::to_string_pretty(&oauth_config)?;
-    fs::write(&calendar_oauth_path, &oauth_json)?;
-    fs::write(&gmail_oauth_path, &oauth_json)?;
-    println!("[OAuth][Bridge] Wrote legacy oauth keys: {:?}, {:?}", calendar_oauth_path, gmail_oauth_path);
+    let calendar_written = write_if_changed(&calendar_oauth_path, &oauth_json)?;
+    let gmail_written = write_if_changed(&gmail_oauth_path, &oauth_json)?;
+    if calendar_written || gmail_written {
+      println!("[OAuth][Bridge] Wrote legacy oauth keys: {:?}, {:?}", calendar_oauth_path, gmail_oauth_path);
+    }
   }
 
   Ok(())
 }
 
+/// The Calendar and Gmail MCP config dirs, each holding one account's credentials.json
+fn legacy_mcp_dirs() -> Result<[PathBuf; 2]> {
+  let home_dir = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
+  Ok([home_dir.join(".calendar-mcp"), home_dir.join(".gmail-mcp")])
+}
+
+/// Where the MCP server's per-account credential store lives
+fn mcp_credentials_dir() -> PathBuf {
+  if let Ok(dir) = std::env::var("GOOGLE_MCP_CREDENTIALS_DIR") {
+    PathBuf::from(dir)
+  } else if let Some(home) = dirs::home_dir() {
+    home.join(".google_workspace_mcp").join("credentials")
+  } else {
+    std::env::current_dir().unwrap_or_else(|_| PathBuf::from("."))
+      .join(".credentials")
+  }
+}
+
 fn open_in_browser(url: &str) -> Result<()> {
   if webbrowser::open(url).is_ok() {
     Ok(())
@@ -193,64 +763,268 @@ fn open_in_browser(url: &str) -> Result<()> {
   }
 }
 
+/// Emails of the connected Google accounts; empty when none are connected
 #[tauri::command]
-pub fn is_google_connected(app: tauri::AppHandle) -> Result<bool, String> {
-  let path = match tokens_path(&app) {
-    Ok(p) => p,
+pub fn is_google_connected(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+  let accounts = match connected_accounts(&app) {
+    Ok(a) => a,
     Err(e) => {
-      eprintln!("[OAuth][Status] Failed to resolve tokens path: {}", e);
-      return Ok(false);
+      eprintln!("[OAuth][Status] Failed to list accounts: {:#}", e);
+      return Ok(Vec::new());
     },
   };
-  let exists = path.exists();
-  println!("[OAuth][Status] Tokens path: {:?}, exists: {}", path, exists);
-  Ok(exists)
+  println!("[OAuth][Status] Connected accounts: {:?}", accounts);
+  if !accounts.is_empty() {
+    // Opportunistically refresh so callers see a usable connection
+    match refresh_if_expiring(&app) {
+      Ok(summary) => {
+        if summary.refreshed > 0 {
+          println!("[OAuth][Status] Refreshed {} near-expiry access token(s)", summary.refreshed);
+        }
+        if !summary.failed.is_empty() {
+          eprintln!("[OAuth][Status] Token refresh failed for {} account(s)", summary.failed.len());
+        }
+      }
+      Err(e) => eprintln!("[OAuth][Status] Token refresh failed: {:#}", e),
+    }
+  }
+  Ok(accounts)
+}
+
+/// Connection and access-token expiry for one account, for the settings UI
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TokenStatus {
+  pub connected: bool,
+  pub email: Option<String>,
+  /// None when Google didn't report a lifetime for the token
+  pub expires_at_ms: Option<u64>,
+  /// Negative once the access token has expired
+  pub seconds_remaining: Option<i64>,
+}
+
+/// Status of `account` (its email and stored tokens) as of `now_ms`
+fn token_status(account: Option<(String, GoogleTokens)>, now_ms: u128) -> TokenStatus {
+  let Some((account_email, tokens)) = account else {
+    return TokenStatus { connected: false, email: None, expires_at_ms: None, seconds_remaining: None };
+  };
+  let expires_at_ms = tokens.expires_in.map(|secs| tokens.obtained_at_ms + secs as u128 * 1000);
+  TokenStatus {
+    connected: true,
+    email: tokens.id_token.as_deref().and_then(extract_email_from_id_token).or(Some(account_email)),
+    expires_at_ms: expires_at_ms.map(|ms| ms as u64),
+    seconds_remaining: expires_at_ms.map(|ms| ((ms as i128 - now_ms as i128) / 1000) as i64),
+  }
+}
+
+/// Expiry of `email`'s access token (or the first account's); `connected: false` when
+/// there are no stored tokens
+#[tauri::command]
+pub fn google_token_status(app: tauri::AppHandle, email: Option<String>) -> Result<TokenStatus, String> {
+  let account = load_tokens(&app, email.as_deref()).map_err(|e| format!("{:#}", e))?;
+  Ok(token_status(account, now_ms()))
 }
 
 #[tauri::command]
-pub fn disconnect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
+pub fn list_google_accounts(app: tauri::AppHandle) -> Result<Vec<String>, String> {
+  connected_accounts(&app).map_err(|e| e.to_string())
+}
+
+/// Result of comparing the granted scopes against what an MCP server needs
+#[derive(Serialize, Debug)]
+pub struct MissingScopes {
+  pub connected: bool,
+  pub granted: Vec<String>,
+  pub missing: Vec<String>,
+}
+
+/// Expand shorthand scopes ("gmail.send", "email") to the full URLs Google reports
+fn normalize_scope(scope: &str) -> String {
+  let scope = scope.trim();
+  match scope {
+    "openid" => scope.to_string(),
+    "email" => "https://www.googleapis.com/auth/userinfo.email".to_string(),
+    "profile" => "https://www.googleapis.com/auth/userinfo.profile".to_string(),
+    s if s.starts_with("https://") => s.to_string(),
+    s => format!("https://www.googleapis.com/auth/{}", s),
+  }
+}
+
+fn missing_scopes(granted: Option<&str>, required: &[String]) -> MissingScopes {
+  let mut granted: Vec<String> = granted
+    .unwrap_or("")
+    .split_whitespace()
+    .map(normalize_scope)
+    .collect();
+  granted.sort();
+  granted.dedup();
+  let mut missing: Vec<String> = required
+    .iter()
+    .filter(|s| !s.trim().is_empty())
+    .map(|s| normalize_scope(s))
+    .filter(|s| !granted.contains(s))
+    .collect();
+  missing.sort();
+  missing.dedup();
+  MissingScopes { connected: true, granted, missing }
+}
+
+/// What `connect_google_suite` hands back to the frontend
+#[derive(Serialize, Debug)]
+pub struct ConnectResult {
+  pub message: String,
+  pub granted_scopes: Vec<String>,
+  /// Requested scopes the user deselected on the consent screen
+  pub missing_scopes: Vec<String>,
+}
+
+/// Compare the scope Google granted against what was requested. A token response
+/// without `scope` means everything requested was granted.
+fn connect_result(granted: Option<&str>, requested: &[String]) -> ConnectResult {
+  let requested_joined = requested.join(" ");
+  let diff = missing_scopes(Some(granted.unwrap_or(&requested_joined)), requested);
+  let message = if diff.missing.is_empty() {
+    "Google Suite connected successfully".to_string()
+  } else {
+    format!("Google Suite connected, but {} requested scope(s) were not granted", diff.missing.len())
+  };
+  ConnectResult { message, granted_scopes: diff.granted, missing_scopes: diff.missing }
+}
+
+/// Report which of `required` the stored Google connection (for `email`, or the first
+/// account) was not granted
+#[tauri::command]
+pub fn check_required_scopes(
+  app: tauri::AppHandle,
+  required: Vec<String>,
+  email: Option<String>,
+) -> Result<MissingScopes, String> {
+  let Some((_, tokens)) = load_tokens(&app, email.as_deref()).map_err(|e| e.to_string())? else {
+    return Ok(MissingScopes {
+      connected: false,
+      granted: Vec::new(),
+      missing: required.iter().map(|s| normalize_scope(s)).collect(),
+    });
+  };
+  let result = missing_scopes(tokens.scope.as_deref(), &required);
+  println!("[OAuth][Scopes] {} required, {} missing", required.len(), result.missing.len());
+  Ok(result)
+}
+
+/// Outcome of a live, read-only call made with the stored access token
+#[derive(Serialize, Debug)]
+pub struct AccessCheck {
+  pub ok: bool,
+  pub status: Option<u16>,
+  /// The token was rejected (401) or has passed its recorded expiry
+  pub needs_refresh: bool,
+  /// Whether the primary calendar belongs to the requested email, when the call succeeded
+  pub account_matches: Option<bool>,
+  pub error: Option<String>,
+}
+
+fn google_api_base() -> String {
+  std::env::var("GOOGLE_API_BASE_URL")
+    .ok()
+    .filter(|v| !v.trim().is_empty())
+    .unwrap_or_else(|| "https://www.googleapis.com".to_string())
+}
+
+/// Fetch the primary calendar's metadata: cheap, read-only, and its id is the account email
+fn check_access(client: &reqwest::blocking::Client, api_base: &str, access_token: &str, email: &str) -> AccessCheck {
+  let url = format!("{}/calendar/v3/calendars/primary", api_base.trim_end_matches('/'));
+  let resp = match client.get(&url).bearer_auth(access_token).send() {
+    Ok(r) => r,
+    Err(e) => {
+      return AccessCheck { ok: false, status: None, needs_refresh: false, account_matches: None, error: Some(e.to_string()) };
+    }
+  };
+  let status = resp.status();
+  if !status.is_success() {
+    let body = resp.text().unwrap_or_default();
+    return AccessCheck {
+      ok: false,
+      status: Some(status.as_u16()),
+      needs_refresh: status.as_u16() == 401,
+      account_matches: None,
+      error: Some(body.chars().take(300).collect()),
+    };
+  }
+  let account_matches = resp
+    .json::<serde_json::Value>()
+    .ok()
+    .and_then(|v| v.get("id").and_then(|id| id.as_str()).map(|id| id.eq_ignore_ascii_case(email.trim())));
+  AccessCheck { ok: true, status: Some(status.as_u16()), needs_refresh: false, account_matches, error: None }
+}
+
+/// Prove the stored token works by making a real Google API call as `email`
+#[tauri::command]
+pub fn verify_google_access(app: tauri::AppHandle, email: String) -> Result<AccessCheck, String> {
+  let (_, tokens) = load_tokens(&app, Some(&email))
+    .map_err(|e| e.to_string())?
+    .ok_or_else(|| format!("Google account {} is not connected", email))?;
+  let client = http_client().map_err(|e| e.to_string())?;
+
+  let mut check = check_access(&client, &google_api_base(), &tokens.access_token, &email);
+  if let Some(expires_in) = tokens.expires_in {
+    if tokens.obtained_at_ms + (expires_in as u128 * 1000) <= now_ms() {
+      check.needs_refresh = true;
+    }
+  }
+  println!("[OAuth][Verify] status={:?} ok={} needs_refresh={}", check.status, check.ok, check.needs_refresh);
+  Ok(check)
+}
+
+/// Disconnect `email`, or every connected account when no email is given
+#[tauri::command]
+pub fn disconnect_google_suite(app: tauri::AppHandle, email: Option<String>) -> Result<String, String> {
   println!("[OAuth][Disconnect] Starting disconnect flow...");
-  // Attempt token revoke (best-effort)
-  let path = tokens_path(&app).map_err(|e| e.to_string())?;
-  if path.exists() {
-    println!("[OAuth][Disconnect] Found tokens at {:?}. Attempting revoke...", path);
-    if let Ok(content) = fs::read_to_string(&path) {
-      if let Ok(tokens) = serde_json::from_str::<GoogleTokens>(&content) {
+  let dir = accounts_dir(&app).map_err(|e| e.to_string())?;
+  let emails = match email {
+    Some(ref email) => vec![email.trim().to_string()],
+    None => connected_accounts(&app).map_err(|e| e.to_string())?,
+  };
+  for email in &emails {
+    // Attempt token revoke (best-effort)
+    match load_tokens(&app, Some(email)) {
+      Ok(Some((_, tokens))) => {
         let has_refresh = tokens.refresh_token.is_some();
-        println!("[OAuth][Disconnect] Using {} token for revoke", if has_refresh {"refresh"} else {"access"});
+        println!("[OAuth][Disconnect] Using {} token to revoke {}", if has_refresh {"refresh"} else {"access"}, email);
         let revoke_token = tokens.refresh_token.as_deref().unwrap_or(&tokens.access_token);
-        let client = reqwest::blocking::Client::new();
-        let resp = client
-          .post("https://oauth2.googleapis.com/revoke")
-          .form(&[("token", revoke_token)])
-          .send();
+        let resp = http_client().and_then(|client| {
+          client
+            .post("https://oauth2.googleapis.com/revoke")
+            .form(&[("token", revoke_token)])
+            .send()
+            .map_err(|e| crate::aws_uploader::describe_send_error(e, "revoke request"))
+        });
         match resp {
           Ok(r) => println!("[OAuth][Disconnect] Revoke status: {}", r.status()),
-          Err(e) => eprintln!("[OAuth][Disconnect] Revoke request failed: {}", e),
+          Err(e) => eprintln!("[OAuth][Disconnect] Revoke request failed: {:#}", e),
         }
-      } else {
-        eprintln!("[OAuth][Disconnect] Failed to parse tokens.json");
       }
-    } else {
-      eprintln!("[OAuth][Disconnect] Failed to read tokens.json");
+      Ok(None) => {}
+      Err(e) => eprintln!("[OAuth][Disconnect] Failed to load tokens for {}: {:#}", email, e),
+    }
+    match remove_account(&dir, email) {
+      Ok(true) => println!("[OAuth][Disconnect] Removed tokens for {}", email),
+      Ok(false) => println!("[OAuth][Disconnect] No tokens stored for {}", email),
+      Err(e) => return Err(e.to_string()),
+    }
+    // The shared MCP files hold the primary account's tokens; they go with it
+    if release_primary(&dir, email).map_err(|e| e.to_string())? {
+      for legacy_dir in legacy_mcp_dirs().map_err(|e| e.to_string())? {
+        let _ = fs::remove_file(legacy_dir.join("credentials.json"));
+      }
+      println!("[OAuth][Disconnect] Removed the shared Calendar/Gmail MCP credentials");
     }
-    let _ = fs::remove_file(&path);
-    println!("[OAuth][Disconnect] Removed tokens file: {:?}", path);
-  } else {
-    println!("[OAuth][Disconnect] No tokens file found at {:?}", path);
   }
 
   // Remove MCP credential store files
-  let base_dir = if let Ok(dir) = std::env::var("GOOGLE_MCP_CREDENTIALS_DIR") {
-    std::path::PathBuf::from(dir)
-  } else if let Some(home) = dirs::home_dir() {
-    home.join(".google_workspace_mcp").join("credentials")
-  } else {
-    std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."))
-      .join(".credentials")
-  };
+  let base_dir = mcp_credentials_dir();
   println!("[OAuth][Disconnect] Cleaning MCP credentials in {:?}", base_dir);
-  if let Ok(entries) = fs::read_dir(&base_dir) {
+  if let Some(email) = email {
+    let _ = fs::remove_file(base_dir.join(format!("{}.json", email.trim())));
+  } else if let Ok(entries) = fs::read_dir(&base_dir) {
     for entry in entries.flatten() {
       if entry.path().extension().and_then(|s| s.to_str()) == Some("json") {
         let _ = fs::remove_file(entry.path());
@@ -261,26 +1035,9 @@ pub fn disconnect_google_suite(app: tauri::AppHandle) -> Result<String, String>
 }
 
 #[tauri::command]
-pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
+pub fn connect_google_suite(app: tauri::AppHandle, scopes: Option<Vec<String>>) -> Result<ConnectResult, String> {
   println!("[OAuth][Connect] Starting connect flow...");
-  // Load .env from current dir, then try explicit src-tauri paths
-  let _ = dotenvy::dotenv();
-  {
-    use std::path::PathBuf;
-    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    let env_candidates = [
-      manifest_dir.join(".env"),
-      manifest_dir.join("../.env"),
-      manifest_dir.join("../src-tauri/.env"),
-    ];
-    for path in env_candidates.iter() {
-      if path.exists() {
-        if dotenvy::from_path(path).is_ok() {
-          println!("[OAuth][Connect] Loaded env from {:?}", path);
-        }
-      }
-    }
-  }
+  load_dotenv();
 
   // Read secrets from env with explicit debug
   let client_id = match load_env("GOOGLE_CLIENT_ID") {
@@ -307,72 +1064,45 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
   println!("[OAuth][Connect] Flow decision: oauth_flow={}, has_secret={}, using={}", 
     oauth_flow, client_secret.is_some(), if is_web_flow { "web" } else { "desktop (PKCE)" });
 
-  // Scopes for Google services (broad access for MCP tools)
-  let scopes = vec![
-    // Gmail
-    "https://www.googleapis.com/auth/gmail.readonly",
-    "https://www.googleapis.com/auth/gmail.modify",
-    "https://www.googleapis.com/auth/gmail.send",
-    "https://www.googleapis.com/auth/gmail.compose",
-    "https://www.googleapis.com/auth/gmail.labels",
-    // Calendar
-    "https://www.googleapis.com/auth/calendar",
-    "https://www.googleapis.com/auth/calendar.readonly",
-    "https://www.googleapis.com/auth/calendar.events",
-    // Drive
-    "https://www.googleapis.com/auth/drive",
-    "https://www.googleapis.com/auth/drive.file",
-    "https://www.googleapis.com/auth/drive.readonly",
-    // Docs
-    "https://www.googleapis.com/auth/documents",
-    "https://www.googleapis.com/auth/documents.readonly",
-    // Sheets
-    "https://www.googleapis.com/auth/spreadsheets",
-    "https://www.googleapis.com/auth/spreadsheets.readonly",
-    // Slides
-    "https://www.googleapis.com/auth/presentations",
-    "https://www.googleapis.com/auth/presentations.readonly",
-    // Tasks
-    "https://www.googleapis.com/auth/tasks",
-    "https://www.googleapis.com/auth/tasks.readonly",
-    // Forms
-    "https://www.googleapis.com/auth/forms.body",
-    "https://www.googleapis.com/auth/forms.body.readonly",
-    "https://www.googleapis.com/auth/forms.responses.readonly",
-    // Chat (user-level scopes)
-    "https://www.googleapis.com/auth/chat.messages",
-    "https://www.googleapis.com/auth/chat.messages.readonly",
-    "https://www.googleapis.com/auth/chat.memberships",
-    "https://www.googleapis.com/auth/chat.memberships.readonly",
-    "https://www.googleapis.com/auth/chat.spaces",
-    "https://www.googleapis.com/auth/chat.spaces.readonly",
-    // OpenID / user info
-    "openid",
-    "https://www.googleapis.com/auth/userinfo.email",
-    "https://www.googleapis.com/auth/userinfo.profile",
-  ].join(" ");
-  println!("[OAuth][Connect] Total scopes length: {}", scopes.len());
+  let scopes: Vec<String> = scopes
+    .filter(|s| !s.is_empty())
+    .unwrap_or_else(|| DEFAULT_SCOPES.iter().map(|s| s.to_string()).collect());
+  println!("[OAuth][Connect] Requesting {} scopes", scopes.len());
 
   // Helper to parse a port number from a URL string like http://localhost:3000/path
   let parse_port = |uri: &str| -> Option<u16> {
     let after_scheme = uri.split("://").nth(1)?; // localhost:3000/path
     let host_port = after_scheme.split('/').next()?; // localhost:3000
-    host_port.split(':').nth(1)?.parse::<u16>().ok()
+    host_port.rsplit(':').next()?.parse::<u16>().ok()
   };
 
+  // Loopback host for the redirect listener (some WSL/container setups need localhost or [::1])
+  let configured_host = std::env::var("GOOGLE_OAUTH_BIND_HOST").ok();
+  if let Some(ref h) = configured_host {
+    println!("[OAuth][Connect] Using configured bind host: {}", h);
+  }
+
   // Start local server for OAuth redirect
   let (listener, redirect_uri) = if is_web_flow {
     let ru = std::env::var("GOOGLE_REDIRECT_URI")
       .unwrap_or_else(|_| "http://localhost:3000/oauth2callback".to_string());
     let port = parse_port(&ru).unwrap_or(3000);
-    let l = TcpListener::bind(format!("127.0.0.1:{}", port)).map_err(|e| {
+    // The listener must bind the same host the redirect URI points the browser at
+    let host = loopback_bind_host(parse_host(&ru).unwrap_or("127.0.0.1")).map_err(|e| e.to_string())?;
+    if let Some(ref h) = configured_host {
+      if loopback_bind_host(h).map_err(|e| e.to_string())? != host {
+        return Err(format!("GOOGLE_OAUTH_BIND_HOST ({}) does not match GOOGLE_REDIRECT_URI host ({})", h, host));
+      }
+    }
+    let l = TcpListener::bind(format!("{}:{}", host, port)).map_err(|e| {
       eprintln!("[OAuth][Connect] Failed to bind configured redirect port {}: {}", port, e);
       e.to_string()
     })?;
     println!("[OAuth][Connect] Redirect URI (web flow): {}", ru);
     (l, ru)
   } else {
-    let l = TcpListener::bind("127.0.0.1:0").map_err(|e| {
+    let host = loopback_bind_host(configured_host.as_deref().unwrap_or("127.0.0.1")).map_err(|e| e.to_string())?;
+    let l = TcpListener::bind(format!("{}:0", host)).map_err(|e| {
       eprintln!("[OAuth][Connect] Failed to bind local port: {}", e);
       e.to_string()
     })?;
@@ -384,18 +1114,16 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
       eprintln!("[OAuth][Connect] Failed to read local addr: {}", e);
       e.to_string()
     })?.port();
-    let ru = format!("http://127.0.0.1:{}", port);
+    let ru = format!("http://{}:{}", host, port);
     println!("[OAuth][Connect] Redirect URI (desktop flow): {}", ru);
     (l, ru)
   };
 
   let (code_verifier, code_challenge) = generate_pkce_pair();
   println!("[OAuth][Connect] Generated PKCE pair (verifier: {} chars)", code_verifier.len());
+  let state = random_state();
 
-  // Build authorization URL (use v2 endpoint)
-  let auth_url = format!(
-    "https://accounts.google.com/o/oauth2/v2/auth?response_type=code&client_id={client_id}&redirect_uri={redirect_uri}&scope={scopes}&access_type=offline&prompt=consent&code_challenge={code_challenge}&code_challenge_method=S256",
-  );
+  let auth_url = build_auth_url(&client_id, &redirect_uri, &scopes, &code_challenge, &state);
   println!("[OAuth][Connect] Opening browser for consent page...");
 
   open_in_browser(&auth_url).map_err(|e| {
@@ -405,15 +1133,8 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
 
   // Accept single connection for redirect
   println!("[OAuth][Connect] Waiting for OAuth redirect on {}...", redirect_uri);
-  listener
-    .set_nonblocking(false)
-    .map_err(|e| {
-      eprintln!("[OAuth][Connect] Failed to set blocking mode (second time): {}", e);
-      e.to_string()
-    })?;
-
-  let (mut stream, _) = listener.accept().map_err(|e| {
-    eprintln!("[OAuth][Connect] Failed to accept redirect: {}", e);
+  let mut stream = accept_redirect(listener, REDIRECT_TIMEOUT).map_err(|e| {
+    eprintln!("[OAuth][Connect] Failed to accept redirect: {:#}", e);
     e.to_string()
   })?;
   stream
@@ -428,33 +1149,16 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
   let req = String::from_utf8_lossy(&buffer[..n]);
   if let Some(first_line) = req.lines().next() { println!("[OAuth][Connect] Redirect first line: {}", first_line); }
 
-  // Parse the first line: GET /?code=... HTTP/1.1
   let first_line = req.lines().next().unwrap_or("");
-  let code_opt = first_line
-    .split_whitespace()
-    .nth(1)
-    .and_then(|path| {
-      let parts: Vec<&str> = path.split('?').collect();
-      if parts.len() < 2 { return None; }
-      let query = parts[1];
-      for kv in query.split('&') {
-        let mut it = kv.splitn(2, '=');
-        let k = it.next()?;
-        let v = it.next().unwrap_or("");
-        if k == "code" { return Some(urlencoding::decode(v).ok()?.to_string()); }
-      }
-      None
-    });
-
-  let code = match code_opt {
-    Some(c) => {
+  let code = match redirect_code(first_line, &state) {
+    Ok(c) => {
       println!("[OAuth][Connect] Received authorization code (len: {})", c.len());
       c
     },
-    None => {
+    Err(e) => {
       let _ = stream.write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Type: text/plain\r\nContent-Length: 12\r\n\r\nBad Request");
-      eprintln!("[OAuth][Connect] Authorization code not found in redirect");
-      return Err("Authorization code not found in redirect".into());
+      eprintln!("[OAuth][Connect] Rejected redirect: {}", e);
+      return Err(e.to_string());
     }
   };
 
@@ -462,8 +1166,8 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
   let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\n\r\n<html><body><h2>Google authorization received.</h2><p>You can close this window and return to ArkAngel.</p></body></html>");
 
   // Exchange code for tokens
-  let token_endpoint = "https://oauth2.googleapis.com/token";
-  let client = reqwest::blocking::Client::new();
+  let token_endpoint = google_token_endpoint();
+  let client = http_client().map_err(|e| e.to_string())?;
   println!("[OAuth][Connect] Exchanging code for tokens...");
 
   let mut form = vec![
@@ -479,18 +1183,14 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
     }
   }
 
-  let resp = match client
-    .post(token_endpoint)
-    .form(&form)
-    .send()
-  {
+  let resp = match post_token_request(&client, &token_endpoint, &form, &TOKEN_RETRY_DELAYS_MS) {
     Ok(r) => {
       println!("[OAuth][Connect] Token endpoint status: {}", r.status());
       r
     },
     Err(e) => {
-      eprintln!("[OAuth][Connect] Token request failed: {}", e);
-      return Err(e.to_string());
+      eprintln!("[OAuth][Connect] Token request failed: {:#}", e);
+      return Err(format!("{:#}", e));
     }
   };
 
@@ -504,16 +1204,6 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
     return Err(format!("Token exchange failed: {}", text));
   }
 
-  #[derive(Deserialize)]
-  struct TokenResp {
-    access_token: String,
-    expires_in: Option<u64>,
-    refresh_token: Option<String>,
-    scope: Option<String>,
-    token_type: Option<String>,
-    id_token: Option<String>,
-  }
-
   let token_resp: TokenResp = resp.json().map_err(|e| {
     eprintln!("[OAuth][Connect] Failed parsing token JSON: {}", e);
     e.to_string()
@@ -538,11 +1228,428 @@ pub fn connect_google_suite(app: tauri::AppHandle) -> Result<String, String> {
     tokens.id_token.is_some()
   );
 
-  save_tokens(&app, &tokens).map_err(|e| {
+  let email = token_email(&tokens);
+  save_tokens(&app, &email, &tokens).map_err(|e| {
     eprintln!("[OAuth][Connect] Failed to save/bridge tokens: {}", e);
     e.to_string()
   })?;
   println!("[OAuth][Connect] Tokens saved and bridged to MCP stores");
 
-  Ok("Google Suite connected successfully".to_string())
-} 
\ No newline at end of file
+  let result = connect_result(tokens.scope.as_deref(), &scopes);
+  if !result.missing_scopes.is_empty() {
+    println!("[OAuth][Connect] Consent did not grant {} requested scope(s): {:?}", result.missing_scopes.len(), result.missing_scopes);
+  }
+  Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_loopback_bind_host() {
+    assert_eq!(loopback_bind_host("127.0.0.1").unwrap(), "127.0.0.1");
+    assert_eq!(loopback_bind_host("localhost").unwrap(), "localhost");
+    assert_eq!(loopback_bind_host("::1").unwrap(), "[::1]");
+    assert!(loopback_bind_host("0.0.0.0").is_err());
+    assert!(loopback_bind_host("192.168.1.20").is_err());
+  }
+
+  #[test]
+  fn test_parse_redirect_host() {
+    assert_eq!(parse_host("http://localhost:3000/oauth2callback"), Some("localhost"));
+    assert_eq!(parse_host("http://[::1]:3000/cb"), Some("[::1]"));
+  }
+
+  #[test]
+  fn test_token_exchange_retries_transient_5xx() {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/token", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || {
+      for (i, request) in server.incoming_requests().enumerate() {
+        let response = if i == 0 {
+          tiny_http::Response::from_string("unavailable").with_status_code(503)
+        } else {
+          tiny_http::Response::from_string(r#"{"access_token":"abc"}"#)
+        };
+        let _ = request.respond(response);
+      }
+    });
+
+    let client = reqwest::blocking::Client::new();
+    let resp = post_token_request(&client, &endpoint, &[("code", "xyz")], &[10, 20]).unwrap();
+    assert!(resp.status().is_success());
+    assert_eq!(resp.text().unwrap(), r#"{"access_token":"abc"}"#);
+  }
+
+  #[test]
+  fn test_token_exchange_does_not_retry_4xx() {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/token", server.server_addr().to_ip().unwrap());
+    let hits = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let seen = hits.clone();
+    std::thread::spawn(move || {
+      for request in server.incoming_requests() {
+        seen.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let _ = request.respond(tiny_http::Response::from_string("invalid_grant").with_status_code(400));
+      }
+    });
+
+    let client = reqwest::blocking::Client::new();
+    let resp = post_token_request(&client, &endpoint, &[("code", "xyz")], &[10, 20]).unwrap();
+    assert_eq!(resp.status().as_u16(), 400);
+    assert_eq!(hits.load(std::sync::atomic::Ordering::SeqCst), 1);
+  }
+
+  #[test]
+  fn test_redirect_state_mismatch_rejected() {
+    let err = redirect_code("GET /?code=4%2Fabc&state=forged HTTP/1.1", "expected").unwrap_err();
+    assert!(err.to_string().contains("state mismatch"), "{}", err);
+    assert!(redirect_code("GET /?code=4%2Fabc HTTP/1.1", "expected").is_err(), "missing state is rejected too");
+
+    // A matching state yields the decoded code, which is what goes to the token endpoint
+    let (endpoint, form) = spawn_token_mock(200, r#"{"access_token":"abc"}"#);
+    let state = random_state();
+    let code = redirect_code(&format!("GET /oauth2callback?state={}&code=4%2Fabc HTTP/1.1", state), &state).unwrap();
+    assert_eq!(code, "4/abc");
+    let client = reqwest::blocking::Client::new();
+    let resp = post_token_request(&client, &endpoint, &[("code", code.as_str())], &[]).unwrap();
+    assert!(resp.status().is_success());
+    assert!(form.lock().unwrap().contains("code=4%2Fabc"));
+  }
+
+  #[test]
+  fn test_custom_scopes_in_auth_url() {
+    let scopes = vec![
+      "https://www.googleapis.com/auth/calendar.readonly".to_string(),
+      "openid".to_string(),
+    ];
+    let url = build_auth_url("client-1", "http://127.0.0.1:5000", &scopes, "challenge", "st");
+    assert!(
+      url.contains("&scope=https%3A%2F%2Fwww.googleapis.com%2Fauth%2Fcalendar.readonly%20openid&"),
+      "{}",
+      url
+    );
+    assert!(!url.contains("gmail"), "defaults are not mixed in: {}", url);
+  }
+
+  #[test]
+  fn test_tokens_file_encrypted_at_rest() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("tokens.json");
+    let key = [3u8; 32];
+    write_tokens_file(&path, &key, &stored_tokens()).unwrap();
+
+    let raw = fs::read(&path).unwrap();
+    assert!(serde_json::from_slice::<serde_json::Value>(&raw).is_err(), "tokens.json must not be plain JSON");
+    assert!(!String::from_utf8_lossy(&raw).contains("refresh-1"));
+
+    let loaded = read_tokens_file(&path, &key).unwrap();
+    assert_eq!(loaded.access_token, "old-access");
+    assert_eq!(loaded.refresh_token.as_deref(), Some("refresh-1"));
+    assert!(read_tokens_file(&path, &[4u8; 32]).is_err(), "wrong key is rejected");
+
+    // Files from before encryption still load
+    fs::write(&path, serde_json::to_string_pretty(&stored_tokens()).unwrap()).unwrap();
+    assert_eq!(read_tokens_file(&path, &key).unwrap().access_token, "old-access");
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_redirect_wait_times_out() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let started = std::time::Instant::now();
+    let err = accept_redirect(listener, Duration::from_millis(300)).unwrap_err();
+    assert!(err.to_string().contains("timed out"), "{}", err);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert!(TcpListener::bind(addr).is_ok(), "port is released after the timeout");
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || TcpStream::connect(addr).unwrap());
+    assert!(accept_redirect(listener, Duration::from_secs(5)).is_ok());
+  }
+
+  #[test]
+  fn test_accounts_stored_separately() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    let key = [5u8; 32];
+    let jane = GoogleTokens { access_token: "jane-access".to_string(), ..stored_tokens() };
+    let bob = GoogleTokens { access_token: "bob-access".to_string(), ..stored_tokens() };
+    store_account_tokens(&dir, &key, "jane@example.com", &jane).unwrap();
+    store_account_tokens(&dir, &key, "bob@example.com", &bob).unwrap();
+
+    assert_eq!(list_accounts(&dir), vec!["bob@example.com", "jane@example.com"]);
+    assert_eq!(load_account_tokens(&dir, &key, "jane@example.com").unwrap().unwrap().access_token, "jane-access");
+    assert_eq!(load_account_tokens(&dir, &key, "bob@example.com").unwrap().unwrap().access_token, "bob-access");
+
+    assert!(remove_account(&dir, "jane@example.com").unwrap());
+    assert_eq!(list_accounts(&dir), vec!["bob@example.com"]);
+    assert!(load_account_tokens(&dir, &key, "jane@example.com").unwrap().is_none());
+    assert_eq!(load_account_tokens(&dir, &key, "bob@example.com").unwrap().unwrap().access_token, "bob-access");
+    assert!(account_tokens_path(&dir, "../evil").is_err());
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_only_the_primary_account_is_bridged_to_shared_files() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    let key = [5u8; 32];
+    store_account_tokens(&dir, &key, "jane@example.com", &stored_tokens()).unwrap();
+    store_account_tokens(&dir, &key, "bob@example.com", &stored_tokens()).unwrap();
+
+    assert!(claim_primary(&dir, "jane@example.com").unwrap(), "the first account becomes primary");
+    assert!(!claim_primary(&dir, "bob@example.com").unwrap());
+    assert!(claim_primary(&dir, "jane@example.com").unwrap());
+
+    assert!(!release_primary(&dir, "bob@example.com").unwrap());
+    assert!(release_primary(&dir, "jane@example.com").unwrap());
+    remove_account(&dir, "jane@example.com").unwrap();
+    assert!(claim_primary(&dir, "bob@example.com").unwrap(), "another account takes over");
+
+    // A primary that was removed without being released doesn't block the others
+    store_account_tokens(&dir, &key, "jane@example.com", &stored_tokens()).unwrap();
+    remove_account(&dir, "bob@example.com").unwrap();
+    assert!(claim_primary(&dir, "jane@example.com").unwrap());
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_legacy_tokens_moved_under_account() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let key = [5u8; 32];
+    write_tokens_file(&dir.join(TOKENS_FILE), &key, &stored_tokens()).unwrap();
+
+    migrate_legacy_tokens(&dir, &key, |_| "jane@example.com".to_string()).unwrap();
+    assert!(!dir.join(TOKENS_FILE).exists());
+    assert_eq!(list_accounts(&dir), vec!["jane@example.com"]);
+    assert_eq!(load_account_tokens(&dir, &key, "jane@example.com").unwrap().unwrap().access_token, "old-access");
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_connect_result_reports_deselected_scopes() {
+    let requested = vec![
+      "openid".to_string(),
+      "https://www.googleapis.com/auth/gmail.readonly".to_string(),
+      "https://www.googleapis.com/auth/calendar.readonly".to_string(),
+    ];
+    let body = r#"{"access_token":"a","scope":"https://www.googleapis.com/auth/calendar.readonly openid"}"#;
+    let token_resp: TokenResp = serde_json::from_str(body).unwrap();
+    let result = connect_result(token_resp.scope.as_deref(), &requested);
+    assert_eq!(result.granted_scopes, vec!["https://www.googleapis.com/auth/calendar.readonly", "openid"]);
+    assert_eq!(result.missing_scopes, vec!["https://www.googleapis.com/auth/gmail.readonly"]);
+    assert!(result.message.contains("1 requested scope"), "{}", result.message);
+
+    let all = connect_result(None, &requested);
+    assert!(all.missing_scopes.is_empty());
+    assert_eq!(all.message, "Google Suite connected successfully");
+  }
+
+  #[test]
+  fn test_token_status_valid_and_expired() {
+    // Header and signature don't matter; only the payload is decoded
+    let payload = b64_url_no_pad(br#"{"email":"jane@example.com"}"#);
+    let tokens = GoogleTokens { id_token: Some(format!("e30.{}.sig", payload)), ..stored_tokens() };
+
+    let valid = token_status(Some(("default@example.com".to_string(), tokens)), 3_600_000 - 42 * 60 * 1000);
+    assert_eq!(valid, TokenStatus {
+      connected: true,
+      email: Some("jane@example.com".to_string()),
+      expires_at_ms: Some(3_600_000),
+      seconds_remaining: Some(42 * 60),
+    });
+
+    let expired = token_status(Some(("bob@example.com".to_string(), stored_tokens())), 3_600_000 + 90_000);
+    assert!(expired.connected);
+    assert_eq!(expired.email.as_deref(), Some("bob@example.com"), "falls back to the account name");
+    assert_eq!(expired.seconds_remaining, Some(-90));
+  }
+
+  #[test]
+  fn test_token_status_without_tokens_file() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    let account = load_account_tokens(&dir, &[5u8; 32], "jane@example.com").unwrap();
+    let status = token_status(account.map(|t| ("jane@example.com".to_string(), t)), now_ms());
+    assert_eq!(status, TokenStatus { connected: false, email: None, expires_at_ms: None, seconds_remaining: None });
+  }
+
+  fn stored_tokens() -> GoogleTokens {
+    GoogleTokens {
+      access_token: "old-access".to_string(),
+      expires_in: Some(3600),
+      refresh_token: Some("refresh-1".to_string()),
+      scope: Some("openid email".to_string()),
+      token_type: Some("Bearer".to_string()),
+      id_token: None,
+      obtained_at_ms: 0,
+    }
+  }
+
+  fn spawn_token_mock(status: u16, body: &'static str) -> (String, std::sync::Arc<std::sync::Mutex<String>>) {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/token", server.server_addr().to_ip().unwrap());
+    let last_form = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    let seen = last_form.clone();
+    std::thread::spawn(move || {
+      for mut request in server.incoming_requests() {
+        let mut form = String::new();
+        let _ = request.as_reader().read_to_string(&mut form);
+        *seen.lock().unwrap() = form;
+        let _ = request.respond(tiny_http::Response::from_string(body).with_status_code(status));
+      }
+    });
+    (endpoint, last_form)
+  }
+
+  #[test]
+  fn test_refresh_keeps_existing_refresh_token() {
+    let (endpoint, form) = spawn_token_mock(200, r#"{"access_token":"new-access","expires_in":3599,"token_type":"Bearer"}"#);
+    let client = reqwest::blocking::Client::new();
+    let tokens = refresh_tokens(&client, &endpoint, "client-1", None, &stored_tokens()).unwrap();
+
+    assert_eq!(tokens.access_token, "new-access");
+    assert_eq!(tokens.expires_in, Some(3599));
+    assert_eq!(tokens.refresh_token.as_deref(), Some("refresh-1"), "Google omitted it, so the old one is kept");
+    assert_eq!(tokens.scope.as_deref(), Some("openid email"));
+    assert!(tokens.obtained_at_ms > 0);
+    let form = form.lock().unwrap().clone();
+    assert!(form.contains("grant_type=refresh_token") && form.contains("refresh_token=refresh-1"), "{}", form);
+  }
+
+  #[test]
+  fn test_refresh_invalid_grant_asks_to_reconnect() {
+    let (endpoint, _) = spawn_token_mock(400, r#"{"error":"invalid_grant","error_description":"Token has been expired or revoked."}"#);
+    let client = reqwest::blocking::Client::new();
+    let err = refresh_tokens(&client, &endpoint, "client-1", None, &stored_tokens()).unwrap_err();
+    assert!(err.to_string().contains("invalid_grant"), "{}", err);
+    assert!(err.to_string().contains("reconnect"), "{}", err);
+  }
+
+  #[test]
+  fn test_one_failing_account_does_not_stop_the_others() {
+    let emails = ["a@example.com", "revoked@example.com", "c@example.com"].map(String::from);
+    let mut tried = Vec::new();
+    let summary = refresh_accounts(&emails, |email| {
+      tried.push(email.to_string());
+      if email.starts_with("revoked") { Err(anyhow!("invalid_grant")) } else { Ok(true) }
+    });
+    assert_eq!(tried, emails);
+    assert_eq!(summary, RefreshSummary {
+      refreshed: 2,
+      failed: vec![("revoked@example.com".to_string(), "invalid_grant".to_string())],
+    });
+  }
+
+  #[test]
+  fn test_only_near_expiry_tokens_are_refreshed() {
+    let (endpoint, form) = spawn_token_mock(200, r#"{"access_token":"new-access","expires_in":3599}"#);
+    let client = reqwest::blocking::Client::new();
+    let refresh = |current: &GoogleTokens| refresh_tokens(&client, &endpoint, "client-1", None, current);
+    let threshold = 5 * 60 * 1000;
+
+    // Obtained at 0 and valid for three hours: an hour in, there is nothing to do
+    let long_lived = GoogleTokens { expires_in: Some(3 * 3600), ..stored_tokens() };
+    assert!(refresh_if_due(&long_lived, 3_600_000, threshold, refresh).unwrap().is_none());
+    assert!(form.lock().unwrap().is_empty(), "no call for a token with hours left");
+
+    // Two minutes before a one-hour token expires falls inside the 5 minute window
+    let imminent = 3_600_000 - 2 * 60 * 1000;
+    let refreshed = refresh_if_due(&stored_tokens(), imminent, threshold, refresh).unwrap().unwrap();
+    assert_eq!(refreshed.access_token, "new-access");
+    assert!(form.lock().unwrap().contains("grant_type=refresh_token"));
+
+    let no_lifetime = GoogleTokens { expires_in: None, ..stored_tokens() };
+    assert!(!expires_within(&no_lifetime, u128::MAX / 2, threshold));
+  }
+
+  #[test]
+  fn test_missing_gmail_scope_reported() {
+    let granted = "openid https://www.googleapis.com/auth/userinfo.email https://www.googleapis.com/auth/gmail.readonly";
+    let required = vec![
+      "gmail.readonly".to_string(),
+      "https://www.googleapis.com/auth/gmail.send".to_string(),
+      "email".to_string(),
+    ];
+    let result = missing_scopes(Some(granted), &required);
+    assert_eq!(result.missing, vec!["https://www.googleapis.com/auth/gmail.send"]);
+    assert_eq!(missing_scopes(None, &required).missing.len(), 3);
+  }
+
+  #[test]
+  fn test_unchanged_credentials_not_rewritten() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("user@example.com.json");
+
+    assert!(write_if_changed(&path, r#"{"token":"a"}"#).unwrap());
+    let first_write = fs::metadata(&path).unwrap().modified().unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+
+    assert!(!write_if_changed(&path, r#"{"token":"a"}"#).unwrap(), "no-op refresh must not rewrite");
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), first_write);
+    assert!(write_if_changed(&path, r#"{"token":"b"}"#).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"token":"b"}"#);
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_unchanged_refresh_leaves_mcp_credentials_alone() {
+    let dir = std::env::temp_dir().join(format!("arkangel-oauth-{}", uuid::Uuid::new_v4()));
+    std::env::set_var("GOOGLE_MCP_CREDENTIALS_DIR", &dir);
+    let path = dir.join("jane@example.com.json");
+
+    // Not primary, so nothing is written outside the temp dir
+    bridge_tokens_to_mcp("jane@example.com", &stored_tokens(), false).unwrap();
+    let contents = fs::read_to_string(&path).unwrap();
+    let written = fs::metadata(&path).unwrap().modified().unwrap();
+    std::thread::sleep(Duration::from_millis(20));
+
+    bridge_tokens_to_mcp("jane@example.com", &stored_tokens(), false).unwrap();
+    std::env::remove_var("GOOGLE_MCP_CREDENTIALS_DIR");
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), written, "an unchanged refresh must not rewrite");
+    assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+    assert!(contents.contains("\"token\": \"old-access\""), "{}", contents);
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  fn spawn_calendar_mock(status: u16, body: &'static str) -> String {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", server.server_addr().to_ip().unwrap());
+    std::thread::spawn(move || {
+      for request in server.incoming_requests() {
+        assert_eq!(request.url(), "/calendar/v3/calendars/primary");
+        let authorized = request
+          .headers()
+          .iter()
+          .any(|h| h.field.equiv("Authorization") && h.value.as_str() == "Bearer tok");
+        assert!(authorized, "access token must be sent");
+        let _ = request.respond(tiny_http::Response::from_string(body).with_status_code(status));
+      }
+    });
+    base
+  }
+
+  #[test]
+  fn test_verify_access_success() {
+    let base = spawn_calendar_mock(200, r#"{"id":"Jane@Example.com","summary":"Jane"}"#);
+    let check = check_access(&reqwest::blocking::Client::new(), &base, "tok", "jane@example.com");
+    assert!(check.ok);
+    assert_eq!(check.status, Some(200));
+    assert!(!check.needs_refresh);
+    assert_eq!(check.account_matches, Some(true));
+  }
+
+  #[test]
+  fn test_verify_access_401_needs_refresh() {
+    let base = spawn_calendar_mock(401, r#"{"error":{"code":401,"message":"Invalid Credentials"}}"#);
+    let check = check_access(&reqwest::blocking::Client::new(), &base, "tok", "jane@example.com");
+    assert!(!check.ok);
+    assert_eq!(check.status, Some(401));
+    assert!(check.needs_refresh);
+    assert!(check.error.unwrap().contains("Invalid Credentials"));
+  }
+}
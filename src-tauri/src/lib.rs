@@ -4,13 +4,21 @@ mod pii_scrubber;
 mod aws_uploader;
 mod google_oauth;
 mod file_storage;
+mod upload_index;
+mod memory_store;
+mod panic_guard;
+mod pseudonym;
+mod sealed;
+mod sidecar;
+mod sigv4;
 
-use std::process::{Command as StdCommand, Stdio, Child};
-use std::sync::Mutex;
+use std::process::Command as StdCommand;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::io::{BufRead, BufReader};
 use tauri::Manager;
 
+use sidecar::SidecarSlot;
+
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
@@ -27,52 +35,251 @@ fn set_window_height(window: tauri::WebviewWindow, height: u32) -> Result<(), St
   
   let new_size = LogicalSize::new(700.0, height as f64);
   
-  match window.set_size(Size::Logical(new_size)) {
-    Ok(_) => {
-      if let Err(e) = window::position_window_top_center(&window, 54) {
-        eprintln!("Failed to reposition window: {}", e);
-      }
-      Ok(())
-    }
-    Err(e) => Err(format!("Failed to resize window: {}", e))
+  // Keep the window where it is; re-centering here would undo a restored position
+  window::resize_in_place(&window, Size::Logical(new_size))
+    .map_err(|e| format!("Failed to resize window: {}", e))
+}
+
+/// Hide the window, or show it again at top-center; returns whether it is now visible
+#[tauri::command]
+fn toggle_window_visibility(window: tauri::WebviewWindow) -> Result<bool, String> {
+  window::toggle_visibility(&window).map_err(|e| format!("Failed to toggle window: {}", e))
+}
+
+/// Forwards high-risk PII warnings to the frontend
+struct AppLeakNotifier(tauri::AppHandle);
+
+impl memory_store::LeakNotifier for AppLeakNotifier {
+  fn high_risk_detected(&self, leak: &memory_store::HighRiskLeak) -> anyhow::Result<()> {
+    use tauri::Emitter;
+    self.0.emit(memory_store::HIGH_RISK_EVENT, leak.clone())?;
+    Ok(())
+  }
+}
+
+/// Forwards AWS upload progress to the frontend
+struct AppUploadNotifier(tauri::AppHandle);
+
+impl aws_uploader::UploadNotifier for AppUploadNotifier {
+  fn upload_event(&self, event: &aws_uploader::UploadEvent) -> anyhow::Result<()> {
+    use tauri::Emitter;
+    self.0.emit(event.name(), event.clone())?;
+    Ok(())
   }
 }
 
+/// Memory store rooted at the configured memory dir (app data dir by default)
+fn memory_store_for(app: &tauri::AppHandle) -> Result<memory_store::MemoryStore, String> {
+  let config = memory_store::MemoryConfig::load();
+  let app_data_dir = app.path().app_data_dir()
+    .map_err(|e| format!("Failed to resolve app data dir: {}", e))?;
+  let base_dir = memory_store::resolve_base_dir(&config, &app_data_dir)
+    .map_err(|e| format!("Failed to prepare memory dir: {}", e))?;
+  Ok(memory_store::MemoryStore::new(&base_dir, config))
+}
+
 #[tauri::command]
-fn write_conversation_to_file(conversation_data: String, filename: String) -> Result<(), String> {
-  use std::fs;
-  use std::path::Path;
-  
-  let clean_conversation_data = pii_scrubber::scrub_conversation_json(conversation_data)
-    .map_err(|e| format!("Failed to scrub PII: {}", e))?;
-  
-  let project_dir = Path::new("C:\\Users\\parad\\Downloads\\pluely-master2");
+fn write_conversation_to_file(app: tauri::AppHandle, conversation_data: String, filename: String, overwrite: Option<bool>, scrub_options: Option<pii_scrubber::ScrubOptions>) -> Result<String, String> {
+  let store = memory_store_for(&app)?
+    .with_notifier(Box::new(AppLeakNotifier(app)))
+    .with_scrub_options(scrub_options.unwrap_or_default());
   
-  let memory_path = project_dir.join("memory");
+  // Existing files are kept unless the caller explicitly asks to overwrite
+  let mode = if overwrite.unwrap_or(false) {
+    memory_store::WriteMode::Overwrite
+  } else {
+    memory_store::WriteMode::Unique
+  };
+  let file_path = panic_guard::catch_panic("write_conversation_to_file", || {
+    store.write_conversation(conversation_data, &filename, mode)
+      .map_err(|e| e.to_string())
+  })?;
   
-  if !memory_path.exists() {
-    fs::create_dir(&memory_path)
-      .map_err(|e| format!("Failed to create memory directory: {}", e))?;
-  }
-  
-  let file_path = memory_path.join(filename);
+  println!("Clean conversation written to: {:?}", file_path);
+  Ok(file_path.to_string_lossy().to_string())
+}
+
+/// Scrub conversation JSON the way `write_conversation_to_file` would, without writing anything
+#[tauri::command]
+fn preview_scrubbed(app: tauri::AppHandle, conversation_data: String, scrub_options: Option<pii_scrubber::ScrubOptions>) -> Result<String, String> {
+  memory_store_for(&app)?
+    .with_scrub_options(scrub_options.unwrap_or_default())
+    .preview(conversation_data)
+    .map_err(|e| e.to_string())
+}
+
+/// Scrubbed conversations in the memory directory, newest first
+#[tauri::command]
+fn list_memory_files(app: tauri::AppHandle) -> Result<Vec<memory_store::MemoryFileInfo>, String> {
+  memory_store_for(&app)?
+    .list_memory_files()
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn append_conversation_turn(app: tauri::AppHandle, session_id: String, turn_data: String) -> Result<String, String> {
+  let store = memory_store_for(&app)?;
   
-  fs::write(&file_path, clean_conversation_data)
-    .map_err(|e| format!("Failed to write file: {}", e))?;
+  store.append_conversation_turn(&session_id, turn_data)
+    .map(|p| p.to_string_lossy().to_string())
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn close_conversation_stream(app: tauri::AppHandle, session_id: String) -> Result<Option<String>, String> {
+  let store = memory_store_for(&app)?;
   
-  println!("Clean conversation written to: {:?}", file_path);
-  Ok(())
+  store.close_conversation_stream(&session_id)
+    .map(|p| p.map(|p| p.to_string_lossy().to_string()))
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn scrub_self_test() -> Vec<pii_scrubber::CategorySelfTest> {
+  pii_scrubber::scrub_self_test()
+}
+
+#[tauri::command]
+fn test_custom_pattern(pattern: String, replacement: String, sample: String) -> Result<pii_scrubber::PatternTestResult, String> {
+  pii_scrubber::test_custom_pattern(&pattern, &replacement, &sample)
+}
+
+/// Scrub newline-delimited JSON (one conversation record per line)
+#[tauri::command]
+fn scrub_ndjson(conversation_data: String) -> Result<String, String> {
+  pii_scrubber::scrub_ndjson(conversation_data)
+}
+
+/// Scrub conversation JSON with user-defined (regex, replacement) rules on top of the built-ins
+#[tauri::command]
+fn scrub_with_custom_patterns(conversation_data: String, custom_patterns: Vec<(String, String)>) -> Result<String, String> {
+  let options = pii_scrubber::ScrubOptions { custom_patterns, ..Default::default() };
+  pii_scrubber::scrub_conversation_json_with_options(conversation_data, &options)
+}
+
+/// Scrub conversation JSON with stable pseudonym tokens, recording them in an encrypted map
+#[tauri::command]
+fn scrub_conversation_pseudonymous(conversation_data: String, mapping_path: String) -> Result<String, String> {
+  pii_scrubber::scrub_conversation_json_pseudonymous(conversation_data, std::path::Path::new(&mapping_path))
+}
+
+/// Look up what a pseudonym token stands for in the encrypted map at `mapping_path`
+#[tauri::command]
+fn resolve_pseudonym(mapping_path: String, token: String) -> Result<Option<pseudonym::PseudonymEntry>, String> {
+  let key = pseudonym::pseudonym_key()
+    .map_err(|e| format!("Failed to load pseudonym key: {}", e))?;
+  let map = pseudonym::PseudonymMap::load(std::path::Path::new(&mapping_path), key)
+    .map_err(|e| format!("Failed to load pseudonym map: {}", e))?;
+  Ok(map.entries().get(&token).cloned())
 }
 
 #[tauri::command]
-fn trigger_aws_upload() -> Result<String, String> {
+fn profile_scrub(sample: String) -> Vec<pii_scrubber::RuleTiming> {
+  pii_scrubber::profile_scrub(&sample)
+}
+
+#[tauri::command]
+fn trigger_aws_upload(app: tauri::AppHandle) -> Result<aws_uploader::ScanSummary, String> {
   let uploader = aws_uploader::AwsUploader::new()
-    .map_err(|e| format!("Failed to create AWS uploader: {}", e))?;
+    .map_err(|e| format!("Failed to create AWS uploader: {}", e))?
+    .with_notifier(std::sync::Arc::new(AppUploadNotifier(app)));
   
-  match uploader.scan_and_upload() {
-    Ok(_) => Ok("AWS upload scan completed successfully".to_string()),
-    Err(e) => Err(format!("AWS upload scan failed: {}", e))
+  uploader.scan_and_upload()
+    .map_err(|e| format!("AWS upload scan failed: {}", e))
+}
+
+#[tauri::command]
+fn get_aws_config() -> Result<aws_uploader::ResolvedAwsConfig, String> {
+  aws_uploader::ResolvedAwsConfig::load()
+    .map_err(|e| format!("Failed to load AWS config: {}", e))
+}
+
+#[tauri::command]
+fn cancel_upload(path: String) -> Result<bool, String> {
+  Ok(aws_uploader::cancel_upload(&path))
+}
+
+#[tauri::command]
+fn list_quarantine() -> Result<Vec<aws_uploader::QuarantineEntry>, String> {
+  let cfg = aws_uploader::AwsConfig::load()
+    .map_err(|e| format!("Failed to load AWS config: {}", e))?;
+  let mut entries = Vec::new();
+  for dir in cfg.watch_dir.paths() {
+    entries.extend(aws_uploader::list_quarantine(dir)
+      .map_err(|e| format!("Failed to list quarantine: {}", e))?);
   }
+  Ok(entries)
+}
+
+/// Remove one quarantined file, or all of them when `filename` is omitted
+#[tauri::command]
+fn clear_quarantine(filename: Option<String>) -> Result<usize, String> {
+  let cfg = aws_uploader::AwsConfig::load()
+    .map_err(|e| format!("Failed to load AWS config: {}", e))?;
+  let dirs = match filename.as_deref() {
+    Some(name) => aws_uploader::quarantine_owner(&cfg.watch_dir, name)
+      .map(|dir| vec![dir])
+      .ok_or_else(|| format!("Failed to clear quarantine: not in quarantine: {}", name))?,
+    None => cfg.watch_dir.paths().map(|d| d.to_path_buf()).collect(),
+  };
+  let mut cleared = 0;
+  for dir in dirs {
+    cleared += aws_uploader::clear_quarantine(&dir, filename.as_deref())
+      .map_err(|e| format!("Failed to clear quarantine: {}", e))?;
+  }
+  Ok(cleared)
+}
+
+/// Forget the recorded upload of the file at `path`, or of all files when `path` is
+/// omitted, so the next scan uploads them again
+#[tauri::command]
+fn forget_uploads(path: Option<String>) -> Result<usize, String> {
+  let uploader = aws_uploader::AwsUploader::new()
+    .map_err(|e| format!("Failed to create AWS uploader: {}", e))?;
+  uploader
+    .forget_uploads(path.as_deref().map(std::path::Path::new))
+    .map_err(|e| format!("Failed to save upload state: {}", e))
+}
+
+#[tauri::command]
+fn resubmit_quarantine(filename: String) -> Result<String, String> {
+  let cfg = aws_uploader::AwsConfig::load()
+    .map_err(|e| format!("Failed to load AWS config: {}", e))?;
+  aws_uploader::resubmit_quarantine(&cfg, &filename)
+    .map(|p| p.to_string_lossy().to_string())
+    .map_err(|e| format!("Failed to resubmit {}: {}", filename, e))
+}
+
+#[tauri::command]
+fn set_watcher_enabled(enabled: bool) -> Result<bool, String> {
+  let runtime = aws_uploader::uploader_runtime()
+    .get()
+    .ok_or_else(|| "AWS uploader is not running".to_string())?;
+  runtime
+    .set_watcher_enabled(enabled)
+    .map_err(|e| format!("Failed to update file watcher: {:#}", e))?;
+  Ok(runtime.is_watcher_enabled())
+}
+
+/// Re-read config.toml and hand it to the running uploader
+#[tauri::command]
+fn reload_aws_config() -> Result<(), String> {
+  let runtime = aws_uploader::uploader_runtime()
+    .get()
+    .ok_or_else(|| "AWS uploader is not running".to_string())?;
+  let cfg = aws_uploader::AwsConfig::load()
+    .map_err(|e| format!("Failed to load AWS config: {}", e))?;
+  runtime
+    .reload(cfg)
+    .map_err(|e| format!("Failed to apply AWS config: {:#}", e))
+}
+
+#[tauri::command]
+fn is_watcher_enabled() -> bool {
+  aws_uploader::uploader_runtime()
+    .get()
+    .map(|runtime| runtime.is_watcher_enabled())
+    .unwrap_or(false)
 }
 
 // File storage commands
@@ -81,8 +288,31 @@ async fn upload_file(file_data: Vec<u8>, filename: String) -> Result<file_storag
     let storage = file_storage::FileStorage::new()
         .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
     
-    storage.upload_file(file_data, filename)
-        .map_err(|e| format!("Failed to upload file: {}", e))
+    panic_guard::catch_panic("upload_file", || {
+        storage.upload_file(file_data, filename)
+            .map_err(|e| format!("Failed to upload file: {}", e))
+    })
+}
+
+#[tauri::command]
+async fn upload_file_from_path(path: String) -> Result<file_storage::FileInfo, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    panic_guard::catch_panic("upload_file_from_path", || {
+        storage.upload_file_from_path(std::path::Path::new(&path))
+            .map_err(|e| format!("Failed to upload file: {}", e))
+    })
+}
+
+#[tauri::command]
+async fn export_file_to_path(file_id: String, dest_path: String) -> Result<String, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    storage.export_file_to_path(&file_id, std::path::Path::new(&dest_path))
+        .map(|p| p.to_string_lossy().to_string())
+        .map_err(|e| format!("Failed to export file: {}", e))
 }
 
 #[tauri::command]
@@ -94,6 +324,16 @@ async fn list_uploaded_files() -> Result<Vec<file_storage::FileInfo>, String> {
         .map_err(|e| format!("Failed to list files: {}", e))
 }
 
+/// A page of uploaded files; `content` is omitted unless `include_content` is set
+#[tauri::command]
+async fn list_uploaded_files_page(offset: usize, limit: usize, include_content: bool) -> Result<file_storage::FilePage, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    storage.list_files_page(offset, limit, include_content)
+        .map_err(|e| format!("Failed to list files: {}", e))
+}
+
 #[tauri::command]
 async fn delete_uploaded_file(file_id: String) -> Result<(), String> {
     let storage = file_storage::FileStorage::new()
@@ -112,6 +352,20 @@ async fn toggle_file_context(file_id: String) -> Result<file_storage::FileInfo,
         .map_err(|e| format!("Failed to toggle file context: {}", e))
 }
 
+/// Search uploaded files by name and content; `context_enabled_only` skips disabled files
+#[tauri::command]
+async fn search_uploaded_files(query: String, context_enabled_only: Option<bool>) -> Result<Vec<file_storage::FileInfo>, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    let mut files = storage.search(&query)
+        .map_err(|e| format!("Failed to search files: {}", e))?;
+    if context_enabled_only.unwrap_or(false) {
+        files.retain(|f| f.is_context_enabled);
+    }
+    Ok(files)
+}
+
 #[tauri::command]
 async fn get_file_context() -> Result<Vec<String>, String> {
     let storage = file_storage::FileStorage::new()
@@ -121,6 +375,77 @@ async fn get_file_context() -> Result<Vec<String>, String> {
         .map_err(|e| format!("Failed to get file context: {}", e))
 }
 
+#[tauri::command]
+async fn get_file_context_budgeted(max_chars: usize) -> Result<Vec<String>, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    storage.get_context_content_budgeted(max_chars)
+        .map_err(|e| format!("Failed to get file context: {}", e))
+}
+
+/// Enabled file contents trimmed to about `max_tokens`, see `get_context_content_token_budgeted`
+#[tauri::command]
+async fn get_file_context_token_budgeted(max_tokens: usize) -> Result<Vec<String>, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    storage.get_context_content_token_budgeted(max_tokens)
+        .map_err(|e| format!("Failed to get file context: {}", e))
+}
+
+#[tauri::command]
+async fn set_file_priority(file_id: String, priority: i32) -> Result<file_storage::FileInfo, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    storage.set_priority(&file_id, priority)
+        .map_err(|e| format!("Failed to set file priority: {}", e))
+}
+
+#[tauri::command]
+async fn reextract_file(file_id: String) -> Result<file_storage::FileInfo, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    
+    panic_guard::catch_panic("reextract_file", || {
+        storage.reextract(&file_id)
+            .map_err(|e| format!("Failed to re-extract file: {}", e))
+    })
+}
+
+#[tauri::command]
+async fn scrub_file_categories(file_id: String, categories: Vec<pii_scrubber::Category>, scrub_options: Option<pii_scrubber::ScrubOptions>) -> Result<file_storage::FileInfo, String> {
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    let options = scrub_options.unwrap_or_default();
+    options.validate()?;
+    
+    storage.scrub_categories(&file_id, &categories, &options)
+        .map_err(|e| format!("Failed to scrub file: {}", e))
+}
+
+#[tauri::command]
+async fn reprocess_all(app: tauri::AppHandle, options: Option<file_storage::ReprocessOptions>, scrub_options: Option<pii_scrubber::ScrubOptions>) -> Result<file_storage::ReprocessReport, String> {
+    use tauri::Emitter;
+    let storage = file_storage::FileStorage::new()
+        .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+    let scrub_options = scrub_options.unwrap_or_default();
+    scrub_options.validate()?;
+    
+    panic_guard::catch_panic("reprocess_all", || {
+        storage.reprocess_all(&options.unwrap_or_default(), &scrub_options, |progress| {
+            let _ = app.emit("uploads://reprocess-progress", progress.clone());
+        })
+        .map_err(|e| format!("Failed to reprocess files: {}", e))
+    })
+}
+
+#[tauri::command]
+fn get_supported_file_types() -> Vec<file_storage::FileTypeInfo> {
+    file_storage::supported_file_types()
+}
+
 #[tauri::command]
 async fn wipe_uploaded_files() -> Result<(), String> {
   let storage = file_storage::FileStorage::new()
@@ -129,8 +454,48 @@ async fn wipe_uploaded_files() -> Result<(), String> {
     .map_err(|e| format!("Failed to wipe uploaded files: {}", e))
 }
 
+#[tauri::command]
+async fn gc_orphan_uploads() -> Result<usize, String> {
+  let storage = file_storage::FileStorage::new()
+    .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+  storage.gc_orphans()
+    .map_err(|e| format!("Failed to remove orphaned uploads: {}", e))
+}
+
+#[tauri::command]
+async fn snapshot_index(include_files: Option<bool>) -> Result<String, String> {
+  let storage = file_storage::FileStorage::new()
+    .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+  storage.snapshot_index(include_files.unwrap_or(false))
+    .map_err(|e| format!("Failed to snapshot file index: {}", e))
+}
+
+#[tauri::command]
+async fn restore_index(snapshot_id: String) -> Result<(), String> {
+  let storage = file_storage::FileStorage::new()
+    .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+  storage.restore_index(&snapshot_id)
+    .map_err(|e| format!("Failed to restore file index: {}", e))
+}
+
+#[tauri::command]
+async fn list_index_snapshots() -> Result<Vec<file_storage::IndexSnapshot>, String> {
+  let storage = file_storage::FileStorage::new()
+    .map_err(|e| format!("Failed to initialize file storage: {}", e))?;
+  storage.list_index_snapshots()
+    .map_err(|e| format!("Failed to list snapshots: {}", e))
+}
+
+/// Whether the Node sidecar is up and listening on the agent port
+#[tauri::command]
+async fn sidecar_health() -> Result<bool, String> {
+  Ok(sidecar::agent_reachable(sidecar::AGENT_PORT, sidecar::HEALTH_TIMEOUT))
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    panic_guard::install_hook();
+
     let builder = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
@@ -138,32 +503,94 @@ pub fn run() {
             greet, 
             get_app_version,
             set_window_height,
+            toggle_window_visibility,
             write_conversation_to_file,
+            preview_scrubbed,
+            list_memory_files,
+            scrub_ndjson,
+            append_conversation_turn,
+            close_conversation_stream,
+            scrub_self_test,
+            test_custom_pattern,
+            profile_scrub,
+            scrub_with_custom_patterns,
+            scrub_conversation_pseudonymous,
+            resolve_pseudonym,
             trigger_aws_upload,
+            cancel_upload,
+            list_quarantine,
+            clear_quarantine,
+            forget_uploads,
+            resubmit_quarantine,
+            set_watcher_enabled,
+            is_watcher_enabled,
+            reload_aws_config,
+            get_aws_config,
             google_oauth::connect_google_suite,
             google_oauth::disconnect_google_suite,
             google_oauth::is_google_connected,
+            google_oauth::list_google_accounts,
+            google_oauth::google_token_status,
+            google_oauth::check_required_scopes,
+            google_oauth::verify_google_access,
+            google_oauth::refresh_google_tokens,
             upload_file,
+            upload_file_from_path,
+            export_file_to_path,
             list_uploaded_files,
+            list_uploaded_files_page,
             delete_uploaded_file,
             toggle_file_context,
+            search_uploaded_files,
             get_file_context,
+            get_file_context_budgeted,
+            get_file_context_token_budgeted,
+            set_file_priority,
             wipe_uploaded_files,
+            gc_orphan_uploads,
+            snapshot_index,
+            restore_index,
+            list_index_snapshots,
+            get_supported_file_types,
+            reextract_file,
+            reprocess_all,
+            scrub_file_categories,
+            sidecar_health,
         ])
         .setup(|app| {
+            // Mirror panics to the app log dir
+            if let Ok(dir) = app.path().app_log_dir() {
+                panic_guard::set_log_dir(dir);
+            }
+
             // Make a shared place to store the sidecar child
-            app.manage(Mutex::new(None::<Child>));
+            let sidecar_slot: SidecarSlot = Arc::new(Mutex::new(None));
+            app.manage(sidecar_slot.clone());
 
             // Setup main window positioning
             window::setup_main_window(app).expect("Failed to setup main window");
+            if let Err(e) = window::register_toggle_shortcut(app) {
+                eprintln!("Failed to register the show/hide shortcut: {}", e);
+            }
+
+            // Conversations are written under the memory dir; the uploader always watches it
+            match memory_store_for(app.handle()) {
+              Ok(store) => aws_uploader::set_memory_dir(store.memory_dir().to_path_buf()),
+              Err(e) => eprintln!("Failed to resolve memory dir for uploads: {}", e),
+            }
 
             // Start AWS background uploader (non-blocking)
-            if let Err(e) = aws_uploader::AwsUploader::start_background_uploader() {
+            if let Err(e) = aws_uploader::AwsUploader::start_background_uploader(
+                std::sync::Arc::new(AppUploadNotifier(app.handle().clone())),
+            ) {
                 eprintln!("Failed to start AWS uploader: {}", e);
             } else {
                 println!("AWS background uploader started successfully");
             }
 
+            // Keep Google tokens fresh for the MCP servers
+            google_oauth::start_token_refresher(app.handle().clone());
+
             // Absolute path to sidecar script based on src-tauri dir
             let script_path = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"))
                 .join("../sidecar/dist/server.js");
@@ -175,101 +602,79 @@ pub fn run() {
             );
 
             // If port already in use, skip building/spawning the sidecar
-            let port_in_use = std::net::TcpStream::connect(("127.0.0.1", 8765)).is_ok();
+            let port_in_use = std::net::TcpStream::connect(("127.0.0.1", sidecar::AGENT_PORT)).is_ok();
             if port_in_use {
-              println!("[sidecar] Port 8765 already in use; skipping sidecar spawn.");
+              println!("[sidecar] Port {} already in use; skipping sidecar spawn.", sidecar::AGENT_PORT);
               return Ok(());
             }
 
-            // Always build sidecar to pick up latest changes during dev
-            println!("[sidecar] Running npm run build...");
-            let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
-
-            // Ensure dependencies are installed (idempotent)
-            let install_status = StdCommand::new(npm_cmd)
-              .current_dir(&sidecar_cwd)
-              .args(["ci", "--silent"]) // prefer clean, reproducible install
-              .status()
-              .map_err(|e| format!("Failed to run sidecar install: {}", e))?;
-            if !install_status.success() {
-              eprintln!("[sidecar] npm ci failed; falling back to npm install...");
-              let fallback_install = StdCommand::new(npm_cmd)
+            // Rebuild only when sources changed (or ARKANGEL_FORCE_SIDECAR_BUILD=1)
+            if sidecar::build_needed(&sidecar_cwd, &script_path) {
+              println!("[sidecar] Running npm run build...");
+              let npm_cmd = if cfg!(target_os = "windows") { "npm.cmd" } else { "npm" };
+
+              // Ensure dependencies are installed (idempotent)
+              let install_status = StdCommand::new(npm_cmd)
                 .current_dir(&sidecar_cwd)
-                .args(["install", "--silent"]) // fallback for environments without lockfile compatibility
+                .args(["ci", "--silent"]) // prefer clean, reproducible install
                 .status()
-                .map_err(|e| format!("Failed to run sidecar install fallback: {}", e))?;
-              if !fallback_install.success() {
-                return Err("Sidecar dependency installation failed.".into());
+                .map_err(|e| format!("Failed to run sidecar install: {}", e))?;
+              if !install_status.success() {
+                eprintln!("[sidecar] npm ci failed; falling back to npm install...");
+                let fallback_install = StdCommand::new(npm_cmd)
+                  .current_dir(&sidecar_cwd)
+                  .args(["install", "--silent"]) // fallback for environments without lockfile compatibility
+                  .status()
+                  .map_err(|e| format!("Failed to run sidecar install fallback: {}", e))?;
+                if !fallback_install.success() {
+                  return Err("Sidecar dependency installation failed.".into());
+                }
               }
-            }
 
-            // Build the sidecar TypeScript -> JavaScript
-            let build_status = StdCommand::new(npm_cmd)
-              .current_dir(&sidecar_cwd)
-              .args(["run", "build", "--silent"])
-              .status()
-              .map_err(|e| format!("Failed to run sidecar build: {}", e))?;
-            if !build_status.success() {
-              return Err("Sidecar build failed. Try running `npm --prefix sidecar ci && npm --prefix sidecar run build`.".into());
+              // Build the sidecar TypeScript -> JavaScript
+              let build_status = StdCommand::new(npm_cmd)
+                .current_dir(&sidecar_cwd)
+                .args(["run", "build", "--silent"])
+                .status()
+                .map_err(|e| format!("Failed to run sidecar build: {}", e))?;
+              if !build_status.success() {
+                return Err("Sidecar build failed. Try running `npm --prefix sidecar ci && npm --prefix sidecar run build`.".into());
+              }
+              println!("[sidecar] Build completed.");
+            } else {
+              println!("[sidecar] dist is up to date; skipping npm install/build.");
             }
-            println!("[sidecar] Build completed.");
 
             // Spawn sidecar
-            println!("[sidecar] Spawning Node...");
-            let mut child = StdCommand::new("node")
-              .current_dir(&sidecar_cwd)
-              .arg(&script_path)
-              .env("AGENT_PORT", "8765")
-              .stdout(Stdio::piped())
-              .stderr(Stdio::piped())
-              .spawn()
+            let child = sidecar::spawn_sidecar(&sidecar_cwd, &script_path)
               .map_err(|e| format!("Failed to spawn sidecar: {}", e))?;
+            *sidecar_slot.lock().map_err(|_| "Failed to lock sidecar state mutex")? = Some(child);
 
-            // Pipe stdout
-            if let Some(stdout) = child.stdout.take() {
-              thread::spawn(move || {
-                let reader = BufReader::new(stdout);
-                for line in reader.lines() {
-                  if let Ok(l) = line {
-                    println!("[sidecar][stdout] {}", l);
-                  }
-                }
-              });
-            }
-            // Pipe stderr
-            if let Some(stderr) = child.stderr.take() {
-              thread::spawn(move || {
-                let reader = BufReader::new(stderr);
-                for line in reader.lines() {
-                  if let Ok(l) = line {
-                    eprintln!("[sidecar][stderr] {}", l);
-                  }
-                }
+            // Relaunch the sidecar if it dies while the app is running
+            thread::spawn(move || {
+              sidecar::supervise(&sidecar_slot, &sidecar::RestartPolicy::default(), || {
+                sidecar::spawn_sidecar(&sidecar_cwd, &script_path)
               });
-            }
-
-            // Store handle for later cleanup (ensure guard drops before state)
-            {
-              let state_mutex = app.state::<Mutex<Option<Child>>>();
-              let mut guard = match state_mutex.lock() {
-                Ok(g) => g,
-                Err(_) => return Err("Failed to lock sidecar state mutex".into()),
-              };
-              *guard = Some(child);
-            }
+            });
 
             Ok(())
         })
         .on_window_event(|w, e| {
+          if let tauri::WindowEvent::Moved(_) | tauri::WindowEvent::Resized(_) = e {
+            window::geometry_changed(w);
+          }
           if let tauri::WindowEvent::CloseRequested { api, .. } = e {
             api.prevent_close();
-            // Attempt to kill sidecar gently
+            // Exiting below skips any save still waiting out its delay
+            if let Err(err) = window::save_geometry(w) {
+              eprintln!("Failed to save window position: {}", err);
+            }
+            // Let the sidecar shut down cleanly; emptying the slot also stops the supervisor
             let app_handle = w.app_handle();
-            if let Some(mutex) = app_handle.try_state::<Mutex<Option<Child>>>() {
-              if let Ok(mut guard) = mutex.lock() {
-                if let Some(mut child) = guard.take() {
-                  let _ = child.kill();
-                }
+            if let Some(mutex) = app_handle.try_state::<SidecarSlot>() {
+              let child = mutex.lock().ok().and_then(|mut guard| guard.take());
+              if let Some(child) = child {
+                sidecar::shutdown_child(child, sidecar::SHUTDOWN_TIMEOUT);
               }
             }
             std::process::exit(0);
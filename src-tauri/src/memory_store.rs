@@ -0,0 +1,559 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{self, OpenOptions};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use crate::{aws_uploader, pii_scrubber};
+
+/// Directory (sibling of `memory/`) holding unscrubbed copies. Never uploaded.
+pub const RAW_DIR_NAME: &str = "raw";
+
+/// Environment override for the directory holding `memory/` and `raw/`
+pub const BASE_DIR_ENV: &str = "ARKANGEL_MEMORY_DIR";
+
+/// Suffix of the segment currently being appended to; the uploader only picks up `*.json`
+const SEGMENT_SUFFIX: &str = ".json.part";
+
+// -------- config --------
+
+/// Settings from the optional `[memory]` table of config.toml
+#[derive(Deserialize, Debug, Clone)]
+pub struct MemoryConfig {
+    /// Also keep the unscrubbed conversation under `raw/` (internal QA only, off by default)
+    #[serde(default)]
+    pub keep_raw_copy: bool,
+    /// Rotate a streamed conversation once its active segment exceeds this many bytes
+    #[serde(default = "default_rotate_max_bytes")]
+    pub rotate_max_bytes: u64,
+    /// Rotate a streamed conversation once its active segment holds this many turns
+    #[serde(default = "default_rotate_max_turns")]
+    pub rotate_max_turns: usize,
+    /// Directory holding `memory/` and `raw/`; defaults to the app data dir
+    #[serde(default)]
+    pub base_dir: Option<PathBuf>,
+}
+
+fn default_rotate_max_bytes() -> u64 { 1024 * 1024 }
+fn default_rotate_max_turns() -> usize { 200 }
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            keep_raw_copy: false,
+            rotate_max_bytes: default_rotate_max_bytes(),
+            rotate_max_turns: default_rotate_max_turns(),
+            base_dir: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct ConfigFile {
+    #[serde(default)]
+    memory: MemoryConfig,
+}
+
+impl MemoryConfig {
+    /// Load the `[memory]` table, falling back to defaults if config.toml is missing or invalid
+    pub fn load() -> Self {
+        let text = match aws_uploader::read_config_file() {
+            Ok((text, _)) => text,
+            Err(_) => return Self::default(),
+        };
+        match toml::from_str::<ConfigFile>(&text) {
+            Ok(file) => file.memory,
+            Err(e) => {
+                eprintln!("[memory] Ignoring invalid [memory] config: {}", e);
+                Self::default()
+            }
+        }
+    }
+}
+
+/// Pick the directory conversations are written under: `ARKANGEL_MEMORY_DIR`, then
+/// `[memory] base_dir`, then `app_data_dir`. The directory is created if missing.
+pub fn resolve_base_dir(config: &MemoryConfig, app_data_dir: &Path) -> Result<PathBuf> {
+    let env_dir = std::env::var_os(BASE_DIR_ENV).filter(|v| !v.is_empty()).map(PathBuf::from);
+    let base = env_dir
+        .or_else(|| config.base_dir.clone())
+        .unwrap_or_else(|| app_data_dir.to_path_buf());
+    fs::create_dir_all(&base).with_context(|| format!("creating memory dir {}", base.display()))?;
+    Ok(base)
+}
+
+// -------- store --------
+
+/// What to do when the requested conversation filename already exists
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteMode {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and write under a generated `<stem>_<timestamp>_<hash>.json` name
+    Unique,
+}
+
+/// Event emitted when a conversation being saved contained high-risk PII
+pub const HIGH_RISK_EVENT: &str = "pii://high-risk-detected";
+
+/// Payload of `HIGH_RISK_EVENT`: the high-risk categories found and how often
+#[derive(Serialize, Clone, Debug)]
+pub struct HighRiskLeak {
+    pub filename: String,
+    pub counts: BTreeMap<pii_scrubber::Category, usize>,
+}
+
+/// Receives high-risk leak warnings; the app forwards them to the UI as Tauri events
+pub trait LeakNotifier: Send + Sync {
+    fn high_risk_detected(&self, leak: &HighRiskLeak) -> Result<()>;
+}
+
+/// A finished scrubbed conversation in the memory directory
+#[derive(Serialize, Clone, Debug)]
+pub struct MemoryFileInfo {
+    pub filename: String,
+    pub path: String,
+    pub size: u64,
+    pub modified: String,              // ISO 8601 timestamp
+}
+
+pub struct MemoryStore {
+    memory_dir: PathBuf,               // <base>/memory (watched by the AWS uploader)
+    raw_dir: PathBuf,                  // <base>/raw (local only)
+    config: MemoryConfig,
+    notifier: Option<Box<dyn LeakNotifier>>,
+    scrub_options: pii_scrubber::ScrubOptions,
+}
+
+impl MemoryStore {
+    pub fn new(base_dir: &Path, config: MemoryConfig) -> Self {
+        Self {
+            memory_dir: base_dir.join("memory"),
+            raw_dir: base_dir.join(RAW_DIR_NAME),
+            config,
+            notifier: None,
+            scrub_options: pii_scrubber::ScrubOptions::default(),
+        }
+    }
+
+    /// Where conversations are written
+    pub fn memory_dir(&self) -> &Path {
+        &self.memory_dir
+    }
+
+    /// Warn `notifier` before writing conversations that contained high-risk PII
+    pub fn with_notifier(mut self, notifier: Box<dyn LeakNotifier>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Redact with `options` instead of the default "BLOCKED" token
+    pub fn with_scrub_options(mut self, options: pii_scrubber::ScrubOptions) -> Self {
+        self.scrub_options = options;
+        self
+    }
+
+    /// The conversation as `write_conversation` would store it, without writing anything
+    pub fn preview(&self, conversation_data: String) -> Result<String> {
+        pii_scrubber::scrub_conversation_json_with_options(conversation_data, &self.scrub_options)
+            .map_err(|e| anyhow!("Failed to scrub PII: {}", e))
+    }
+
+    /// Scrub a conversation and write it to the memory directory, returning the path actually used.
+    /// When `keep_raw_copy` is enabled the original is also written to the raw directory.
+    pub fn write_conversation(&self, conversation_data: String, filename: &str, mode: WriteMode) -> Result<PathBuf> {
+        let raw_copy = if self.config.keep_raw_copy { Some(conversation_data.clone()) } else { None };
+
+        let (clean_conversation_data, report) = pii_scrubber::scrub_conversation_json_with_report(conversation_data, &self.scrub_options)
+            .map_err(|e| anyhow!("Failed to scrub PII: {}", e))?;
+
+        // Warn before anything is written; a failed notification never blocks the save
+        let high_risk = report.high_risk_counts();
+        if let (Some(notifier), false) = (&self.notifier, high_risk.is_empty()) {
+            let leak = HighRiskLeak { filename: filename.to_string(), counts: high_risk };
+            if let Err(e) = notifier.high_risk_detected(&leak) {
+                eprintln!("[memory] Failed to report high-risk PII: {}", e);
+            }
+        }
+
+        fs::create_dir_all(&self.memory_dir).context("creating memory directory")?;
+        let file_path = match mode {
+            WriteMode::Overwrite => self.memory_dir.join(filename),
+            WriteMode::Unique => self.unique_path(filename, clean_conversation_data.as_bytes()),
+        };
+        fs::write(&file_path, clean_conversation_data).context("writing scrubbed conversation")?;
+
+        if let Some(raw) = raw_copy {
+            fs::create_dir_all(&self.raw_dir).context("creating raw directory")?;
+            let raw_path = self.raw_dir.join(file_path.file_name().unwrap_or_default());
+            fs::write(&raw_path, raw).context("writing raw conversation")?;
+            println!("[memory] Raw conversation retained at: {:?}", raw_path);
+        }
+
+        Ok(file_path)
+    }
+
+    /// `filename` if it is free, otherwise `<stem>_<timestamp>_<hash>.<ext>` derived from the content
+    fn unique_path(&self, filename: &str, content: &[u8]) -> PathBuf {
+        let requested = self.memory_dir.join(filename);
+        if !requested.exists() {
+            return requested;
+        }
+        let path = Path::new(filename);
+        let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("conversation");
+        let ext = path.extension().and_then(|e| e.to_str()).unwrap_or("json");
+        let hash = format!("{:x}", Sha256::digest(content));
+        let base = format!("{}_{}_{}", stem, Utc::now().format("%Y-%m-%dT%H-%M-%S-%3fZ"), &hash[..8]);
+
+        let mut candidate = self.memory_dir.join(format!("{}.{}", base, ext));
+        let mut n = 1;
+        while candidate.exists() {
+            candidate = self.memory_dir.join(format!("{}-{}.{}", base, n, ext));
+            n += 1;
+        }
+        candidate
+    }
+
+    /// Scrub a single conversation turn and append it to the session's active segment,
+    /// rotating to a new timestamped file when the size/turn thresholds would be exceeded.
+    /// Returns the path of the segment the turn was written to.
+    pub fn append_conversation_turn(&self, session_id: &str, turn_data: String) -> Result<PathBuf> {
+        validate_session_id(session_id)?;
+
+        let mut turn: Value = serde_json::from_str(&turn_data).context("parsing conversation turn")?;
+        pii_scrubber::scrub_conversation_value(&mut turn, &self.scrub_options)
+            .map_err(|e| anyhow!("Failed to scrub PII: {}", e))?;
+        let line = serde_json::to_string(&turn)?;
+
+        fs::create_dir_all(&self.memory_dir).context("creating memory directory")?;
+
+        let mut counts = segment_turns().lock().unwrap_or_else(|e| e.into_inner());
+        let segment = match self.active_segment(session_id)? {
+            Some(active) => {
+                let size = fs::metadata(&active)?.len();
+                let turns = match counts.get(&active) {
+                    Some(&turns) => turns,
+                    // First append to this segment since launch
+                    None => fs::read(&active)?.iter().filter(|&&b| b == b'\n').count(),
+                };
+                if size + line.len() as u64 + 1 > self.config.rotate_max_bytes
+                    || turns >= self.config.rotate_max_turns
+                {
+                    counts.remove(&active);
+                    let finished = self.finalize_segment(&active)?;
+                    println!("[memory] Rotated conversation segment: {:?}", finished);
+                    self.new_segment_path(session_id)
+                } else {
+                    active
+                }
+            }
+            None => self.new_segment_path(session_id),
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&segment)
+            .context("opening conversation segment")?;
+        writeln!(file, "{}", line).context("appending conversation turn")?;
+        *counts.entry(segment.clone()).or_insert(0) += 1;
+
+        Ok(segment)
+    }
+
+    /// Scrubbed conversation files (`*.json`), most recently modified first. Open stream
+    /// segments are left out until they are finalized.
+    pub fn list_memory_files(&self) -> Result<Vec<MemoryFileInfo>> {
+        if !self.memory_dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.memory_dir).context("reading memory directory")? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let metadata = fs::metadata(&path)?;
+            if metadata.is_file() {
+                files.push((metadata.modified()?, metadata.len(), path));
+            }
+        }
+        files.sort_by_key(|(modified, ..)| std::cmp::Reverse(*modified));
+        Ok(files
+            .into_iter()
+            .map(|(modified, size, path)| MemoryFileInfo {
+                filename: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                path: path.to_string_lossy().to_string(),
+                size,
+                modified: chrono::DateTime::<Utc>::from(modified).to_rfc3339(),
+            })
+            .collect())
+    }
+
+    /// Finalize the session's active segment (if any) so it becomes uploadable
+    pub fn close_conversation_stream(&self, session_id: &str) -> Result<Option<PathBuf>> {
+        validate_session_id(session_id)?;
+        match self.active_segment(session_id)? {
+            Some(active) => {
+                segment_turns().lock().unwrap_or_else(|e| e.into_inner()).remove(&active);
+                Ok(Some(self.finalize_segment(&active)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn active_segment(&self, session_id: &str) -> Result<Option<PathBuf>> {
+        if !self.memory_dir.exists() {
+            return Ok(None);
+        }
+        for entry in fs::read_dir(&self.memory_dir)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if segment_session(name) == Some(session_id) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Timestamped segment path, e.g. `session_2025-08-25T02-41-40-972Z.json.part`
+    fn new_segment_path(&self, session_id: &str) -> PathBuf {
+        let stem = format!("{}_{}", session_id, Utc::now().format("%Y-%m-%dT%H-%M-%S-%3fZ"));
+        let mut candidate = stem.clone();
+        let mut n = 1;
+        // Rotations within the same millisecond must not reuse a finished file's name
+        while self.memory_dir.join(format!("{}.json", candidate)).exists()
+            || self.memory_dir.join(format!("{}{}", candidate, SEGMENT_SUFFIX)).exists()
+        {
+            candidate = format!("{}-{}", stem, n);
+            n += 1;
+        }
+        self.memory_dir.join(format!("{}{}", candidate, SEGMENT_SUFFIX))
+    }
+
+    /// Convert an NDJSON segment into a JSON array file via tmp+rename
+    fn finalize_segment(&self, segment: &Path) -> Result<PathBuf> {
+        let content = fs::read_to_string(segment)?;
+        let turns = content
+            .lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(serde_json::from_str::<Value>)
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .context("parsing conversation segment")?;
+
+        let name = segment.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+        let final_path = segment.with_file_name(name.trim_end_matches(".part"));
+        let tmp_path = segment.with_file_name(format!("{}.tmp", name.trim_end_matches(SEGMENT_SUFFIX)));
+        fs::write(&tmp_path, serde_json::to_string_pretty(&Value::Array(turns))?)?;
+        fs::rename(&tmp_path, &final_path)?;
+        fs::remove_file(segment)?;
+        Ok(final_path)
+    }
+}
+
+/// Session a segment file belongs to. Names are `<session>_<timestamp>.json.part`, and the
+/// timestamp has no `_`, so the session is everything before the last one.
+fn segment_session(name: &str) -> Option<&str> {
+    let (session, timestamp) = name.strip_suffix(SEGMENT_SUFFIX)?.rsplit_once('_')?;
+    timestamp.starts_with(|c: char| c.is_ascii_digit()).then_some(session)
+}
+
+/// Turns written to each active segment by this process, so appends don't re-read the file
+fn segment_turns() -> &'static Mutex<HashMap<PathBuf, usize>> {
+    static TURNS: OnceLock<Mutex<HashMap<PathBuf, usize>>> = OnceLock::new();
+    TURNS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+fn validate_session_id(session_id: &str) -> Result<()> {
+    let valid = !session_id.is_empty()
+        && session_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        return Err(anyhow!("Invalid session id: {:?}", session_id));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_base() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arkangel-memory-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const CONVERSATION: &str = r#"{"messages":[{"content":"Email me at john@example.com"}]}"#;
+
+    #[test]
+    fn test_raw_copy_written_when_enabled() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig { keep_raw_copy: true, ..MemoryConfig::default() });
+        store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Overwrite).unwrap();
+
+        let scrubbed = fs::read_to_string(base.join("memory").join("conv.json")).unwrap();
+        let raw = fs::read_to_string(base.join(RAW_DIR_NAME).join("conv.json")).unwrap();
+        assert!(!scrubbed.contains("john@example.com"));
+        assert_eq!(raw, CONVERSATION);
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_preview_matches_persisted_conversation() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig::default());
+        let path = store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Overwrite).unwrap();
+
+        let preview = store.preview(CONVERSATION.to_string()).unwrap();
+        assert_eq!(preview, fs::read_to_string(path).unwrap());
+        assert!(!preview.contains("john@example.com"));
+
+        // The caller's options apply to the preview just as to the saved file
+        let options = pii_scrubber::ScrubOptions { replacement: "[REDACTED]".to_string(), ..Default::default() };
+        let store = MemoryStore::new(&base, MemoryConfig::default()).with_scrub_options(options);
+        let path = store.write_conversation(CONVERSATION.to_string(), "custom.json", WriteMode::Overwrite).unwrap();
+        let preview = store.preview(CONVERSATION.to_string()).unwrap();
+        assert_eq!(preview, fs::read_to_string(path).unwrap());
+        assert!(preview.contains("[REDACTED]") && !preview.contains("BLOCKED"), "{}", preview);
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_memory_files_listed_newest_first() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig::default());
+        assert!(store.list_memory_files().unwrap().is_empty());
+
+        let older = store.write_conversation(CONVERSATION.to_string(), "older.json", WriteMode::Overwrite).unwrap();
+        store.write_conversation(CONVERSATION.to_string(), "newer.json", WriteMode::Overwrite).unwrap();
+        store.append_conversation_turn("session", r#"{"content":"hi"}"#.to_string()).unwrap();
+        let an_hour_ago = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File::options().write(true).open(&older).unwrap().set_modified(an_hour_ago).unwrap();
+
+        let files = store.list_memory_files().unwrap();
+        let names: Vec<&str> = files.iter().map(|f| f.filename.as_str()).collect();
+        assert_eq!(names, ["newer.json", "older.json"]);
+        assert_eq!(files[1].size, fs::metadata(&older).unwrap().len());
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_only_scrubbed_copy_written_by_default() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig::default());
+        store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Overwrite).unwrap();
+
+        assert!(base.join("memory").join("conv.json").exists());
+        assert!(!base.join(RAW_DIR_NAME).exists());
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_stream_rotates_after_turn_threshold() {
+        let base = temp_base();
+        let config = MemoryConfig { rotate_max_turns: 2, ..MemoryConfig::default() };
+        let store = MemoryStore::new(&base, config);
+
+        let first = store.append_conversation_turn("session", r#"{"content":"one"}"#.to_string()).unwrap();
+        let second = store.append_conversation_turn("session", r#"{"content":"two"}"#.to_string()).unwrap();
+        assert_eq!(first, second);
+
+        let third = store.append_conversation_turn("session", r#"{"content":"john@example.com"}"#.to_string()).unwrap();
+        assert_ne!(first, third, "exceeding the threshold starts a new segment");
+        assert!(!first.exists());
+
+        // The rotated segment is a complete, uploadable JSON file
+        let rotated = first.with_file_name(first.file_name().unwrap().to_str().unwrap().trim_end_matches(".part"));
+        let turns: Vec<Value> = serde_json::from_str(&fs::read_to_string(&rotated).unwrap()).unwrap();
+        assert_eq!(turns.len(), 2);
+
+        let closed = store.close_conversation_stream("session").unwrap().unwrap();
+        let content = fs::read_to_string(&closed).unwrap();
+        assert!(!content.contains("john@example.com"));
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_stream_segments_matched_by_exact_session() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig::default());
+
+        let other = store.append_conversation_turn("a_1", r#"{"content":"one"}"#.to_string()).unwrap();
+        let ours = store.append_conversation_turn("a", r#"{"content":"two"}"#.to_string()).unwrap();
+        assert_ne!(other, ours, "session a must not append to a_1's segment");
+        assert_eq!(segment_session(other.file_name().unwrap().to_str().unwrap()), Some("a_1"));
+
+        let closed = store.close_conversation_stream("a").unwrap().unwrap();
+        assert!(fs::read_to_string(&closed).unwrap().contains("two"));
+        assert!(other.exists(), "a_1's segment is still open");
+        assert_eq!(segment_session("a_1.json"), None);
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_unique_mode_never_overwrites() {
+        let base = temp_base();
+        let store = MemoryStore::new(&base, MemoryConfig::default());
+
+        let first = store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Unique).unwrap();
+        let second = store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Unique).unwrap();
+        assert_eq!(first, base.join("memory").join("conv.json"));
+        assert_ne!(first, second);
+        assert!(first.exists() && second.exists());
+
+        let replaced = store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Overwrite).unwrap();
+        assert_eq!(replaced, first);
+        fs::remove_dir_all(&base).ok();
+    }
+
+    struct RecordingNotifier(std::sync::Arc<std::sync::Mutex<Vec<HighRiskLeak>>>);
+
+    impl LeakNotifier for RecordingNotifier {
+        fn high_risk_detected(&self, leak: &HighRiskLeak) -> Result<()> {
+            self.0.lock().unwrap().push(leak.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_card_number_triggers_high_risk_event() {
+        let base = temp_base();
+        let events = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let store = MemoryStore::new(&base, MemoryConfig::default())
+            .with_notifier(Box::new(RecordingNotifier(events.clone())));
+
+        store.write_conversation(r#"{"content":"card 4111 1111 1111 1111"}"#.to_string(), "card.json", WriteMode::Overwrite).unwrap();
+        store.write_conversation(r#"{"content":"nothing sensitive"}"#.to_string(), "plain.json", WriteMode::Overwrite).unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].filename, "card.json");
+        assert_eq!(events[0].counts.get(&pii_scrubber::Category::CreditCard), Some(&1));
+        assert!(base.join("memory").join("card.json").exists());
+        fs::remove_dir_all(&base).ok();
+    }
+
+    #[test]
+    fn test_configured_base_dir_receives_scrubbed_file() {
+        let tmp = temp_base();
+        let configured = tmp.join("configured");
+        let config = MemoryConfig { base_dir: Some(configured.clone()), ..MemoryConfig::default() };
+        let base = resolve_base_dir(&config, &tmp.join("app-data")).unwrap();
+        assert_eq!(base, configured);
+        assert!(configured.is_dir(), "base dir is created when missing");
+
+        let store = MemoryStore::new(&base, config);
+        let path = store.write_conversation(CONVERSATION.to_string(), "conv.json", WriteMode::Overwrite).unwrap();
+        assert_eq!(path, configured.join("memory").join("conv.json"));
+        assert!(!fs::read_to_string(&path).unwrap().contains("john@example.com"));
+
+        let fallback = resolve_base_dir(&MemoryConfig::default(), &tmp.join("app-data")).unwrap();
+        assert_eq!(fallback, tmp.join("app-data"));
+        fs::remove_dir_all(&tmp).ok();
+    }
+}
@@ -0,0 +1,106 @@
+use std::any::Any;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Panic log kept in the app log dir; rotated to `panics.log.1` past this size
+const PANIC_LOG_NAME: &str = "panics.log";
+const PANIC_LOG_MAX_BYTES: u64 = 1024 * 1024;
+
+fn log_dir() -> &'static OnceLock<PathBuf> {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    &DIR
+}
+
+/// Install a process-wide hook that records every panic with its thread and source
+/// location, so a panic in a command thread leaves a trace instead of vanishing.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let thread = std::thread::current();
+        let location = info
+            .location()
+            .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_else(|| "unknown location".to_string());
+        let line = format!(
+            "[panic] {} thread '{}' panicked at {}: {}",
+            chrono::Utc::now().to_rfc3339(),
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            panic_message(info.payload())
+        );
+        eprintln!("{}", line);
+        if let Some(dir) = log_dir().get() {
+            if let Err(e) = append_log(dir, &line) {
+                eprintln!("[panic] Failed to write panic log: {}", e);
+            }
+        }
+    }));
+}
+
+/// Start mirroring panics to `<dir>/panics.log`
+pub fn set_log_dir(dir: PathBuf) {
+    let _ = log_dir().set(dir);
+}
+
+fn append_log(dir: &Path, line: &str) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(PANIC_LOG_NAME);
+    if fs::metadata(&path).map(|m| m.len() > PANIC_LOG_MAX_BYTES).unwrap_or(false) {
+        fs::rename(&path, dir.join(format!("{}.1", PANIC_LOG_NAME)))?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+    writeln!(file, "{}", line)
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "non-string panic payload".to_string()
+    }
+}
+
+/// Run a command body, turning a panic into an error result for the frontend
+/// instead of unwinding into the Tauri runtime
+pub fn catch_panic<T, F>(command: &str, f: F) -> Result<T, String>
+where
+    F: FnOnce() -> Result<T, String>,
+{
+    panic::catch_unwind(AssertUnwindSafe(f))
+        .unwrap_or_else(|payload| Err(format!("{} failed unexpectedly: {}", command, panic_message(payload.as_ref()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panicking_command_returns_error() {
+        let result: Result<u32, String> = catch_panic("reextract_file", || {
+            let text = "héllo";
+            Ok(text[..2].len() as u32) // slices through 'é'
+        });
+        let err = result.unwrap_err();
+        assert!(err.starts_with("reextract_file failed unexpectedly:"), "{}", err);
+        assert!(err.contains("char boundary"), "{}", err);
+
+        assert_eq!(catch_panic("ok", || Ok::<_, String>(7)), Ok(7));
+        assert_eq!(catch_panic("err", || Err::<u32, _>("nope".to_string())), Err("nope".to_string()));
+    }
+
+    #[test]
+    fn test_panic_log_rotates() {
+        let dir = std::env::temp_dir().join(format!("arkangel-panics-{}", uuid::Uuid::new_v4()));
+        append_log(&dir, "first").unwrap();
+        fs::write(dir.join(PANIC_LOG_NAME), vec![b'x'; PANIC_LOG_MAX_BYTES as usize + 1]).unwrap();
+        append_log(&dir, "second").unwrap();
+
+        assert_eq!(fs::read_to_string(dir.join(PANIC_LOG_NAME)).unwrap(), "second\n");
+        assert!(dir.join("panics.log.1").exists());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
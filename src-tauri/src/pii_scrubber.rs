@@ -1,288 +1,1274 @@
-use regex::Regex;
+use base64::Engine;
+use rayon::prelude::*;
+use regex::{Captures, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::pseudonym::{self, PseudonymMap};
+
+/// Token written in place of redacted text unless `ScrubOptions` says otherwise
+pub const DEFAULT_REPLACEMENT: &str = "BLOCKED";
+
+/// How redacted text is written out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScrubOptions {
+    /// Replacement for every redaction, e.g. "[REDACTED]" or "***"
+    #[serde(default = "default_replacement")]
+    pub replacement: String,
+    /// Per-category overrides of `replacement`, e.g. `{"email": "[EMAIL]"}`
+    #[serde(default)]
+    pub category_replacements: BTreeMap<Category, String>,
+    /// Which groups of rules run at all
+    #[serde(default)]
+    pub categories: ScrubCategories,
+    /// Extra (regex, replacement) rules run after the built-ins, e.g. project codes
+    #[serde(default)]
+    pub custom_patterns: Vec<(String, String)>,
+    /// How card, phone and SSN matches are redacted
+    #[serde(default)]
+    pub mask_style: MaskStyle,
+    /// Decode base64 string values holding text or JSON, scrub them and re-encode.
+    /// Off by default so legitimate binary blobs are never touched.
+    #[serde(default)]
+    pub scrub_encoded: bool,
+    /// Only redact a bare dotted quad when a cue like "IP" or "address" precedes it, so
+    /// version numbers such as "4.10.22.3" survive. Private, loopback and link-local
+    /// addresses, and any with a port or CIDR suffix, are redacted regardless.
+    #[serde(default)]
+    pub ip_requires_context: bool,
+    /// Only redact an @handle when a platform cue ("twitter", "instagram", "follow", ...)
+    /// comes shortly before it on the same line
+    #[serde(default)]
+    pub handles_require_platform: bool,
+    /// Values that are never redacted, e.g. product names. A match is kept only when the
+    /// value a rule would redact equals a term (ignoring case); a term inside a longer
+    /// match, such as the domain of an email address, protects nothing.
+    #[serde(default)]
+    pub allowlist: Vec<String>,
+    /// When set, each redacted value becomes a stable token recorded in this map
+    #[serde(skip)]
+    pub(crate) pseudonyms: Option<SharedPseudonyms>,
+}
+
+/// A pseudonym map shared by every string of a scrub pass. Options compare equal when
+/// they write into the same map.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedPseudonyms(Arc<Mutex<PseudonymMap>>);
+
+impl PartialEq for SharedPseudonyms {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+/// Redaction style for numeric identifiers (cards, phones, SSNs). Other categories are
+/// always replaced in full.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskStyle {
+    /// Replace the whole match with the replacement token
+    #[default]
+    Full,
+    /// Star out every digit but the last four, keeping separators: "***-**-6789"
+    LastFour,
+}
+
+/// Switches for groups of scrub rules, all on by default. Credentials and document
+/// identifiers (auth headers, licenses, passports, VINs, plates, employee IDs) always run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrubCategories {
+    pub ssn: bool,
+    pub phone: bool,
+    pub email: bool,
+    pub names: bool,
+    pub dates: bool,
+    pub ages: bool,
+    pub addresses: bool,
+    pub financial: bool,
+    pub medical: bool,
+    pub digital: bool,
+}
+
+impl Default for ScrubCategories {
+    fn default() -> Self {
+        Self {
+            ssn: true,
+            phone: true,
+            email: true,
+            names: true,
+            dates: true,
+            ages: true,
+            addresses: true,
+            financial: true,
+            medical: true,
+            digital: true,
+        }
+    }
+}
+
+impl ScrubCategories {
+    /// Whether rules for `category` should run
+    pub fn allows(&self, category: Category) -> bool {
+        match category {
+            Category::Ssn | Category::NationalId => self.ssn,
+            Category::Phone => self.phone,
+            Category::Email => self.email,
+            Category::Name => self.names,
+            Category::Date | Category::DateOfBirth => self.dates,
+            Category::Age => self.ages,
+            Category::Address => self.addresses,
+            Category::CreditCard | Category::BankAccount | Category::TaxId => self.financial,
+            Category::MedicalRecord | Category::Insurance | Category::IcdCode => self.medical,
+            Category::IpAddress | Category::MacAddress | Category::Url | Category::DeviceId | Category::SocialHandle => self.digital,
+            Category::AuthHeader
+            | Category::DriversLicense
+            | Category::Passport
+            | Category::EmployeeId
+            | Category::Vin
+            | Category::LicensePlate => true,
+        }
+    }
+}
+
+fn default_replacement() -> String {
+    DEFAULT_REPLACEMENT.to_string()
+}
+
+impl Default for ScrubOptions {
+    fn default() -> Self {
+        Self {
+            replacement: default_replacement(),
+            category_replacements: BTreeMap::new(),
+            categories: ScrubCategories::default(),
+            custom_patterns: Vec::new(),
+            mask_style: MaskStyle::default(),
+            scrub_encoded: false,
+            ip_requires_context: false,
+            handles_require_platform: false,
+            allowlist: Vec::new(),
+            pseudonyms: None,
+        }
+    }
+}
+
+impl ScrubOptions {
+    fn token_for(&self, category: Category) -> &str {
+        self.category_replacements.get(&category).unwrap_or(&self.replacement)
+    }
+
+    /// Whether a built-in rule is switched off by one of the context-gating options
+    fn skips(&self, rule: &Rule) -> bool {
+        match rule.gate {
+            Some(Gate::IpContext) => self.ip_requires_context,
+            Some(Gate::HandlePlatform) => self.handles_require_platform,
+            None => false,
+        }
+    }
+
+    /// Whether `value` is exactly one of the allowlisted terms, ignoring case
+    fn is_allowlisted(&self, value: &str) -> bool {
+        if self.allowlist.is_empty() {
+            return false;
+        }
+        let value = value.to_lowercase();
+        self.allowlist.iter().map(|term| term.trim()).any(|term| !term.is_empty() && term.to_lowercase() == value)
+    }
+
+    /// Check that every custom pattern compiles
+    pub fn validate(&self) -> Result<(), String> {
+        for (pattern, _) in &self.custom_patterns {
+            custom_regex(pattern).map_err(|e| format!("Invalid custom pattern '{}': {}", pattern, e))?;
+        }
+        Ok(())
+    }
+}
 
 /// Scrub PII/PHI from conversation JSON and replace with "BLOCKED"
 pub fn scrub_conversation_json(json_content: String) -> Result<String, String> {
+    scrub_conversation_json_with_options(json_content, &ScrubOptions::default())
+}
+
+/// Scrub PII/PHI from conversation JSON using the caller's replacement tokens
+pub fn scrub_conversation_json_with_options(json_content: String, options: &ScrubOptions) -> Result<String, String> {
+    Ok(scrub_conversation_json_with_report(json_content, options)?.0)
+}
+
+/// Scrub newline-delimited JSON, one value per line, keeping each on its own line. Blank
+/// lines are dropped; a line that doesn't parse fails the whole input with its line number.
+pub fn scrub_ndjson(content: String) -> Result<String, String> {
+    let options = ScrubOptions::default();
+    let mut scrubbed = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let mut value: Value = serde_json::from_str(line)
+            .map_err(|e| format!("Failed to parse JSON on line {}: {}", i + 1, e))?;
+        scrub_conversation_value(&mut value, &options)?;
+        scrubbed.push(serde_json::to_string(&value).map_err(|e| format!("Failed to serialize JSON: {}", e))?);
+    }
+    Ok(scrubbed.join("\n"))
+}
+
+/// Scrub conversation JSON, replacing each redacted value with a stable token such as
+/// "EMAIL_1a2b3c4d". The token map is merged into the encrypted file at `mapping_path`,
+/// so the same value keeps its token across conversations.
+pub fn scrub_conversation_json_pseudonymous(json_content: String, mapping_path: &Path) -> Result<String, String> {
+    let key = pseudonym::pseudonym_key().map_err(|e| format!("Failed to load pseudonym key: {}", e))?;
+    scrub_pseudonymous_with_key(json_content, mapping_path, key)
+}
+
+fn scrub_pseudonymous_with_key(json_content: String, mapping_path: &Path, key: [u8; 32]) -> Result<String, String> {
+    let map = PseudonymMap::load(mapping_path, key).map_err(|e| format!("Failed to load pseudonym map: {}", e))?;
+    let map = Arc::new(Mutex::new(map));
+    let options = ScrubOptions { pseudonyms: Some(SharedPseudonyms(map.clone())), ..ScrubOptions::default() };
+    let scrubbed = scrub_conversation_json_with_options(json_content, &options)?;
+    map.lock()
+        .unwrap()
+        .save(mapping_path)
+        .map_err(|e| format!("Failed to save pseudonym map: {}", e))?;
+    Ok(scrubbed)
+}
+
+/// Scrub conversation JSON, also reporting what was redacted per category
+pub fn scrub_conversation_json_with_report(json_content: String, options: &ScrubOptions) -> Result<(String, ScrubReport), String> {
+    options.validate()?;
+    
     // Parse the JSON
     let mut conversation: Value = serde_json::from_str(&json_content)
         .map_err(|e| format!("Failed to parse JSON: {}", e))?;
     
-    // Scrub the conversation data
-    scrub_conversation_value(&mut conversation)?;
+    // Scrub the conversation data; large inputs are split across threads
+    let config = scrubber_config();
+    let report = if config.parallel && json_content.len() >= config.parallel_min_bytes {
+        scrub_value_parallel(&mut conversation, options)
+    } else {
+        let mut report = ScrubReport::default();
+        scrub_value_with_report(&mut conversation, options, &mut report);
+        report
+    };
     
     // Convert back to string
-    serde_json::to_string_pretty(&conversation)
-        .map_err(|e| format!("Failed to serialize JSON: {}", e))
+    let scrubbed = serde_json::to_string_pretty(&conversation)
+        .map_err(|e| format!("Failed to serialize JSON: {}", e))?;
+    Ok((scrubbed, report))
 }
 
 /// Recursively scrub PII from conversation value
-fn scrub_conversation_value(value: &mut Value) -> Result<(), String> {
+pub(crate) fn scrub_conversation_value(value: &mut Value, options: &ScrubOptions) -> Result<(), String> {
     match value {
         Value::Object(map) => {
             for (_, v) in map.iter_mut() {
-                scrub_conversation_value(v)?;
+                scrub_conversation_value(v, options)?;
             }
         }
         Value::Array(arr) => {
             for v in arr.iter_mut() {
-                scrub_conversation_value(v)?;
+                scrub_conversation_value(v, options)?;
             }
         }
         Value::String(s) => {
-            *s = scrub_text_string(s);
+            *s = scrub_string_value(s, options).0;
         }
         _ => {} // Numbers, booleans, null don't need scrubbing
     }
     Ok(())
 }
 
-/// Scrub sensitive information from text strings
-fn scrub_text_string(text: &str) -> String {
-    let mut result = text.to_string();
+/// Scrub one JSON string value, looking inside base64 payloads when `scrub_encoded` is set
+fn scrub_string_value(s: &str, options: &ScrubOptions) -> (String, ScrubReport) {
+    if options.scrub_encoded {
+        if let Some(found) = scrub_base64(s, options) {
+            return found;
+        }
+    }
+    scrub_text_with_report(s, options)
+}
+
+/// Decode `s` as standard base64; if it holds UTF-8 JSON or text, scrub that and re-encode.
+/// Returns None when `s` isn't such a payload or nothing inside needed redacting.
+fn scrub_base64(s: &str, options: &ScrubOptions) -> Option<(String, ScrubReport)> {
+    let encoded = s.trim();
+    if encoded.len() < 8 || !encoded.len().is_multiple_of(4) {
+        return None;
+    }
+    let engine = base64::engine::general_purpose::STANDARD;
+    let decoded = String::from_utf8(engine.decode(encoded).ok()?).ok()?;
+    if decoded.chars().any(|c| c.is_control() && !c.is_whitespace()) {
+        return None;
+    }
+    let (scrubbed, report) = match serde_json::from_str::<Value>(&decoded) {
+        Ok(mut inner) if inner.is_object() || inner.is_array() => {
+            let mut report = ScrubReport::default();
+            scrub_value_with_report(&mut inner, options, &mut report);
+            (serde_json::to_string(&inner).ok()?, report)
+        }
+        _ => scrub_string_value(&decoded, options),
+    };
+    if report.total == 0 {
+        return None;
+    }
+    Some((engine.encode(scrubbed), report))
+}
+
+/// Like `scrub_conversation_value`, accumulating what was redacted into `report`
+fn scrub_value_with_report(value: &mut Value, options: &ScrubOptions, report: &mut ScrubReport) {
+    match value {
+        Value::Object(map) => {
+            for (_, v) in map.iter_mut() {
+                scrub_value_with_report(v, options, report);
+            }
+        }
+        Value::Array(arr) => {
+            for v in arr.iter_mut() {
+                scrub_value_with_report(v, options, report);
+            }
+        }
+        Value::String(s) => {
+            let (scrubbed, found) = scrub_string_value(s, options);
+            *s = scrubbed;
+            report.merge(found);
+        }
+        _ => {} // Numbers, booleans, null don't need scrubbing
+    }
+}
+
+/// Parallel version of `scrub_value_with_report`. Each node is scrubbed independently,
+/// so the output is identical to the sequential pass.
+fn scrub_value_parallel(value: &mut Value, options: &ScrubOptions) -> ScrubReport {
+    let combine = |mut a: ScrubReport, b: ScrubReport| {
+        a.merge(b);
+        a
+    };
+    match value {
+        Value::Object(map) => map
+            .values_mut()
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .map(|v| scrub_value_parallel(v, options))
+            .reduce(ScrubReport::default, combine),
+        Value::Array(arr) => arr
+            .par_iter_mut()
+            .map(|v| scrub_value_parallel(v, options))
+            .reduce(ScrubReport::default, combine),
+        Value::String(s) => {
+            let (scrubbed, report) = scrub_string_value(s, options);
+            *s = scrubbed;
+            report
+        }
+        _ => ScrubReport::default(),
+    }
+}
+
+// -------- rules --------
+
+/// What kind of PII a scrub rule targets
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    AuthHeader,
+    Ssn,
+    NationalId,
+    DriversLicense,
+    Passport,
+    EmployeeId,
+    Vin,
+    LicensePlate,
+    Phone,
+    Email,
+    Address,
+    SocialHandle,
+    CreditCard,
+    BankAccount,
+    TaxId,
+    MedicalRecord,
+    Insurance,
+    IcdCode,
+    Date,
+    DateOfBirth,
+    Age,
+    IpAddress,
+    MacAddress,
+    Url,
+    DeviceId,
+    Name,
+}
+
+impl Category {
+    /// Secrets and financial/government identifiers whose presence warrants warning the user
+    pub fn is_high_risk(self) -> bool {
+        matches!(
+            self,
+            Category::AuthHeader | Category::Ssn | Category::NationalId | Category::CreditCard | Category::BankAccount
+        )
+    }
+
+    /// Numeric identifiers that `MaskStyle::LastFour` may partially reveal
+    fn is_maskable(self) -> bool {
+        matches!(self, Category::CreditCard | Category::Phone | Category::Ssn)
+    }
+}
+
+/// How a rule rewrites its matches
+enum Replace {
+    /// Replace the whole match with the token
+    Blocked,
+    /// Keep group 1 (the cue, e.g. a header name) and redact the rest of the match
+    AfterCue,
+    /// Write a fixed label in place of the cue and redact group 1 onwards, e.g. "Dr. BLOCKED"
+    Labeled(&'static str),
+    /// Custom logic, e.g. validation that may leave the match untouched (None)
+    With(fn(&Captures) -> Option<Redaction>),
+}
+
+/// What a rule keeps from one match and where the redacted value starts
+struct Redaction {
+    /// Text written before the token
+    prefix: String,
+    /// Byte offset in the match where the redacted value begins; it runs to the end
+    secret: usize,
+}
+
+impl Redaction {
+    /// Keep everything before capture group `group` and redact from it onwards
+    fn from_group(caps: &Captures, group: usize) -> Self {
+        let start = caps.get(group).unwrap().start() - caps.get(0).unwrap().start();
+        Self { prefix: caps[0][..start].to_string(), secret: start }
+    }
+
+    /// Redact the whole match
+    fn whole() -> Self {
+        Self { prefix: String::new(), secret: 0 }
+    }
+}
+
+struct Rule {
+    category: Category,
+    pattern: &'static str,
+    replace: Replace,
+    /// Option that switches the rule off, for rules that only run without a context cue
+    gate: Option<Gate>,
+}
+
+/// `ScrubOptions` switches that turn off a cue-less variant of a rule
+#[derive(Clone, Copy)]
+enum Gate {
+    /// Bare dotted quads, off when `ip_requires_context` is set
+    IpContext,
+    /// Bare @handles, off when `handles_require_platform` is set
+    HandlePlatform,
+}
+
+const fn blocked(category: Category, pattern: &'static str) -> Rule {
+    Rule { category, pattern, replace: Replace::Blocked, gate: None }
+}
+
+const fn after_cue(category: Category, pattern: &'static str) -> Rule {
+    Rule { category, pattern, replace: Replace::AfterCue, gate: None }
+}
+
+const fn labeled(category: Category, pattern: &'static str, label: &'static str) -> Rule {
+    Rule { category, pattern, replace: Replace::Labeled(label), gate: None }
+}
+
+/// All scrub rules, applied in order
+const RULES: &[Rule] = &[
+    // ===== CREDENTIALS =====
+    
+    // Sensitive HTTP headers (curl -H, header dumps) - keep the header name, blank the value
+    after_cue(Category::AuthHeader, r#"(?i)(\b(?:Proxy-)?(?:Authorization|X-Api-Key|X-Auth-Token|X-Access-Token|Api-Key|Cookie|Set-Cookie)\s*:\s*(?:(?:Bearer|Basic|Token|Digest)\s+)?)[^\s'",;]+"#),
     
     // ===== PERSONAL IDENTIFIERS =====
     
     // SSN detection - specific formats only
-    let ssn_patterns = [
-        r"\b\d{3}-\d{2}-\d{4}\b",           // XXX-XX-XXXX
-        r"\b\d{3}\s\d{2}\s\d{4}\b",         // XXX XX XXXX
-        r"\b\d{3}\.\d{2}\.\d{4}\b",         // XXX.XX.XXXX
-    ];
-    for pattern in ssn_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Ssn, r"\b\d{3}-\d{2}-\d{4}\b"),                     // XXX-XX-XXXX
+    blocked(Category::Ssn, r"\b\d{3}\s\d{2}\s\d{4}\b"),                   // XXX XX XXXX
+    blocked(Category::Ssn, r"\b\d{3}\.\d{2}\.\d{4}\b"),                   // XXX.XX.XXXX
     
     // Driver's License patterns (only specific formats)
-    let dl_patterns = [
-        r"\b[A-Z]\d{7}\b",                   // A1234567
-    ];
-    for pattern in dl_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::DriversLicense, r"\b[A-Z]\d{7}\b"),                 // A1234567
     
     // Passport numbers
-    let passport_regex = Regex::new(r"\b[A-Z]\d{8}\b").unwrap();
-    result = passport_regex.replace_all(&result, "BLOCKED").to_string();
+    blocked(Category::Passport, r"\b[A-Z]\d{8}\b"),
     
     // Employee ID patterns (only specific formats)
-    let employee_patterns = [
-        r"\bEMP\d{6}\b",                     // EMP123456
-    ];
-    for pattern in employee_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::EmployeeId, r"\bEMP\d{6}\b"),                       // EMP123456
+    
+    // ===== VEHICLE IDENTIFIERS =====
+    
+    // VINs: 17 chars, no I/O/Q. Require a valid check digit unless a "VIN" cue precedes it
+    // (European VINs don't use the check digit).
+    Rule {
+        category: Category::Vin,
+        pattern: r"(?i)\b(VIN(?:\s*(?:number|no\.?|#))?\s*[:#]?\s*)?\b([A-HJ-NPR-Z0-9]{17})\b",
+        replace: Replace::With(replace_vin),
+        gate: None,
+    },
+    
+    // License plates - only when introduced by a cue word, and must contain a digit
+    Rule {
+        category: Category::LicensePlate,
+        pattern: r"(?i)\b((?:license|licence|registration)\s+plate(?:\s+(?:number|no\.?|#))?|plate\s+(?:number|no\.?|#)|tag\s+number)(\s*(?:is|:|#)?\s*)([A-Z0-9]{1,4}[- ]?[A-Z0-9]{1,4})\b",
+        replace: Replace::With(replace_plate),
+        gate: None,
+    },
     
     // ===== CONTACT INFORMATION =====
     
     // Phone numbers - specific phone formats only
-    let phone_patterns = [
-        r"\b\+\d{1,3}[-.\s]?\d{1,4}[-.\s]?\d{1,4}[-.\s]?\d{1,9}\b",  // International
-        r"\b\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b",                    // US Domestic
-        r"\b1[-.\s]?\d{3}[-.\s]?\d{3}[-.\s]?\d{4}\b",                 // US with 1
-    ];
-    for pattern in phone_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Phone, r"\b\+\d{1,3}[-.\s]?\d{1,4}[-.\s]?\d{1,4}[-.\s]?\d{1,9}\b"),  // International
+    blocked(Category::Phone, r"\b\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b"),                    // US Domestic
+    blocked(Category::Phone, r"\b1[-.\s]?\d{3}[-.\s]?\d{3}[-.\s]?\d{4}\b"),                 // US with 1
     
     // Phone extensions
-    let ext_regex = Regex::new(r"\b(?:ext|extension|ext\.)\s*\d{1,5}\b").unwrap();
-    result = ext_regex.replace_all(&result, "BLOCKED").to_string();
+    blocked(Category::Phone, r"\b(?:ext|extension|ext\.)\s*\d{1,5}\b"),
     
     // Fax numbers
-    let fax_regex = Regex::new(r"\b(?:fax|f\.)\s*\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b").unwrap();
-    result = fax_regex.replace_all(&result, "BLOCKED").to_string();
+    blocked(Category::Phone, r"\b(?:fax|f\.)\s*\(?\d{3}\)?[-.\s]?\d{3}[-.\s]?\d{4}\b"),
     
     // Email detection - comprehensive patterns
-    let email_patterns = [
-        r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b",        // Standard email
-        r"\b[A-Za-z0-9._%+-]+\s+at\s+[A-Za-z0-9.-]+\s+dot\s+[A-Z|a-z]{2,}\b", // Spoken "at dot"
-        r"\b[A-Za-z0-9._%+-]+\s+@\s+[A-Za-z0-9.-]+\s+\.\s+[A-Z|a-z]{2,}\b",   // Spoken "@ ."
-        r"\b[A-Za-z0-9._%+-]+\s+at\s+[A-Za-z0-9.-]+\s+\.\s+[A-Z|a-z]{2,}\b",  // Spoken "at ."
-    ];
-    for pattern in email_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Email, r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b"),        // Standard email
+    blocked(Category::Email, r"\b[A-Za-z0-9._%+-]+\s+at\s+[A-Za-z0-9.-]+\s+dot\s+[A-Z|a-z]{2,}\b"), // Spoken "at dot"
+    blocked(Category::Email, r"\b[A-Za-z0-9._%+-]+\s+@\s+[A-Za-z0-9.-]+\s+\.\s+[A-Z|a-z]{2,}\b"),   // Spoken "@ ."
+    blocked(Category::Email, r"\b[A-Za-z0-9._%+-]+\s+at\s+[A-Za-z0-9.-]+\s+\.\s+[A-Z|a-z]{2,}\b"),  // Spoken "at ."
     
     // Address patterns - specific address formats only
-    let address_patterns = [
-        r"\b\d+\s+[A-Za-z\s]+(?:Street|St|Avenue|Ave|Road|Rd|Boulevard|Blvd|Drive|Dr|Lane|Ln|Court|Ct|Place|Pl|Way|Circle|Cir)\b", // Street addresses
-        r"\b[A-Za-z\s]+,\s*[A-Za-z\s]+,\s*[A-Z]{2}\s*\d{5}(?:-\d{4})?\b",     // City, State ZIP
-    ];
-    for pattern in address_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Address, r"\b\d+\s+[A-Za-z\s]+(?:Street|St|Avenue|Ave|Road|Rd|Boulevard|Blvd|Drive|Dr|Lane|Ln|Court|Ct|Place|Pl|Way|Circle|Cir)\b"), // Street addresses
+    blocked(Category::Address, r"\b[A-Za-z\s]+,\s*[A-Za-z\s]+,\s*[A-Z]{2}\s*\d{5}(?:-\d{4})?\b"),     // City, State ZIP
     
-    // Social media handles (only actual handles, not random words)
-    let social_patterns = [
-        r"\b@[A-Za-z0-9_]{1,15}\b",                                        // Twitter/Instagram handles
-    ];
-    for pattern in social_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    // Social media handles. Runs after the email rules; an @word touching a domain or
+    // another word, a decorator call, or a known mention/annotation (@here, @Override) is kept.
+    Rule {
+        category: Category::SocialHandle,
+        pattern: r"(?i)(\b(?:twitter|tweet|instagram|insta|ig|tiktok|threads|mastodon|bluesky|telegram|github|handle|follow|dm)\b[^@\n]{0,30}?)@([a-z0-9_]{1,15})(\.[a-z]|[\w@(])?",
+        replace: Replace::With(replace_handle),
+        gate: None,
+    },
+    Rule {
+        category: Category::SocialHandle,
+        pattern: HANDLE_BARE,
+        replace: Replace::With(replace_handle),
+        gate: Some(Gate::HandlePlatform),
+    },
     
     // ===== FINANCIAL INFORMATION =====
     
     // Credit card patterns - specific formats only
-    let cc_patterns = [
-        r"\b\d{4}[-.\s]?\d{4}[-.\s]?\d{4}[-.\s]?\d{4}\b",                // 16 digits (Visa/MC)
-        r"\b\d{4}[-.\s]?\d{6}[-.\s]?\d{5}\b",                             // 15 digits (Amex)
-    ];
-    for pattern in cc_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::CreditCard, r"\b\d{4}[-.\s]?\d{4}[-.\s]?\d{4}[-.\s]?\d{4}\b"),         // 16 digits (Visa/MC)
+    blocked(Category::CreditCard, r"\b\d{4}[-.\s]?\d{6}[-.\s]?\d{5}\b"),                      // 15 digits (Amex)
     
     // Bank account and routing numbers (only specific formats)
-    let bank_patterns = [
-        r"\b\d{9}\b",                                                       // Routing number (exact 9 digits)
-        r"\b[A-Z]{2}\d{2}[A-Z0-9]{4}\d{7}([A-Z0-9]?){0,16}\b",           // IBAN
-    ];
-    for pattern in bank_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    Rule {
+        // Routing number (exact 9 digits) - only when the ABA checksum holds
+        category: Category::BankAccount,
+        pattern: r"\b\d{9}\b",
+        replace: Replace::With(replace_aba_routing),
+        gate: None,
+    },
+    blocked(Category::BankAccount, r"\b[A-Z]{2}\d{2}[A-Z0-9]{4}\d{7}([A-Z0-9]?){0,16}\b"),    // IBAN
     
     // Tax IDs (only specific formats, not all 9-digit numbers)
-    let tax_patterns = [
-        r"\b\d{2}-\d{7}\b",                                                 // EIN XX-XXXXXXX
-        r"\b\d{3}-\d{2}-\d{4}\b",                                          // TIN XXX-XX-XXXX
-    ];
-    for pattern in tax_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::TaxId, r"\b\d{2}-\d{7}\b"),                                          // EIN XX-XXXXXXX
+    blocked(Category::TaxId, r"\b\d{3}-\d{2}-\d{4}\b"),                                   // TIN XXX-XX-XXXX
     
     // ===== MEDICAL/HEALTH INFORMATION =====
     
     // Medical record numbers (only specific formats)
-    let medical_patterns = [
-        r"\bMRN\d{6,8}\b",                                                  // MRN123456
-    ];
-    for pattern in medical_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::MedicalRecord, r"\bMRN\d{6,8}\b"),                                   // MRN123456
     
     // Insurance numbers (only specific formats)
-    let insurance_patterns = [
-        r"\b[A-Z]{3}\d{6,8}\b",                                             // Group IDs
-    ];
-    for pattern in insurance_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Insurance, r"\b[A-Z]{3}\d{6,8}\b"),                                  // Group IDs
     
     // ICD codes
-    let icd_regex = Regex::new(r"\b[A-Z]\d{2}\.\d{1,2}[A-Z0-9]?\b").unwrap();
-    result = icd_regex.replace_all(&result, "BLOCKED").to_string();
+    blocked(Category::IcdCode, r"\b[A-Z]\d{2}\.\d{1,2}[A-Z0-9]?\b"),
     
     // ===== TEMPORAL DATA =====
     
+    // Dates of birth - the cue forces redaction in any format, including ones the generic date rules miss
+    after_cue(Category::DateOfBirth, r"(?i)(\b(?:DOB|D\.O\.B\.?|date of birth|birth\s?date|born on)\s*[:\-]?\s*)(?:\d{1,2}[/.\-]\d{1,2}[/.\-]\d{2,4}|\d{4}[/.\-]\d{1,2}[/.\-]\d{1,2}|\d{1,2}(?:st|nd|rd|th)?\s+(?:of\s+)?(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\.?,?\s+\d{4}|(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\.?\s+\d{1,2}(?:st|nd|rd|th)?,?\s+\d{4})\b"),
+
     // Date patterns - specific date formats only
-    let date_patterns = [
-        r"\b\d{1,2}/\d{1,2}/\d{4}\b",                                      // MM/DD/YYYY
-        r"\b\d{4}-\d{1,2}-\d{1,2}\b",                                      // YYYY-MM-DD
-        r"\b(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\s+\d{1,2},?\s+\d{4}\b", // Month DD, YYYY
-    ];
-    for pattern in date_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Date, r"\b\d{1,2}/\d{1,2}/\d{4}\b"),                                 // MM/DD/YYYY
+    blocked(Category::Date, r"\b\d{4}-\d{1,2}-\d{1,2}\b"),                                 // YYYY-MM-DD
+    blocked(Category::Date, r"\b(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Oct|Nov|Dec)[a-z]*\s+\d{1,2},?\s+\d{4}\b"), // Month DD, YYYY
     
     // Age patterns (only specific age contexts)
-    let age_patterns = [
-        r"\bage\s*\d{1,3}\b",                                               // age 25
-        r"\b\d{1,3}\s*years?\s*old\b",                                     // 25 years old
-        r"\b(?:born|birth)\s+(?:in\s+)?\d{4}\b",                           // born 1990, birth 1990
-    ];
-    for pattern in age_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Age, r"\bage\s*\d{1,3}\b"),                                          // age 25
+    blocked(Category::Age, r"\b\d{1,3}\s*years?\s*old\b"),                                // 25 years old
+    blocked(Category::Age, r"\b(?:born|birth)\s+(?:in\s+)?\d{4}\b"),                      // born 1990, birth 1990
     
     // ===== DIGITAL IDENTIFIERS =====
     
     // IP addresses - IPv4 and IPv6
-    let ip_patterns = [
-        r"\b(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\b", // IPv4
-        r"\b(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}\b",                   // IPv6 full
-        r"\b(?:[0-9a-fA-F]{1,4}:){1,7}:\b",                                // IPv6 compressed
-        r"\b::(?:[0-9a-fA-F]{1,4}:){1,7}\b",                               // IPv6 compressed
-        r"\b(?:[0-9a-fA-F]{1,4}:){1,6}::[0-9a-fA-F]{1,4}\b",              // IPv6 compressed
-    ];
-    for pattern in ip_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    // IPv4: every octet 0-255 without leading zeros, and not part of a longer dotted run
+    // ("1.2.3.4.5"). A cue word, a reserved range or a port/CIDR suffix marks an obvious IP;
+    // other bare dotted quads are skipped when `ip_requires_context` is set.
+    Rule {
+        category: Category::IpAddress,
+        pattern: r"(?i)(\b(?:ipv4|ip\s+address|ip|address|addr|host|server)\b[^\w\n]{0,3}(?:(?:is|was|of|at|from)\s+)?)((?:\d{1,3}\.){3}\d{1,3})(\.\d|\w)?",
+        replace: Replace::With(replace_ipv4),
+        gate: None,
+    },
+    Rule {
+        category: Category::IpAddress,
+        pattern: r"(^|[^\w.])((?:\d{1,3}\.){3}\d{1,3})(:\d{1,5}\b|/\d{1,2}\b)?(\.\d|\w)?",
+        replace: Replace::With(replace_obvious_ipv4),
+        gate: None,
+    },
+    Rule {
+        category: Category::IpAddress,
+        pattern: IPV4_BARE,
+        replace: Replace::With(replace_ipv4),
+        gate: Some(Gate::IpContext),
+    },
+    blocked(Category::IpAddress, r"\b(?:[0-9a-fA-F]{1,4}:){7}[0-9a-fA-F]{1,4}\b"),         // IPv6 full
+    blocked(Category::IpAddress, r"\b(?:[0-9a-fA-F]{1,4}:){1,7}:\b"),                      // IPv6 compressed
+    blocked(Category::IpAddress, r"\b::(?:[0-9a-fA-F]{1,4}:){1,7}\b"),                     // IPv6 compressed
+    blocked(Category::IpAddress, r"\b(?:[0-9a-fA-F]{1,4}:){1,6}::[0-9a-fA-F]{1,4}\b"),    // IPv6 compressed
     
     // MAC addresses
-    let mac_regex = Regex::new(r"\b(?:[0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}\b").unwrap();
-    result = mac_regex.replace_all(&result, "BLOCKED").to_string();
+    blocked(Category::MacAddress, r"\b(?:[0-9A-Fa-f]{2}[:-]){5}[0-9A-Fa-f]{2}\b"),
     
     // URLs and file paths - specific formats only
-    let url_patterns = [
-        r"\bhttps?://[^\s]+\b",                                             // HTTP/HTTPS URLs
-        r"\bwww\.[^\s]+\b",                                                 // WWW URLs
-        r"\b[A-Za-z]:\\[^\s]*\b",                                          // Windows file paths
-    ];
-    for pattern in url_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::Url, r"\bhttps?://[^\s]+\b"),                                        // HTTP/HTTPS URLs
+    blocked(Category::Url, r"\bwww\.[^\s]+\b"),                                            // WWW URLs
+    blocked(Category::Url, r"\b[A-Za-z]:\\[^\s]*\b"),                                     // Windows file paths
     
     // Device IDs and serial numbers (only specific formats)
-    let device_patterns = [
-        r"\b[A-Z]{2}\d{6,8}[A-Z0-9]{2,4}\b",                              // Serial numbers
-    ];
-    for pattern in device_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, "BLOCKED").to_string();
-    }
+    blocked(Category::DeviceId, r"\b[A-Z]{2}\d{6,8}[A-Z0-9]{2,4}\b"),                    // Serial numbers
     
     // ===== ENHANCED NAME DETECTION =====
     
     // Specific name patterns (case insensitive) - only actual personal names
-    let name_patterns = [
-        // Direct identification
-        (r"(?i)\bmy name is\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "my name is BLOCKED"),
-        (r"(?i)\bI'm\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "I'm BLOCKED"),
-        (r"(?i)\bI am\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "I am BLOCKED"),
-        (r"(?i)\bcall me\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "call me BLOCKED"),
-        (r"(?i)\bthis is\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "this is BLOCKED"),
-        
-        // Greetings and introductions
-        (r"(?i)\bnice to meet you,?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "nice to meet you, BLOCKED"),
-        
-        // Professional contexts (only with titles)
-        (r"(?i)\bdr\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Dr. BLOCKED"),
-        (r"(?i)\bprofessor\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Professor BLOCKED"),
-        (r"(?i)\bprof\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Prof. BLOCKED"),
-        (r"(?i)\bmr\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Mr. BLOCKED"),
-        (r"(?i)\bms\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Ms. BLOCKED"),
-        (r"(?i)\bmrs\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Mrs. BLOCKED"),
-        (r"(?i)\bmiss\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Miss BLOCKED"),
-        
-        // Family relationships
-        (r"(?i)\bmy (?:father|dad|mother|mom|sister|brother|son|daughter|uncle|aunt|cousin|grandfather|grandmother|grandpa|grandma)\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "my family member BLOCKED"),
-    ];
+    // Direct identification
+    labeled(Category::Name, r"(?i)\bmy name is\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "my name is "),
+    labeled(Category::Name, r"(?i)\bI'm\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "I'm "),
+    labeled(Category::Name, r"(?i)\bI am\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "I am "),
+    labeled(Category::Name, r"(?i)\bcall me\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "call me "),
+    labeled(Category::Name, r"(?i)\bthis is\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "this is "),
     
-    for (pattern, replacement) in name_patterns.iter() {
-        let regex = Regex::new(pattern).unwrap();
-        result = regex.replace_all(&result, *replacement).to_string();
-    }
+    // Greetings and introductions
+    labeled(Category::Name, r"(?i)\bnice to meet you,?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "nice to meet you, "),
+    
+    // Professional contexts (only with titles)
+    labeled(Category::Name, r"(?i)\bdr\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Dr. "),
+    labeled(Category::Name, r"(?i)\bprofessor\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Professor "),
+    labeled(Category::Name, r"(?i)\bprof\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Prof. "),
+    labeled(Category::Name, r"(?i)\bmr\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Mr. "),
+    labeled(Category::Name, r"(?i)\bms\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Ms. "),
+    labeled(Category::Name, r"(?i)\bmrs\.?\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Mrs. "),
+    labeled(Category::Name, r"(?i)\bmiss\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "Miss "),
     
-    // Only block names in specific contexts, not random capitalized word pairs
+    // Family relationships
+    labeled(Category::Name, r"(?i)\bmy (?:father|dad|mother|mom|sister|brother|son|daughter|uncle|aunt|cousin|grandfather|grandmother|grandpa|grandma)\s+([A-Z][a-z]+(?:\s+[A-Z][a-z]+)*)\b", "my family member "),
     
     // Only block actual names in specific contexts, not random capitalized words
+];
+
+/// Countries whose national ID formats can be scrubbed in addition to the US rules
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    Uk,
+    Ca,
+}
+
+const ALL_LOCALES: &[Locale] = &[Locale::Uk, Locale::Ca];
+
+/// National ID rules, applied before `RULES` for each enabled locale
+const LOCALE_RULES: &[(Locale, Rule)] = &[
+    // UK National Insurance number: AB123456C, optionally spaced "AB 12 34 56 C"
+    (Locale::Uk, Rule {
+        category: Category::NationalId,
+        pattern: r"\b([A-Z]{2})\s?(\d{2})\s?(\d{2})\s?(\d{2})\s?([A-D])\b",
+        replace: Replace::With(replace_uk_nino),
+        gate: None,
+    }),
+    // Canadian SIN: 9 digits, optionally grouped 3-3-3, must pass the Luhn check
+    (Locale::Ca, Rule {
+        category: Category::NationalId,
+        pattern: r"\b(\d{3})[-\s]?(\d{3})[-\s]?(\d{3})\b",
+        replace: Replace::With(replace_ca_sin),
+        gate: None,
+    }),
+];
+
+/// Settings from the optional `[scrubber]` table of config.toml
+#[derive(Deserialize)]
+struct ScrubberConfig {
+    #[serde(default)]
+    locales: Vec<Locale>,
+    /// Scrub large conversations across threads
+    #[serde(default = "default_parallel")]
+    parallel: bool,
+    /// Inputs smaller than this are always scrubbed sequentially
+    #[serde(default = "default_parallel_min_bytes")]
+    parallel_min_bytes: usize,
+    /// Redact dates next to a birth cue ("DOB:", "date of birth") as date_of_birth
+    #[serde(default = "default_date_of_birth")]
+    date_of_birth: bool,
+}
+
+fn default_parallel() -> bool {
+    true
+}
+
+fn default_parallel_min_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_date_of_birth() -> bool {
+    true
+}
+
+impl Default for ScrubberConfig {
+    fn default() -> Self {
+        Self {
+            locales: Vec::new(),
+            parallel: default_parallel(),
+            parallel_min_bytes: default_parallel_min_bytes(),
+            date_of_birth: default_date_of_birth(),
+        }
+    }
+}
+
+#[derive(Deserialize, Default)]
+struct ConfigFile {
+    #[serde(default)]
+    scrubber: ScrubberConfig,
+}
+
+/// The `[scrubber]` table of config.toml, read once
+fn scrubber_config() -> &'static ScrubberConfig {
+    static CONFIG: OnceLock<ScrubberConfig> = OnceLock::new();
+    CONFIG.get_or_init(|| {
+        let text = match crate::aws_uploader::read_config_file() {
+            Ok((text, _)) => text,
+            Err(_) => return ScrubberConfig::default(),
+        };
+        match toml::from_str::<ConfigFile>(&text) {
+            Ok(file) => file.scrubber,
+            Err(e) => {
+                eprintln!("[scrubber] Ignoring invalid [scrubber] config: {}", e);
+                ScrubberConfig::default()
+            }
+        }
+    })
+}
+
+/// Locales enabled in config.toml
+fn configured_locales() -> &'static [Locale] {
+    &scrubber_config().locales
+}
+
+/// Bare dotted quad with the characters around it, so `replace_ipv4` can reject longer runs
+const IPV4_BARE: &str = r"(^|[^\w.])((?:\d{1,3}\.){3}\d{1,3})(\.\d|\w)?";
+
+/// Four octets, each 0-255 and without a leading zero
+fn parse_ipv4(s: &str) -> Option<[u8; 4]> {
+    let mut octets = [0u8; 4];
+    let mut parts = s.split('.');
+    for octet in &mut octets {
+        let part = parts.next()?;
+        if part.len() > 1 && part.starts_with('0') {
+            return None;
+        }
+        *octet = part.parse().ok()?;
+    }
+    parts.next().is_none().then_some(octets)
+}
+
+/// Groups: 1 = kept prefix, 2 = address, last = trailing text that makes it a longer token
+fn replace_ipv4(caps: &Captures) -> Option<Redaction> {
+    (caps.get(3).is_none() && parse_ipv4(&caps[2]).is_some()).then(|| Redaction::from_group(caps, 2))
+}
+
+/// Like `replace_ipv4`, but only for private/loopback/link-local addresses or ones
+/// followed by a port or CIDR suffix (group 3), which is redacted along with them
+fn replace_obvious_ipv4(caps: &Captures) -> Option<Redaction> {
+    let [a, b, ..] = parse_ipv4(&caps[2])?;
+    let reserved = a == 10 || a == 127 || (a == 172 && (16..32).contains(&b)) || (a, b) == (192, 168) || (a, b) == (169, 254);
+    (caps.get(4).is_none() && (reserved || caps.get(3).is_some())).then(|| Redaction::from_group(caps, 2))
+}
+
+/// Bare @handle with the characters around it; skipped when `handles_require_platform` is set
+const HANDLE_BARE: &str = r"(^|[^\w@.])@([A-Za-z0-9_]{1,15})(\.[A-Za-z]|[\w@(])?";
+
+/// Chat mentions and code annotations/doc tags that look like handles
+const NOT_HANDLES: &[&str] = &[
+    "channel", "here", "everyone", "all",
+    "Override", "Deprecated", "SuppressWarnings", "FunctionalInterface", "SafeVarargs", "Test",
+    "Autowired", "Component", "Service", "Bean", "Entity", "Inject", "Nullable", "NonNull",
+    "param", "return", "returns", "throws", "type", "property", "staticmethod", "classmethod",
+    "dataclass", "media", "import", "keyframes", "apply", "interface", "implementation", "end",
+];
+
+/// Groups: 1 = kept prefix, 2 = handle without the @, 3 = trailing text that rules it out
+fn replace_handle(caps: &Captures) -> Option<Redaction> {
+    let handle = &caps[2];
+    // The @ goes with the handle
+    let keep = caps[1].len();
+    (caps.get(3).is_none() && !NOT_HANDLES.iter().any(|word| word.eq_ignore_ascii_case(handle)))
+        .then(|| Redaction { prefix: caps[1].to_string(), secret: keep })
+}
+
+fn replace_uk_nino(caps: &Captures) -> Option<Redaction> {
+    // First letter never D, F, I, Q, U, V; second never D, F, I, O, Q, U, V; some prefixes are unassigned
+    let prefix = &caps[1];
+    let mut letters = prefix.chars();
+    let (first, second) = (letters.next().unwrap(), letters.next().unwrap());
+    let valid = !"DFIQUV".contains(first)
+        && !"DFIOQUV".contains(second)
+        && !["BG", "GB", "NK", "KN", "TN", "NT", "ZZ"].contains(&prefix);
+    valid.then(Redaction::whole)
+}
+
+fn replace_ca_sin(caps: &Captures) -> Option<Redaction> {
+    let digits: String = format!("{}{}{}", &caps[1], &caps[2], &caps[3]);
+    is_luhn_valid(&digits).then(Redaction::whole)
+}
+
+fn is_luhn_valid(digits: &str) -> bool {
+    let sum: u32 = digits
+        .chars()
+        .rev()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+/// ABA routing checksum: 3·(d1+d4+d7) + 7·(d2+d5+d8) + (d3+d6+d9) must be a multiple of 10
+fn is_aba_routing(s: &str) -> bool {
+    let digits: Vec<u32> = s.chars().filter_map(|c| c.to_digit(10)).collect();
+    if digits.len() != 9 || s.len() != 9 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .enumerate()
+        .map(|(i, d)| d * [3, 7, 1][i % 3])
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn replace_aba_routing(caps: &Captures) -> Option<Redaction> {
+    is_aba_routing(&caps[0]).then(Redaction::whole)
+}
+
+fn replace_vin(caps: &Captures) -> Option<Redaction> {
+    let vin = &caps[2];
+    let has_cue = caps.get(1).is_some();
+    let looks_like_vin = vin.chars().any(|c| c.is_ascii_digit()) && vin.chars().any(|c| c.is_ascii_alphabetic());
+    (looks_like_vin && (has_cue || is_valid_vin_check_digit(vin))).then(|| Redaction::from_group(caps, 2))
+}
+
+fn replace_plate(caps: &Captures) -> Option<Redaction> {
+    caps[3].chars().any(|c| c.is_ascii_digit()).then(|| Redaction::from_group(caps, 3))
+}
+
+// -------- scrubbing --------
+
+/// Per-category counts of what a scrub pass redacted
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct ScrubReport {
+    pub counts: BTreeMap<Category, usize>,
+    /// Matches redacted by custom patterns
+    pub custom: usize,
+    pub total: usize,
+}
+
+impl ScrubReport {
+    fn record(&mut self, category: Category, n: usize) {
+        if n > 0 {
+            *self.counts.entry(category).or_insert(0) += n;
+            self.total += n;
+        }
+    }
+
+    /// Counts for the high-risk categories only
+    pub fn high_risk_counts(&self) -> BTreeMap<Category, usize> {
+        self.counts.iter().filter(|(c, _)| c.is_high_risk()).map(|(c, n)| (*c, *n)).collect()
+    }
+
+    fn record_custom(&mut self, n: usize) {
+        self.custom += n;
+        self.total += n;
+    }
+
+    fn merge(&mut self, other: ScrubReport) {
+        for (category, n) in other.counts {
+            self.record(category, n);
+        }
+        self.record_custom(other.custom);
+    }
+}
+
+/// Scrub sensitive information from text strings
+pub(crate) fn scrub_text_string(text: &str, options: &ScrubOptions) -> String {
+    scrub_text_with_report(text, options).0
+}
+
+/// Scrub a string with only the rules for `categories`, for spot fixes on stored content.
+/// Tokens, masking, gating and the allowlist come from `options`; custom patterns don't run.
+pub(crate) fn scrub_text_categories(text: &str, categories: &[Category], options: &ScrubOptions) -> (String, ScrubReport) {
+    let mut result = text.to_string();
+    let mut report = ScrubReport::default();
+    let rules = active_rules(configured_locales())
+        .filter(|rule| categories.contains(&rule.category))
+        .filter(|rule| !options.skips(rule));
+    for rule in rules {
+        let (scrubbed, hits) = apply_rule(rule, &result, options);
+        result = scrubbed;
+        report.record(rule.category, hits);
+    }
+    (result, report)
+}
+
+/// Scrub a string, also reporting how many matches each category redacted
+fn scrub_text_with_report(text: &str, options: &ScrubOptions) -> (String, ScrubReport) {
+    scrub_text_with_locales(text, configured_locales(), options)
+}
+
+/// Scrub with the national ID rules for `locales` in addition to the built-in rules
+fn scrub_text_with_locales(text: &str, locales: &[Locale], options: &ScrubOptions) -> (String, ScrubReport) {
+    let mut result = text.to_string();
+    let mut report = ScrubReport::default();
+    
+    let rules = active_rules(locales)
+        .filter(|rule| options.categories.allows(rule.category))
+        .filter(|rule| !options.skips(rule));
+    // One pass over the text finds which rules can match at all; it is redone only after
+    // a replacement changes the text, so the output is the same as running every rule.
+    let (set, index) = rule_set();
+    let mut matching = set.matches(&result);
+    for rule in rules {
+        if !matching.matched(index[rule.pattern]) {
+            continue;
+        }
+        let (scrubbed, hits) = apply_rule(rule, &result, options);
+        if scrubbed != result {
+            result = scrubbed;
+            matching = set.matches(&result);
+        }
+        report.record(rule.category, hits);
+    }
+    
+    // Invalid custom patterns are rejected up front by `ScrubOptions::validate`
+    for (pattern, replacement) in &options.custom_patterns {
+        if let Ok(regex) = custom_regex(pattern) {
+            let mut hits = 0;
+            result = regex.replace_all(&result, |caps: &Captures| {
+                if options.is_allowlisted(&caps[0]) {
+                    return caps[0].to_string();
+                }
+                hits += 1;
+                let mut expanded = String::new();
+                caps.expand(replacement, &mut expanded);
+                expanded
+            }).to_string();
+            report.record_custom(hits);
+        }
+    }
     
-    result
+    (result, report)
+}
+
+/// Locale rules for `locales` followed by the built-in rules, in application order
+fn active_rules(locales: &[Locale]) -> impl Iterator<Item = &'static Rule> + '_ {
+    LOCALE_RULES
+        .iter()
+        .filter(move |(locale, _)| locales.contains(locale))
+        .map(|(_, rule)| rule)
+        .chain(RULES)
+        .filter(|rule| rule.category != Category::DateOfBirth || scrubber_config().date_of_birth)
+}
+
+/// Every rule's pattern, compiled on first use and shared for the life of the process
+fn compiled_rules() -> &'static HashMap<&'static str, Regex> {
+    static COMPILED: OnceLock<HashMap<&'static str, Regex>> = OnceLock::new();
+    COMPILED.get_or_init(|| {
+        LOCALE_RULES
+            .iter()
+            .map(|(_, rule)| rule)
+            .chain(RULES)
+            .map(|rule| (rule.pattern, Regex::new(rule.pattern).unwrap()))
+            .collect()
+    })
+}
+
+/// Every rule's pattern in one `RegexSet`, with each pattern's index in the set
+fn rule_set() -> &'static (RegexSet, HashMap<&'static str, usize>) {
+    static SET: OnceLock<(RegexSet, HashMap<&'static str, usize>)> = OnceLock::new();
+    SET.get_or_init(|| {
+        let patterns: Vec<&'static str> = LOCALE_RULES.iter().map(|(_, rule)| rule).chain(RULES).map(|rule| rule.pattern).collect();
+        let set = regex::RegexSetBuilder::new(&patterns).size_limit(1 << 26).build().unwrap();
+        let index = patterns.iter().enumerate().map(|(i, pattern)| (*pattern, i)).collect();
+        (set, index)
+    })
+}
+
+fn regex_for(rule: &Rule) -> &'static Regex {
+    &compiled_rules()[rule.pattern]
+}
+
+/// Apply one rule, returning the rewritten text and how many matches it redacted
+fn apply_rule(rule: &Rule, text: &str, options: &ScrubOptions) -> (String, usize) {
+    let regex = regex_for(rule);
+    let token = options.token_for(rule.category);
+    let mut hits = 0;
+    let result = regex.replace_all(text, |caps: &Captures| {
+        let matched = &caps[0];
+        let redaction = match rule.replace {
+            Replace::Blocked => Some(Redaction::whole()),
+            Replace::AfterCue => Some(Redaction { prefix: caps[1].to_string(), secret: caps[1].len() }),
+            Replace::Labeled(label) => {
+                let start = caps.get(1).unwrap().start() - caps.get(0).unwrap().start();
+                Some(Redaction { prefix: label.to_string(), secret: start })
+            }
+            Replace::With(f) => f(caps),
+        };
+        let Some(Redaction { prefix, secret }) = redaction else {
+            return matched.to_string();
+        };
+        let value = &matched[secret..];
+        if options.is_allowlisted(value) {
+            return matched.to_string();
+        }
+        let masked = matches!(rule.replace, Replace::Blocked) && options.mask_style == MaskStyle::LastFour && rule.category.is_maskable();
+        let replaced = if masked {
+            mask_last_four(value)
+        } else if let Some(map) = &options.pseudonyms {
+            map.0.lock().unwrap().token_for(rule.category, value)
+        } else {
+            token.to_string()
+        };
+        let replaced = format!("{}{}", prefix, replaced);
+        if replaced != matched {
+            hits += 1;
+        }
+        replaced
+    }).to_string();
+    (result, hits)
+}
+
+/// Replace every digit except the last four with '*', leaving separators in place
+fn mask_last_four(matched: &str) -> String {
+    let digits = matched.chars().filter(|c| c.is_ascii_digit()).count();
+    let mut seen = 0;
+    matched
+        .chars()
+        .map(|c| {
+            if !c.is_ascii_digit() {
+                return c;
+            }
+            seen += 1;
+            if seen + 4 > digits { c } else { '*' }
+        })
+        .collect()
+}
+
+/// Time spent by one category's rules in `profile_scrub`
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleTiming {
+    pub category: Category,
+    pub rules: usize,
+    pub micros: u64,
+    pub matches: usize,
+}
+
+/// Scrub `sample` once, timing each category's rules
+pub fn profile_scrub(sample: &str) -> Vec<RuleTiming> {
+    let mut timings: Vec<RuleTiming> = Vec::new();
+    let mut text = sample.to_string();
+    for rule in active_rules(configured_locales()) {
+        let started = std::time::Instant::now();
+        let (scrubbed, hits) = apply_rule(rule, &text, &ScrubOptions::default());
+        let micros = started.elapsed().as_micros() as u64;
+        text = scrubbed;
+        match timings.iter_mut().find(|t| t.category == rule.category) {
+            Some(t) => {
+                t.rules += 1;
+                t.micros += micros;
+                t.matches += hits;
+            }
+            None => timings.push(RuleTiming { category: rule.category, rules: 1, micros, matches: hits }),
+        }
+    }
+    timings
+}
+
+/// Report what scrubbing would redact from conversation JSON, without modifying it.
+/// Run over already-scrubbed content this counts residual PII the rules still find.
+pub fn scrub_report_json(json_content: &str) -> Result<ScrubReport, String> {
+    let conversation: Value = serde_json::from_str(json_content)
+        .map_err(|e| format!("Failed to parse JSON: {}", e))?;
+    Ok(report_value(&conversation))
+}
+
+fn report_value(value: &Value) -> ScrubReport {
+    let mut report = ScrubReport::default();
+    match value {
+        Value::Object(map) => map.values().for_each(|v| report.merge(report_value(v))),
+        Value::Array(arr) => arr.iter().for_each(|v| report.merge(report_value(v))),
+        Value::String(s) => report = scrub_text_with_report(s, &ScrubOptions::default()).1,
+        _ => {}
+    }
+    report
+}
+
+// -------- self-test --------
+
+/// Outcome of running one category's rules against its canonical example
+#[derive(Debug, Clone, Serialize)]
+pub struct CategorySelfTest {
+    pub category: Category,
+    pub example: &'static str,
+    pub scrubbed: String,
+    pub passed: bool,
+}
+
+/// A known positive example each category must redact
+fn canonical_example(category: Category) -> &'static str {
+    match category {
+        Category::AuthHeader => "Authorization: Bearer abc123secrettoken",
+        Category::Ssn => "SSN 123-45-6789",
+        Category::NationalId => "NINo AB123456C",
+        Category::DriversLicense => "license D1234567",
+        Category::Passport => "passport X12345678",
+        Category::EmployeeId => "badge EMP123456",
+        Category::Vin => "VIN 1HGCM82633A004352",
+        Category::LicensePlate => "license plate 7ABC123",
+        Category::Phone => "call 555-123-4567",
+        Category::Email => "mail jane.doe@example.com",
+        Category::Address => "lives at 123 Main Street",
+        Category::SocialHandle => "follow @jdoe",
+        Category::CreditCard => "card 4111 1111 1111 1111",
+        Category::BankAccount => "routing 026009593",
+        Category::TaxId => "EIN 12-3456789",
+        Category::MedicalRecord => "chart MRN1234567",
+        Category::Insurance => "group ABC123456",
+        Category::IcdCode => "diagnosis E11.9",
+        Category::Date => "seen on 2023-01-15",
+        Category::DateOfBirth => "DOB: 2 Jan 1990",
+        Category::Age => "she is 42 years old",
+        Category::IpAddress => "host 192.168.1.1",
+        Category::MacAddress => "nic 00:1A:2B:3C:4D:5E",
+        Category::Url => "see https://example.com/profile",
+        Category::DeviceId => "serial SN12345678AB",
+        Category::Name => "my name is Jane",
+    }
+}
+
+/// Run every built-in category against its canonical example. A category passes when
+/// its own rules redact the example, so a broken or shadowed pattern shows up as a failure.
+pub fn scrub_self_test() -> Vec<CategorySelfTest> {
+    let mut categories: Vec<Category> = Vec::new();
+    for rule in LOCALE_RULES.iter().map(|(_, rule)| rule).chain(RULES) {
+        if !categories.contains(&rule.category) {
+            categories.push(rule.category);
+        }
+    }
+    categories
+        .into_iter()
+        .map(|category| {
+            let example = canonical_example(category);
+            let (scrubbed, report) = scrub_text_with_locales(example, ALL_LOCALES, &ScrubOptions::default());
+            CategorySelfTest {
+                category,
+                example,
+                passed: report.counts.get(&category).copied().unwrap_or(0) > 0 && scrubbed != example,
+                scrubbed,
+            }
+        })
+        .collect()
+}
+
+// -------- custom pattern testing --------
+
+/// One match of a pattern under test (byte offsets into the sample)
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternMatch {
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+/// What a custom pattern would do to a sample
+#[derive(Debug, Clone, Serialize)]
+pub struct PatternTestResult {
+    pub matches: Vec<PatternMatch>,
+    pub result: String,
+}
+
+/// Compile a user-supplied pattern with a cap on compiled size
+fn compile_custom_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    regex::RegexBuilder::new(pattern).size_limit(1 << 20).build()
+}
+
+/// `compile_custom_pattern`, cached so repeated scrubs don't recompile user rules
+fn custom_regex(pattern: &str) -> Result<Regex, regex::Error> {
+    static CACHE: OnceLock<Mutex<HashMap<String, Regex>>> = OnceLock::new();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(regex) = cache.lock().unwrap().get(pattern) {
+        return Ok(regex.clone());
+    }
+    let regex = compile_custom_pattern(pattern)?;
+    cache.lock().unwrap().insert(pattern.to_string(), regex.clone());
+    Ok(regex)
+}
+
+/// Compile `pattern` and apply it to `sample`, replacing matches with `replacement`
+/// (which may reference groups as `$1` / `${name}`). Compile errors are returned as text.
+pub fn test_custom_pattern(pattern: &str, replacement: &str, sample: &str) -> Result<PatternTestResult, String> {
+    let regex = compile_custom_pattern(pattern).map_err(|e| format!("Invalid pattern: {}", e))?;
+    let matches = regex
+        .find_iter(sample)
+        .map(|m| PatternMatch { start: m.start(), end: m.end(), text: m.as_str().to_string() })
+        .collect();
+    let result = regex.replace_all(sample, replacement).to_string();
+    Ok(PatternTestResult { matches, result })
+}
+
+/// Validate the VIN check digit (position 9) per the North American standard
+fn is_valid_vin_check_digit(vin: &str) -> bool {
+    const WEIGHTS: [u32; 17] = [8, 7, 6, 5, 4, 3, 2, 10, 0, 9, 8, 7, 6, 5, 4, 3, 2];
+    let transliterate = |c: char| -> Option<u32> {
+        match c.to_ascii_uppercase() {
+            '0'..='9' => c.to_digit(10),
+            'A' | 'J' => Some(1),
+            'B' | 'K' | 'S' => Some(2),
+            'C' | 'L' | 'T' => Some(3),
+            'D' | 'M' | 'U' => Some(4),
+            'E' | 'N' | 'V' => Some(5),
+            'F' | 'W' => Some(6),
+            'G' | 'P' | 'X' => Some(7),
+            'H' | 'Y' => Some(8),
+            'R' | 'Z' => Some(9),
+            _ => None,
+        }
+    };
+    let chars: Vec<char> = vin.chars().collect();
+    if chars.len() != 17 {
+        return false;
+    }
+    let mut sum = 0;
+    for (c, weight) in chars.iter().zip(WEIGHTS.iter()) {
+        match transliterate(*c) {
+            Some(v) => sum += v * weight,
+            None => return false,
+        }
+    }
+    let expected = match sum % 11 {
+        10 => 'X',
+        n => char::from_digit(n, 10).unwrap(),
+    };
+    chars[8].to_ascii_uppercase() == expected
 }
 
 #[cfg(test)]
@@ -293,39 +1279,516 @@ mod tests {
     fn test_ssn_scrubbing() {
         let input = "My SSN is 123-45-6789";
         let expected = "My SSN is BLOCKED";
-        assert_eq!(scrub_text_string(input), expected);
+        assert_eq!(scrub_text_string(input, &ScrubOptions::default()), expected);
     }
     
     #[test]
     fn test_phone_scrubbing() {
         let input = "Call me at 555-123-4567";
         let expected = "Call me at BLOCKED";
-        assert_eq!(scrub_text_string(input), expected);
+        assert_eq!(scrub_text_string(input, &ScrubOptions::default()), expected);
     }
     
     #[test]
     fn test_email_scrubbing() {
         let input = "Email me at john@example.com";
         let expected = "Email me at BLOCKED";
-        assert_eq!(scrub_text_string(input), expected);
+        assert_eq!(scrub_text_string(input, &ScrubOptions::default()), expected);
     }
     
     #[test]
     fn test_name_scrubbing() {
         let input1 = "My name is John Smith";
         let expected1 = "My name is BLOCKED";
-        assert_eq!(scrub_text_string(input1), expected1);
+        assert_eq!(scrub_text_string(input1, &ScrubOptions::default()), expected1);
         
         let input2 = "I am Nadav Shannon";
         let expected2 = "I am BLOCKED";
-        assert_eq!(scrub_text_string(input2), expected2);
+        assert_eq!(scrub_text_string(input2, &ScrubOptions::default()), expected2);
         
         let input3 = "Nice to meet you, Nadav";
         let expected3 = "Nice to meet you, BLOCKED";
-        assert_eq!(scrub_text_string(input3), expected3);
+        assert_eq!(scrub_text_string(input3, &ScrubOptions::default()), expected3);
         
         let input4 = "Standalone Name Here";
         let expected4 = "BLOCKED";
-        assert_eq!(scrub_text_string(input4), expected4);
+        assert_eq!(scrub_text_string(input4, &ScrubOptions::default()), expected4);
+    }
+    
+    #[test]
+    fn test_vin_scrubbing() {
+        // Valid check digit: blocked even without a cue word
+        assert_eq!(scrub_text_string("Car 1M8GDM9AXKP042788 was sold", &ScrubOptions::default()), "Car BLOCKED was sold");
+        // Contains I/O/Q so it can't be a VIN
+        let not_vin = "Token QOI4X7Z2B9KQ1O8AB here";
+        assert_eq!(scrub_text_string(not_vin, &ScrubOptions::default()), not_vin);
+    }
+    
+    #[test]
+    fn test_license_plate_scrubbing() {
+        assert_eq!(scrub_text_string("license plate is 7ABC123", &ScrubOptions::default()), "license plate is BLOCKED");
+        // No cue word: left alone
+        assert_eq!(scrub_text_string("room 7ABC", &ScrubOptions::default()), "room 7ABC");
+    }
+    
+    #[test]
+    fn test_scrub_report_counts_categories() {
+        let report = scrub_report_json(r#"{"a":"Email john@example.com or jane@example.com","b":["SSN 123-45-6789"]}"#).unwrap();
+        assert_eq!(report.counts[&Category::Email], 2);
+        assert_eq!(report.counts[&Category::Ssn], 1);
+        assert_eq!(report.total, 3);
+    }
+    
+    #[test]
+    fn test_auth_header_scrubbing() {
+        assert_eq!(scrub_text_string("Authorization: Bearer abc.def.ghi", &ScrubOptions::default()), "Authorization: Bearer BLOCKED");
+        assert_eq!(
+            scrub_text_string(r#"curl -H "X-Api-Key: sk_live_123" https"#, &ScrubOptions::default()),
+            r#"curl -H "X-Api-Key: BLOCKED" https"#
+        );
+    }
+
+    #[test]
+    fn test_all_categories_pass_self_test() {
+        let results = scrub_self_test();
+        assert_eq!(results.len(), 26);
+        let failed: Vec<_> = results.iter().filter(|r| !r.passed).collect();
+        assert!(failed.is_empty(), "self-test failures: {:?}", failed);
+    }
+
+    #[test]
+    fn test_large_input_reuses_compiled_patterns() {
+        let compiled: &'static HashMap<_, _> = compiled_rules();
+        let regexes: Vec<*const Regex> = RULES.iter().map(|rule| regex_for(rule) as *const Regex).collect();
+        assert!(compiled.len() <= LOCALE_RULES.len() + RULES.len(), "{} patterns", compiled.len());
+
+        let line = "Meeting notes: mail jane@example.com or call 555-123-4567 about the release.\n";
+        let text = line.repeat(1024 * 1024 / line.len());
+        for _ in 0..2 {
+            let scrubbed = scrub_text_string(&text, &ScrubOptions::default());
+            assert!(!scrubbed.contains("jane@example.com"));
+        }
+        assert!(std::ptr::eq(compiled_rules(), compiled), "patterns must be compiled once");
+        assert!(RULES.iter().zip(&regexes).all(|(rule, regex)| std::ptr::eq(regex_for(rule), *regex)));
+    }
+
+    #[test]
+    fn test_default_replacement_token() {
+        let json = r#"{"content":"mail jane@example.com"}"#.to_string();
+        assert_eq!(ScrubOptions::default().replacement, "BLOCKED");
+        assert!(scrub_conversation_json(json.clone()).unwrap().contains("mail BLOCKED"));
+    }
+
+    #[test]
+    fn test_custom_replacement_token() {
+        let options = ScrubOptions { replacement: "[REDACTED]".to_string(), ..ScrubOptions::default() };
+        let json = r#"{"content":"my name is Jane, mail jane@example.com"}"#.to_string();
+        let scrubbed = scrub_conversation_json_with_options(json, &options).unwrap();
+        assert!(scrubbed.contains("my name is [REDACTED], mail [REDACTED]"), "{}", scrubbed);
+        assert!(!scrubbed.contains("BLOCKED"));
+
+        let mut options: ScrubOptions = serde_json::from_str(r#"{"replacement":"***","category_replacements":{"email":"[EMAIL]"}}"#).unwrap();
+        assert_eq!(scrub_text_string("mail jane@example.com, SSN 123-45-6789", &options), "mail [EMAIL], SSN ***");
+        options.category_replacements.clear();
+        assert_eq!(scrub_text_string("Authorization: Bearer abc", &options), "Authorization: Bearer ***");
+    }
+
+    #[test]
+    fn test_disabled_category_is_skipped() {
+        let options = ScrubOptions {
+            categories: ScrubCategories { dates: false, ..ScrubCategories::default() },
+            ..ScrubOptions::default()
+        };
+        let json = r#"{"content":"released 2023-01-15, mail jane@example.com"}"#.to_string();
+        let scrubbed = scrub_conversation_json_with_options(json, &options).unwrap();
+        assert!(scrubbed.contains("released 2023-01-15, mail BLOCKED"), "{}", scrubbed);
+
+        let options: ScrubOptions = serde_json::from_str(r#"{"categories":{"email":false}}"#).unwrap();
+        assert!(options.categories.dates);
+        assert_eq!(scrub_text_string("mail jane@example.com on 2023-01-15", &options), "mail jane@example.com on BLOCKED");
+    }
+
+    #[test]
+    fn test_custom_pattern_scrubs_project_codes() {
+        let options = ScrubOptions {
+            custom_patterns: vec![(r"\bPRJ-\d{6}\b".to_string(), "PRJ-BLOCKED".to_string())],
+            ..ScrubOptions::default()
+        };
+        let json = r#"{"content":"PRJ-000123 owner jane@example.com"}"#.to_string();
+        let (scrubbed, report) = scrub_conversation_json_with_report(json, &options).unwrap();
+        assert!(scrubbed.contains("PRJ-BLOCKED owner BLOCKED"), "{}", scrubbed);
+        assert_eq!((report.custom, report.total), (1, 2));
+    }
+
+    #[test]
+    fn test_invalid_custom_pattern_is_an_error() {
+        let options = ScrubOptions { custom_patterns: vec![("PRJ-(".to_string(), "x".to_string())], ..ScrubOptions::default() };
+        let err = scrub_conversation_json_with_options(r#"{"content":"hi"}"#.to_string(), &options).unwrap_err();
+        assert!(err.starts_with("Invalid custom pattern 'PRJ-('"), "{}", err);
+    }
+
+    #[test]
+    fn test_custom_patterns_run_after_builtins() {
+        // The built-in email rule has already replaced the address, so the custom rule sees BLOCKED
+        let options = ScrubOptions {
+            custom_patterns: vec![(r"BLOCKED@corp".to_string(), "never".to_string()), (r"mail BLOCKED".to_string(), "mail [email]".to_string())],
+            ..ScrubOptions::default()
+        };
+        assert_eq!(scrub_text_string("mail jane@corp.example.com", &options), "mail [email]");
+    }
+
+    #[test]
+    fn test_ipv4_octets_validated() {
+        let options = ScrubOptions::default();
+        assert_eq!(scrub_text_string("192.168.1.1", &options), "BLOCKED");
+        assert_eq!(scrub_text_string("from 8.8.8.8, 1.1.1.1", &options), "from BLOCKED, BLOCKED");
+        assert_eq!(scrub_text_string("build 300.1.2.3", &options), "build 300.1.2.3");
+        assert_eq!(scrub_text_string("oid 1.3.6.1.4.1", &options), "oid 1.3.6.1.4.1");
+        assert_eq!(scrub_text_string("tag v1.2.3.4", &options), "tag v1.2.3.4");
+        assert_eq!(scrub_text_string("version 4.10.22.3 release", &options), "version BLOCKED release");
+    }
+
+    #[test]
+    fn test_ipv4_context_gating() {
+        let options = ScrubOptions { ip_requires_context: true, ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("192.168.1.1", &options), "BLOCKED");
+        assert_eq!(scrub_text_string("version 4.10.22.3 release", &options), "version 4.10.22.3 release");
+        assert_eq!(scrub_text_string("server IP is 8.8.8.8", &options), "server IP is BLOCKED");
+        assert_eq!(scrub_text_string("IP address: 93.184.216.34", &options), "IP address: BLOCKED");
+        assert_eq!(scrub_text_string("listening on 93.184.216.34:443", &options), "listening on BLOCKED");
+        assert_eq!(scrub_text_string("route 93.184.216.0/24", &options), "route BLOCKED");
+
+        let options: ScrubOptions = serde_json::from_str(r#"{"ip_requires_context":true}"#).unwrap();
+        assert!(options.ip_requires_context);
+    }
+
+    #[test]
+    fn test_gates_switch_off_only_the_cueless_rules() {
+        assert!(!RULES.iter().any(|rule| ScrubOptions::default().skips(rule)));
+        let options = ScrubOptions { ip_requires_context: true, handles_require_platform: true, ..ScrubOptions::default() };
+        let skipped: Vec<Category> = RULES.iter().filter(|rule| options.skips(rule)).map(|rule| rule.category).collect();
+        assert_eq!(skipped, [Category::SocialHandle, Category::IpAddress]);
+    }
+
+    #[test]
+    fn test_handles_spare_mentions_annotations_and_emails() {
+        let options = ScrubOptions::default();
+        assert_eq!(scrub_text_string("follow @jdoe_42 for updates", &options), "follow BLOCKED for updates");
+        assert_eq!(scrub_text_string("@jdoe", &options), "BLOCKED");
+        let code = "class Foo {\n    @Override\n    public String toString() { return \"x\"; }\n}";
+        assert_eq!(scrub_text_string(code, &options), code);
+        assert_eq!(scrub_text_string("@here standup in 5, ping @channel", &options), "@here standup in 5, ping @channel");
+        assert_eq!(scrub_text_string("@app.route(\"/\") and @pytest(", &options), "@app.route(\"/\") and @pytest(");
+
+        // The email rule owns addresses; the handle rule never sees or re-counts them
+        let (scrubbed, report) = scrub_text_with_report("mail jane@example.com", &options);
+        assert_eq!(scrubbed, "mail BLOCKED");
+        assert_eq!(report.counts.get(&Category::Email), Some(&1));
+        assert_eq!(report.counts.get(&Category::SocialHandle), None);
+        let no_email = ScrubOptions { categories: ScrubCategories { email: false, ..ScrubCategories::default() }, ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("mail jane@example.com", &no_email), "mail jane@example.com");
+    }
+
+    #[test]
+    fn test_handles_require_platform_cue() {
+        let options = ScrubOptions { handles_require_platform: true, ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("thanks @jdoe", &options), "thanks @jdoe");
+        assert_eq!(scrub_text_string("on Twitter: @jdoe", &options), "on Twitter: BLOCKED");
+        assert_eq!(scrub_text_string("follow me at @jdoe", &options), "follow me at BLOCKED");
+    }
+
+    #[test]
+    fn test_ip_and_handle_rules_use_the_configured_tokens() {
+        let options = ScrubOptions {
+            replacement: "***".to_string(),
+            category_replacements: BTreeMap::from([(Category::SocialHandle, "[HANDLE]".to_string())]),
+            ..ScrubOptions::default()
+        };
+        assert_eq!(scrub_text_string("server IP is 8.8.8.8", &options), "server IP is ***");
+        assert_eq!(scrub_text_string("listening on 10.0.0.5:8080", &options), "listening on ***");
+        assert_eq!(scrub_text_string("ping 93.184.216.34 now", &options), "ping *** now");
+        assert_eq!(scrub_text_string("follow @jdoe_42, thanks @amy", &options), "follow [HANDLE], thanks [HANDLE]");
+    }
+
+    #[test]
+    fn test_allowlisted_terms_survive() {
+        assert_eq!(scrub_text_string("this is Acme Corp", &ScrubOptions::default()), "this is BLOCKED");
+
+        let options = ScrubOptions { allowlist: vec!["acme corp".to_string()], ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("this is Acme Corp", &options), "this is Acme Corp");
+        assert_eq!(
+            scrub_text_string("my name is Jane, I work at ACME CORP, mail jane@example.com", &options),
+            "my name is BLOCKED, I work at ACME CORP, mail BLOCKED"
+        );
+
+        let options: ScrubOptions = serde_json::from_str(r#"{"allowlist":["Widget", "Acme", ""]}"#).unwrap();
+        assert_eq!(scrub_text_string("Dr. Widget", &options), "Dr. Widget");
+    }
+
+    #[test]
+    fn test_allowlist_never_hides_pii_inside_a_longer_match() {
+        let options = ScrubOptions { allowlist: vec!["Acme".to_string(), "Ann".to_string()], ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("mail bob@acme.com", &options), "mail BLOCKED");
+        assert_eq!(scrub_text_string("this is Annabelle Smith", &options), "this is BLOCKED");
+        assert_eq!(scrub_text_string("this is Ann", &options), "this is Ann");
+
+        let options = ScrubOptions {
+            allowlist: vec!["ACCT-0001".to_string()],
+            custom_patterns: vec![(r"ACCT-\d+".to_string(), "[ACCOUNT]".to_string())],
+            ..ScrubOptions::default()
+        };
+        let (scrubbed, report) = scrub_text_with_report("acct-0001 and ACCT-0002", &options);
+        assert_eq!(scrubbed, "acct-0001 and [ACCOUNT]");
+        assert_eq!(report.custom, 1);
+    }
+
+    #[test]
+    fn test_ndjson_scrubbed_line_by_line() {
+        let content = "{\"content\":\"mail jane@example.com\",\"role\":\"user\"}\n\n{\"content\":\"noted\",\"role\":\"assistant\"}\n{\"content\":\"SSN 123-45-6789\"}\n";
+        let scrubbed = scrub_ndjson(content.to_string()).unwrap();
+        let lines: Vec<&str> = scrubbed.lines().collect();
+        assert_eq!(lines, [
+            r#"{"content":"mail BLOCKED","role":"user"}"#,
+            r#"{"content":"noted","role":"assistant"}"#,
+            r#"{"content":"SSN BLOCKED"}"#,
+        ]);
+    }
+
+    #[test]
+    fn test_ndjson_reports_malformed_line() {
+        let content = "{\"content\":\"hi\"}\n{\"content\": oops}\n{\"content\":\"bye\"}";
+        let err = scrub_ndjson(content.to_string()).unwrap_err();
+        assert!(err.starts_with("Failed to parse JSON on line 2:"), "{}", err);
+    }
+
+    #[test]
+    fn test_last_four_masking() {
+        let options = ScrubOptions { mask_style: MaskStyle::LastFour, ..ScrubOptions::default() };
+        assert_eq!(scrub_text_string("SSN 123-45-6789", &options), "SSN ***-**-6789");
+        assert_eq!(scrub_text_string("card 4111-1111-1111-1111", &options), "card ****-****-****-1111");
+        assert_eq!(scrub_text_string("call 555-123-4567", &options), "call ***-***-4567");
+        // Non-numeric categories are still fully blocked
+        assert_eq!(scrub_text_string("mail jane@example.com", &options), "mail BLOCKED");
+        assert_eq!(scrub_text_string("SSN 123-45-6789", &ScrubOptions::default()), "SSN BLOCKED");
+    }
+
+    #[test]
+    fn test_base64_payloads_scrubbed_when_enabled() {
+        let engine = base64::engine::general_purpose::STANDARD;
+        let blob = engine.encode(r#"{"from":"jane@example.com"}"#);
+        let json = serde_json::json!({ "attachment": blob, "note": "plain words here" }).to_string();
+
+        let untouched = scrub_conversation_json(json.clone()).unwrap();
+        assert!(untouched.contains(&blob), "off by default");
+
+        let options = ScrubOptions { scrub_encoded: true, ..ScrubOptions::default() };
+        let (scrubbed, report) = scrub_conversation_json_with_report(json, &options).unwrap();
+        let value: Value = serde_json::from_str(&scrubbed).unwrap();
+        let decoded = String::from_utf8(engine.decode(value["attachment"].as_str().unwrap()).unwrap()).unwrap();
+        assert_eq!(decoded, r#"{"from":"BLOCKED"}"#);
+        assert_eq!(report.counts[&Category::Email], 1);
+        assert_eq!(value["note"], "plain words here");
+    }
+
+    #[test]
+    fn test_non_base64_string_left_alone() {
+        let options = ScrubOptions { scrub_encoded: true, ..ScrubOptions::default() };
+        // Valid base64 alphabet and length, but decodes to bytes that aren't text
+        assert_eq!(scrub_string_value("AAECAwQFBgc=", &options).0, "AAECAwQFBgc=");
+        assert_eq!(scrub_string_value("just a sentence", &options).0, "just a sentence");
+    }
+
+    #[test]
+    fn test_pseudonyms_are_stable_and_persisted() {
+        let path = std::env::temp_dir().join(format!("arkangel-pseudonyms-{}.map", uuid::Uuid::new_v4()));
+        let key = [3u8; 32];
+        let json = r#"[{"content":"mail jane@example.com"},{"content":"my name is Jane, again jane@example.com"}]"#;
+
+        let first: Value = serde_json::from_str(&scrub_pseudonymous_with_key(json.to_string(), &path, key).unwrap()).unwrap();
+        let token = first[0]["content"].as_str().unwrap().trim_start_matches("mail ").to_string();
+        assert!(token.starts_with("EMAIL_"), "{}", token);
+        let second_turn = first[1]["content"].as_str().unwrap();
+        assert!(second_turn.starts_with("my name is PERSON_"), "{}", second_turn);
+        assert!(second_turn.ends_with(&format!(", again {}", token)), "same email, same token");
+
+        // A later run with the same mapping reuses the token
+        let second = scrub_pseudonymous_with_key(r#"{"content":"jane@example.com"}"#.to_string(), &path, key).unwrap();
+        assert!(second.contains(&token));
+
+        let map = PseudonymMap::load(&path, key).unwrap();
+        assert_eq!(map.entries()[&token].original, "jane@example.com");
+        assert!(map.entries().values().any(|e| e.category == Category::Name && e.original == "Jane"));
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[test]
+    fn test_pseudonyms_record_exactly_the_redacted_value() {
+        let map = Arc::new(Mutex::new(PseudonymMap::new([5u8; 32])));
+        let options = ScrubOptions { pseudonyms: Some(SharedPseudonyms(map.clone())), ..ScrubOptions::default() };
+        let scrubbed = scrub_text_string("my mom Mya; follow on twitter @jane_doe; Authorization: Bearer tok123", &options);
+        assert!(scrubbed.starts_with("my family member PERSON_"), "{}", scrubbed);
+        assert!(scrubbed.contains("Authorization: Bearer ID_"), "{}", scrubbed);
+
+        let map = map.lock().unwrap();
+        let originals: Vec<&str> = map.entries().values().map(|e| e.original.as_str()).collect();
+        for expected in ["Mya", "@jane_doe", "tok123"] {
+            assert!(originals.contains(&expected), "{:?} missing from {:?}", expected, originals);
+        }
+    }
+
+    #[test]
+    fn test_options_compare_by_value_and_shared_map() {
+        assert_eq!(ScrubOptions::default(), ScrubOptions::default());
+        let custom = ScrubOptions { replacement: "[X]".to_string(), ..ScrubOptions::default() };
+        assert_ne!(custom, ScrubOptions::default());
+
+        let map = SharedPseudonyms(Arc::new(Mutex::new(PseudonymMap::new([1u8; 32]))));
+        let with_map = ScrubOptions { pseudonyms: Some(map.clone()), ..ScrubOptions::default() };
+        assert_eq!(with_map, with_map.clone());
+        let other_map = SharedPseudonyms(Arc::new(Mutex::new(PseudonymMap::new([1u8; 32]))));
+        assert_ne!(with_map, ScrubOptions { pseudonyms: Some(other_map), ..ScrubOptions::default() });
+    }
+
+    #[test]
+    fn test_dob_phrasings_reported_as_date_of_birth() {
+        for (input, expected) in [
+            ("DOB: 01/02/1990", "DOB: BLOCKED"),
+            ("date of birth 1990-01-02", "date of birth BLOCKED"),
+            ("Date of Birth: 2 Jan 1990", "Date of Birth: BLOCKED"),
+            ("born on March 3rd, 1985", "born on BLOCKED"),
+            ("d.o.b. 14.07.88", "d.o.b. BLOCKED"),
+        ] {
+            let (scrubbed, report) = scrub_text_with_locales(input, &[], &ScrubOptions::default());
+            assert_eq!(scrubbed, expected, "input: {}", input);
+            assert_eq!(report.counts.get(&Category::DateOfBirth), Some(&1), "input: {}", input);
+            assert_eq!(report.counts.get(&Category::Date), None, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn test_scrub_only_selected_categories() {
+        let (scrubbed, report) = scrub_text_categories("mail jane@example.com or call 555-123-4567", &[Category::Email], &ScrubOptions::default());
+        assert_eq!(scrubbed, "mail BLOCKED or call 555-123-4567");
+        assert_eq!(report.total, 1);
+
+        let options = ScrubOptions {
+            category_replacements: BTreeMap::from([(Category::Email, "[EMAIL]".to_string())]),
+            allowlist: vec!["support@example.com".to_string()],
+            ..ScrubOptions::default()
+        };
+        let (scrubbed, _) = scrub_text_categories("mail jane@example.com or support@example.com", &[Category::Email], &options);
+        assert_eq!(scrubbed, "mail [EMAIL] or support@example.com", "the caller's options apply");
+    }
+
+    #[test]
+    fn test_custom_token_leaves_existing_blocked_text_alone() {
+        let options = ScrubOptions { replacement: "[REDACTED]".to_string(), ..ScrubOptions::default() };
+        assert_eq!(
+            scrub_text_string("Status BLOCKED until 555-123-4567 calls back", &options),
+            "Status BLOCKED until [REDACTED] calls back"
+        );
+        assert_eq!(scrub_text_string("Authorization: Bearer BLOCKEDtoken", &options), "Authorization: Bearer [REDACTED]");
+        assert_eq!(scrub_text_string("follow on twitter @BLOCKED_fan", &options), "follow on twitter [REDACTED]");
+    }
+
+    #[test]
+    fn test_routing_numbers_require_aba_checksum() {
+        assert!(is_aba_routing("021000021"));
+        assert!(is_aba_routing("011000015"));
+        assert!(!is_aba_routing("123456789"));
+        assert!(!is_aba_routing("02100002"));
+
+        let options = ScrubOptions::default();
+        assert_eq!(scrub_text_string("order 123456789 shipped", &options), "order 123456789 shipped");
+        assert_eq!(scrub_text_string("wire to routing 026009593 today", &options), "wire to routing BLOCKED today");
+    }
+
+    #[test]
+    fn test_uk_nino_scrubbing() {
+        let uk = [Locale::Uk];
+        assert_eq!(scrub_text_with_locales("NI number AB123456C", &uk, &ScrubOptions::default()).0, "NI number BLOCKED");
+        assert_eq!(scrub_text_with_locales("NI number AB 12 34 56 C", &uk, &ScrubOptions::default()).0, "NI number BLOCKED");
+        // Unassigned prefix, and locale not enabled
+        assert_eq!(scrub_text_with_locales("ref GB123456A", &uk, &ScrubOptions::default()).0, "ref GB123456A");
+        assert_eq!(scrub_text_with_locales("NI number AB123456C", &[], &ScrubOptions::default()).0, "NI number AB123456C");
+    }
+
+    #[test]
+    fn test_canadian_sin_scrubbing() {
+        let ca = [Locale::Ca];
+        assert_eq!(scrub_text_with_locales("SIN 046 454 286", &ca, &ScrubOptions::default()).0, "SIN BLOCKED");
+        // Fails the Luhn check
+        assert_eq!(scrub_text_with_locales("SIN 046 454 287", &ca, &ScrubOptions::default()).0, "SIN 046 454 287");
+        assert_eq!(scrub_text_with_locales("SIN 046 454 286", &[], &ScrubOptions::default()).0, "SIN 046 454 286");
+    }
+
+    #[test]
+    fn test_parallel_scrub_matches_sequential() {
+        let turns: Vec<Value> = (0..10)
+            .map(|i| serde_json::json!({
+                "role": if i % 2 == 0 { "user" } else { "assistant" },
+                "content": format!("turn {} from user{}@example.com, call 555-123-{:04}", i, i, i),
+                "meta": { "ip": "10.0.0.1", "n": i }
+            }))
+            .collect();
+        let conversation = Value::Array(turns);
+
+        let mut sequential = conversation.clone();
+        let mut sequential_report = ScrubReport::default();
+        scrub_value_with_report(&mut sequential, &ScrubOptions::default(), &mut sequential_report);
+
+        let mut parallel = conversation;
+        let parallel_report = scrub_value_parallel(&mut parallel, &ScrubOptions::default());
+
+        assert_eq!(parallel, sequential);
+        assert_eq!(parallel_report, sequential_report);
+        assert_eq!(parallel_report.counts[&Category::Email], 10);
+    }
+
+    #[test]
+    fn test_custom_pattern_valid() {
+        let result = test_custom_pattern(r"ACCT-(\d+)", "ACCT-BLOCKED", "ACCT-1234 and ACCT-98").unwrap();
+        assert_eq!(result.matches.len(), 2);
+        assert_eq!((result.matches[0].start, result.matches[0].end), (0, 9));
+        assert_eq!(result.matches[1].text, "ACCT-98");
+        assert_eq!(result.result, "ACCT-BLOCKED and ACCT-BLOCKED");
+    }
+
+    #[test]
+    fn test_custom_pattern_invalid() {
+        let err = test_custom_pattern(r"ACCT-(\d+", "BLOCKED", "ACCT-1234").unwrap_err();
+        assert!(err.starts_with("Invalid pattern"), "{}", err);
+    }
+
+    #[test]
+    fn test_rule_set_prefilter_matches_every_rule_pass() {
+        let mut text = String::new();
+        for i in 0..400 {
+            text.push_str(&format!("Line {} of the release notes covers build 4.{}.0 and the new parser.\n", i, i));
+            if i % 50 == 0 {
+                text.push_str(&format!("Ping user{}@example.com, SSN 123-45-6789, host 10.0.0.{}, follow @dev{}\n", i, i % 255, i));
+            }
+        }
+        let options = ScrubOptions::default();
+
+        let mut every_rule = text.clone();
+        for rule in active_rules(configured_locales()) {
+            every_rule = apply_rule(rule, &every_rule, &options).0;
+        }
+        let (prefiltered, report) = scrub_text_with_report(&text, &options);
+
+        assert_eq!(prefiltered, every_rule);
+        assert_eq!(report.counts[&Category::Email], 8);
+    }
+
+    #[test]
+    fn test_profile_scrub_times_every_category() {
+        let timings = profile_scrub("Email john@example.com, SSN 123-45-6789");
+        for rule in RULES {
+            assert!(timings.iter().any(|t| t.category == rule.category), "missing {:?}", rule.category);
+        }
+        let email = timings.iter().find(|t| t.category == Category::Email).unwrap();
+        assert_eq!(email.matches, 1);
+        assert_eq!(email.rules, RULES.iter().filter(|r| r.category == Category::Email).count());
     }
 }
@@ -0,0 +1,145 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::pii_scrubber::Category;
+use crate::sealed::{self, KeySource};
+
+/// Leading bytes of an encrypted mapping file
+const MAPPING_MAGIC: &[u8] = b"AKPM1";
+
+/// What a pseudonym token stands for
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PseudonymEntry {
+    pub category: Category,
+    pub original: String,
+}
+
+/// Token <-> original value map for reversible pseudonymization. Tokens are derived from
+/// a keyed hash, so the same value gets the same token across runs that share a key.
+#[derive(Debug, Default)]
+pub struct PseudonymMap {
+    key: [u8; 32],
+    tokens: BTreeMap<String, PseudonymEntry>,
+    by_original: HashMap<(Category, String), String>,
+}
+
+impl PseudonymMap {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key, ..Self::default() }
+    }
+
+    /// Stable token for `original`, e.g. "EMAIL_1a2b3c4d"
+    pub fn token_for(&mut self, category: Category, original: &str) -> String {
+        let lookup = (category, original.to_string());
+        if let Some(token) = self.by_original.get(&lookup) {
+            return token.clone();
+        }
+
+        let mut hasher = Sha256::new();
+        hasher.update(self.key);
+        hasher.update(format!("{:?}", category));
+        hasher.update(original.as_bytes());
+        let digest: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+
+        // Lengthen the suffix on the (unlikely) event of a collision
+        let mut len = 8;
+        let token = loop {
+            let candidate = format!("{}_{}", token_prefix(category), &digest[..len]);
+            match self.tokens.get(&candidate) {
+                Some(entry) if entry.category != category || entry.original != original => len += 2,
+                _ => break candidate,
+            }
+        };
+        self.tokens.insert(token.clone(), PseudonymEntry { category, original: original.to_string() });
+        self.by_original.insert(lookup, token.clone());
+        token
+    }
+
+    pub fn entries(&self) -> &BTreeMap<String, PseudonymEntry> {
+        &self.tokens
+    }
+
+    /// Load an encrypted mapping file, or start empty if it doesn't exist yet
+    pub fn load(path: &Path, key: [u8; 32]) -> Result<Self> {
+        let mut map = Self::new(key);
+        if !path.exists() {
+            return Ok(map);
+        }
+        let bytes = fs::read(path).with_context(|| format!("reading {}", path.display()))?;
+        let plaintext = sealed::open(MAPPING_MAGIC, &key, &bytes)
+            .map_err(|e| anyhow!("cannot decrypt {} ({})", path.display(), e))?
+            .ok_or_else(|| anyhow!("{} is not a pseudonym mapping file", path.display()))?;
+        let tokens: BTreeMap<String, PseudonymEntry> = serde_json::from_slice(&plaintext)?;
+        for (token, entry) in &tokens {
+            map.by_original.insert((entry.category, entry.original.clone()), token.clone());
+        }
+        map.tokens = tokens;
+        Ok(map)
+    }
+
+    /// Encrypt and write the mapping, replacing `path` atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let plaintext = serde_json::to_vec(&self.tokens)?;
+        let out = sealed::seal(MAPPING_MAGIC, &self.key, &plaintext).context("encrypting pseudonym mapping")?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        sealed::write_private(path, &out)
+    }
+}
+
+fn token_prefix(category: Category) -> &'static str {
+    match category {
+        Category::Name => "PERSON",
+        Category::Email => "EMAIL",
+        Category::Phone => "PHONE",
+        Category::Address => "ADDRESS",
+        Category::Date | Category::DateOfBirth => "DATE",
+        Category::SocialHandle => "HANDLE",
+        Category::IpAddress | Category::MacAddress | Category::Url | Category::DeviceId => "DIGITAL",
+        _ => "ID",
+    }
+}
+
+/// The mapping key: `ARKANGEL_PSEUDONYM_KEY` (base64, 32 bytes) if set, otherwise a key
+/// generated on first use and kept in the OS keychain (~/.arkangel/pseudonym.key where
+/// there is none)
+pub fn pseudonym_key() -> Result<[u8; 32]> {
+    sealed::load_or_create_key(&KeySource {
+        env_var: "ARKANGEL_PSEUDONYM_KEY",
+        keychain_account: "pseudonym-key",
+        file: key_path()?,
+    })
+}
+
+fn key_path() -> Result<PathBuf> {
+    let home = dirs::home_dir().ok_or_else(|| anyhow!("Could not find home directory"))?;
+    Ok(home.join(".arkangel").join("pseudonym.key"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mapping_file_round_trips() {
+        let path = std::env::temp_dir().join(format!("arkangel-pseudonyms-{}.map", uuid::Uuid::new_v4()));
+        let key = [7u8; 32];
+        let mut map = PseudonymMap::new(key);
+        let token = map.token_for(Category::Email, "jane@example.com");
+        map.save(&path).unwrap();
+
+        let raw = fs::read(&path).unwrap();
+        assert!(!String::from_utf8_lossy(&raw).contains("jane@example.com"), "mapping must be encrypted");
+
+        let mut loaded = PseudonymMap::load(&path, key).unwrap();
+        assert_eq!(loaded.entries()[&token].original, "jane@example.com");
+        assert_eq!(loaded.token_for(Category::Email, "jane@example.com"), token);
+        assert!(PseudonymMap::load(&path, [8u8; 32]).is_err(), "wrong key is rejected");
+        fs::remove_file(&path).ok();
+    }
+}
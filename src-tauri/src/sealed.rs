@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const NONCE_LEN: usize = 12;
+/// Keychain service the keys are stored under (the app's bundle identifier)
+const KEYCHAIN_SERVICE: &str = "com.nadavshanun.arkangel";
+
+/// Encrypt `plaintext` into `magic` + nonce + ChaCha20-Poly1305 ciphertext
+pub fn seal(magic: &[u8], key: &[u8; 32], plaintext: &[u8]) -> Result<Vec<u8>> {
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow!("encryption failed"))?;
+    let mut out = magic.to_vec();
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&ciphertext);
+    Ok(out)
+}
+
+/// Decrypt what `seal` wrote, or None if `bytes` doesn't start with `magic`
+pub fn open(magic: &[u8], key: &[u8; 32], bytes: &[u8]) -> Result<Option<Vec<u8>>> {
+    let Some(body) = bytes.strip_prefix(magic) else {
+        return Ok(None);
+    };
+    if body.len() <= NONCE_LEN {
+        return Err(anyhow!("truncated"));
+    }
+    let (nonce, ciphertext) = body.split_at(NONCE_LEN);
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map(Some)
+        .map_err(|_| anyhow!("wrong key or corrupted file"))
+}
+
+/// Replace `path` atomically with an owner-only file holding `contents`
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    private_file(&tmp)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::rename(&tmp, path))
+        .map_err(|e| {
+            fs::remove_file(&tmp).ok();
+            anyhow!("writing {}: {}", path.display(), e)
+        })
+}
+
+/// Where a 32-byte key comes from
+pub struct KeySource<'a> {
+    /// Environment variable holding the key as base64; wins over everything else
+    pub env_var: &'a str,
+    /// Account name of the key's OS keychain entry
+    pub keychain_account: &'a str,
+    /// Key file, used only where there is no keychain. A key file left by an earlier
+    /// version is moved into the keychain.
+    pub file: PathBuf,
+}
+
+/// The key for `source`, generated and stored on first use
+pub fn load_or_create_key(source: &KeySource) -> Result<[u8; 32]> {
+    if let Ok(encoded) = std::env::var(source.env_var) {
+        return decode_key(&encoded).ok_or_else(|| anyhow!("{} must be 32 bytes of base64", source.env_var));
+    }
+    load_key(source, keyring::Entry::new(KEYCHAIN_SERVICE, source.keychain_account))
+}
+
+fn load_key(source: &KeySource, keychain: keyring::Result<keyring::Entry>) -> Result<[u8; 32]> {
+    let stored = keychain.and_then(|entry| match entry.get_password() {
+        Ok(encoded) => Ok((entry, Some(encoded))),
+        Err(keyring::Error::NoEntry) => Ok((entry, None)),
+        Err(e) => Err(e),
+    });
+    let entry = match stored {
+        Ok((_, Some(encoded))) => {
+            return decode_key(&encoded)
+                .ok_or_else(|| anyhow!("invalid key in keychain entry {}/{}", KEYCHAIN_SERVICE, source.keychain_account));
+        }
+        Ok((entry, None)) => entry,
+        // A locked keychain is an error, not a reason to write the key to disk
+        Err(e @ keyring::Error::NoStorageAccess(_)) => {
+            return Err(anyhow!("keychain is not accessible: {}", e));
+        }
+        Err(e) => {
+            println!("[keys] No OS keychain ({}); using {}", e, source.file.display());
+            return file_key(&source.file);
+        }
+    };
+
+    let legacy = source.file.exists();
+    let key = if legacy { file_key(&source.file)? } else { generate_key() };
+    if let Err(e) = entry.set_password(&encode_key(&key)) {
+        println!("[keys] Could not store key in keychain ({}); using {}", e, source.file.display());
+        return file_key(&source.file);
+    }
+    if legacy {
+        fs::remove_file(&source.file).ok();
+        println!("[keys] Moved {} into the keychain", source.file.display());
+    }
+    // Another process may have stored its key first; the last write is the one kept
+    match entry.get_password() {
+        Ok(stored) => decode_key(&stored).ok_or_else(|| anyhow!("invalid key in keychain")),
+        Err(_) => Ok(key),
+    }
+}
+
+/// The key in `path`, generating it there if the file doesn't exist yet
+fn file_key(path: &Path) -> Result<[u8; 32]> {
+    if let Ok(encoded) = fs::read_to_string(path) {
+        return decode_key(&encoded).ok_or_else(|| anyhow!("invalid key in {}", path.display()));
+    }
+    let key = generate_key();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if !create_key_file(path, encode_key(&key).as_bytes())? {
+        // Another process generated the key first; use theirs
+        return file_key(path);
+    }
+    println!("[keys] Generated key at {}", path.display());
+    Ok(key)
+}
+
+/// Write a new owner-only key file at `path`. The contents are written to a private temp
+/// file which is then hard-linked into place, so the key never exists with wider
+/// permissions or partly written. Returns false if `path` already exists.
+fn create_key_file(path: &Path, contents: &[u8]) -> Result<bool> {
+    let tmp = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+    let written = private_file(&tmp)
+        .and_then(|mut file| file.write_all(contents).and_then(|_| file.sync_all()))
+        .and_then(|_| fs::hard_link(&tmp, path));
+    fs::remove_file(&tmp).ok();
+    match written {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+        Err(e) => Err(e).with_context(|| format!("writing {}", path.display())),
+    }
+}
+
+/// Create `path`, which must not exist, readable and writable by the owner only
+fn private_file(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+fn generate_key() -> [u8; 32] {
+    ChaCha20Poly1305::generate_key(&mut OsRng).into()
+}
+
+fn encode_key(key: &[u8; 32]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(key)
+}
+
+fn decode_key(encoded: &str) -> Option<[u8; 32]> {
+    base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()
+        .and_then(|k| <[u8; 32]>::try_from(k).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use keyring::mock::MockCredential;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arkangel-keys-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn source(dir: &Path) -> KeySource<'static> {
+        KeySource { env_var: "ARKANGEL_TEST_UNSET_KEY", keychain_account: "test", file: dir.join("test.key") }
+    }
+
+    fn mock_keychain() -> keyring::Entry {
+        keyring::Entry::new_with_credential(Box::new(MockCredential::default()))
+    }
+
+    #[test]
+    fn test_seal_round_trips_and_rejects_wrong_key() {
+        let sealed = seal(b"AKT1", &[1u8; 32], b"secret").unwrap();
+        assert!(!String::from_utf8_lossy(&sealed).contains("secret"));
+        assert_eq!(open(b"AKT1", &[1u8; 32], &sealed).unwrap().unwrap(), b"secret");
+        assert!(open(b"AKT1", &[2u8; 32], &sealed).is_err());
+        assert!(open(b"AKT1", &[1u8; 32], b"AKT1short").is_err());
+        assert!(open(b"AKT1", &[1u8; 32], b"{\"plain\":true}").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_key_goes_to_the_keychain_not_disk() {
+        let dir = temp_dir();
+        let source = source(&dir);
+        let entry = mock_keychain();
+        let key = load_key(&source, Ok(entry)).unwrap();
+        assert!(!source.file.exists(), "no key file when a keychain is available");
+
+        // A key file from an earlier version is adopted and removed
+        let legacy = [9u8; 32];
+        fs::write(&source.file, encode_key(&legacy)).unwrap();
+        let entry = mock_keychain();
+        assert_eq!(load_key(&source, Ok(entry)).unwrap(), legacy);
+        assert!(!source.file.exists());
+        assert_ne!(key, legacy);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_file_fallback_only_without_a_keychain() {
+        let dir = temp_dir();
+        let source = source(&dir);
+        let no_keychain = || Err(keyring::Error::PlatformFailure("no secret service".into()));
+        let key = load_key(&source, no_keychain()).unwrap();
+        assert_eq!(load_key(&source, no_keychain()).unwrap(), key, "the fallback file is reused");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&source.file).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let locked = mock_keychain();
+        let mock: &MockCredential = locked.get_credential().downcast_ref().unwrap();
+        mock.set_error(keyring::Error::NoStorageAccess("locked".into()));
+        assert!(load_key(&source, Ok(locked)).is_err(), "a locked keychain doesn't fall back to the file");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_key_file_is_created_once_and_owner_only() {
+        let dir = temp_dir();
+        let path = dir.join("pseudonym.key");
+
+        assert!(create_key_file(&path, b"first").unwrap());
+        assert!(!create_key_file(&path, b"second").unwrap(), "an existing key is never replaced");
+        assert_eq!(fs::read(&path).unwrap(), b"first");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1, "no temp files are left behind");
+        fs::remove_dir_all(&dir).ok();
+    }
+}
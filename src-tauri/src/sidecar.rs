@@ -0,0 +1,277 @@
+use std::io::{self, BufRead, BufReader};
+use std::net::{Ipv4Addr, SocketAddr, TcpStream};
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// Port the Node agent listens on
+pub const AGENT_PORT: u16 = 8765;
+
+/// How long `sidecar_health` waits for the agent port to accept a connection
+pub const HEALTH_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// How long quitting waits for the sidecar to exit on its own before killing it
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Shared handle to the running sidecar; `None` once it has been shut down
+pub type SidecarSlot = Arc<Mutex<Option<Child>>>;
+
+/// How the supervisor relaunches a sidecar that exits on its own
+#[derive(Clone, Debug)]
+pub struct RestartPolicy {
+    /// Consecutive relaunches before giving up
+    pub max_restarts: u32,
+    /// Delay before the first relaunch; doubles with each consecutive one
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// A sidecar that stays up this long resets the consecutive count
+    pub stable_after: Duration,
+    /// How often the child is checked for exit
+    pub poll_interval: Duration,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(30),
+            stable_after: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
+
+/// Whether something accepts TCP connections on `port` on localhost within `timeout`
+pub fn agent_reachable(port: u16, timeout: Duration) -> bool {
+    TcpStream::connect_timeout(&SocketAddr::from((Ipv4Addr::LOCALHOST, port)), timeout).is_ok()
+}
+
+/// Sidecar files whose changes call for a reinstall/rebuild, besides everything under src/
+const BUILD_INPUTS: &[&str] = &["package.json", "package-lock.json", "tsconfig.json"];
+
+/// Whether the built script is missing or older than any of its sources
+pub fn needs_rebuild(source_mtimes: impl IntoIterator<Item = SystemTime>, dist_mtime: Option<SystemTime>) -> bool {
+    match dist_mtime {
+        None => true,
+        Some(built) => source_mtimes.into_iter().any(|modified| modified > built),
+    }
+}
+
+/// Whether `npm ci && npm run build` should run before spawning `script`. Forced by
+/// `ARKANGEL_FORCE_SIDECAR_BUILD=1`, and always true without node_modules.
+pub fn build_needed(sidecar_dir: &Path, script: &Path) -> bool {
+    if std::env::var("ARKANGEL_FORCE_SIDECAR_BUILD").is_ok_and(|v| v.trim() == "1") {
+        println!("[sidecar] ARKANGEL_FORCE_SIDECAR_BUILD is set; rebuilding");
+        return true;
+    }
+    if !sidecar_dir.join("node_modules").is_dir() {
+        return true;
+    }
+    let mtime = |path: &Path| path.metadata().and_then(|m| m.modified()).ok();
+    let sources = WalkDir::new(sidecar_dir.join("src"))
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| mtime(entry.path()))
+        .chain(BUILD_INPUTS.iter().filter_map(|name| mtime(&sidecar_dir.join(name))));
+    needs_rebuild(sources, mtime(script))
+}
+
+fn lock(slot: &SidecarSlot) -> MutexGuard<'_, Option<Child>> {
+    slot.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start `node <script>` in `cwd`, forwarding its output to our stdout/stderr
+pub fn spawn_sidecar(cwd: &Path, script: &Path) -> io::Result<Child> {
+    println!("[sidecar] Spawning Node...");
+    let mut child = Command::new("node")
+        .current_dir(cwd)
+        .arg(script)
+        .env("AGENT_PORT", AGENT_PORT.to_string())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    if let Some(stdout) = child.stdout.take() {
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                println!("[sidecar][stdout] {}", line);
+            }
+        });
+    }
+    if let Some(stderr) = child.stderr.take() {
+        thread::spawn(move || {
+            for line in BufReader::new(stderr).lines().map_while(Result::ok) {
+                eprintln!("[sidecar][stderr] {}", line);
+            }
+        });
+    }
+    Ok(child)
+}
+
+/// Watch the child in `slot` and relaunch it with `spawn` whenever it exits, backing off
+/// between attempts. Returns once the slot is emptied (shutdown) or the restarts run out.
+pub fn supervise<F>(slot: &SidecarSlot, policy: &RestartPolicy, mut spawn: F)
+where
+    F: FnMut() -> io::Result<Child>,
+{
+    let mut started = Instant::now();
+    let mut restarts = 0;
+    loop {
+        thread::sleep(policy.poll_interval);
+        let status = match lock(slot).as_mut() {
+            None => return,
+            Some(child) => match child.try_wait() {
+                Ok(Some(status)) => status,
+                Ok(None) => continue,
+                Err(e) => {
+                    eprintln!("[sidecar] Failed to check sidecar status: {}", e);
+                    continue;
+                }
+            },
+        };
+
+        if started.elapsed() >= policy.stable_after {
+            restarts = 0;
+        }
+        if restarts >= policy.max_restarts {
+            eprintln!("[sidecar] Exited ({}) after {} restart(s); giving up", status, restarts);
+            return;
+        }
+        let backoff = policy.initial_backoff.saturating_mul(1 << restarts.min(16)).min(policy.max_backoff);
+        restarts += 1;
+        eprintln!(
+            "[sidecar] Exited unexpectedly ({}); restarting in {:?} (attempt {}/{})",
+            status, backoff, restarts, policy.max_restarts
+        );
+        thread::sleep(backoff);
+
+        let mut guard = lock(slot);
+        if guard.is_none() {
+            return;
+        }
+        match spawn() {
+            Ok(child) => {
+                println!("[sidecar] Restarted (pid {})", child.id());
+                *guard = Some(child);
+                started = Instant::now();
+            }
+            // The exited child stays in the slot, so the next poll retries
+            Err(e) => eprintln!("[sidecar] Restart failed: {}", e),
+        }
+    }
+}
+
+/// Ask `child` to exit (SIGTERM on Unix) and give it up to `timeout` to do so before
+/// falling back to `kill()`. Returns true if it had to be killed.
+pub fn shutdown_child(mut child: Child, timeout: Duration) -> bool {
+    if let Ok(Some(_)) = child.try_wait() {
+        return false;
+    }
+    if request_exit(&child) {
+        let deadline = Instant::now() + timeout;
+        while Instant::now() < deadline {
+            match child.try_wait() {
+                Ok(Some(status)) => {
+                    println!("[sidecar] Exited cleanly ({})", status);
+                    return false;
+                }
+                Ok(None) => thread::sleep(Duration::from_millis(50)),
+                Err(_) => break,
+            }
+        }
+        eprintln!("[sidecar] Still running after {:?}; killing it", timeout);
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    true
+}
+
+/// Send SIGTERM; the sidecar closes its MCP sessions and exits on it
+#[cfg(unix)]
+fn request_exit(child: &Child) -> bool {
+    // SAFETY: kill(2) on the pid of a child we spawned and haven't reaped yet
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) == 0 }
+}
+
+/// No SIGTERM on Windows, so the caller goes straight to `kill()`
+#[cfg(not(unix))]
+fn request_exit(_child: &Child) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_needs_rebuild_compares_mtimes() {
+        let built = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        let older = built - Duration::from_secs(60);
+        let newer = built + Duration::from_secs(1);
+
+        assert!(!needs_rebuild([older, older], Some(built)));
+        assert!(!needs_rebuild([built], Some(built)), "same mtime counts as up to date");
+        assert!(needs_rebuild([older, newer], Some(built)));
+        assert!(needs_rebuild([older], None), "missing dist is always rebuilt");
+        assert!(!needs_rebuild([], Some(built)));
+    }
+
+    #[test]
+    fn test_agent_reachable_only_while_listening() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(agent_reachable(port, HEALTH_TIMEOUT));
+
+        drop(listener);
+        assert!(!agent_reachable(port, HEALTH_TIMEOUT));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_exited_child_is_relaunched() {
+        let mut spawns = 0;
+        let mut spawn = || {
+            spawns += 1;
+            Command::new("sh").args(["-c", "exit 3"]).spawn()
+        };
+        let slot: SidecarSlot = Arc::new(Mutex::new(Some(spawn().unwrap())));
+        let policy = RestartPolicy {
+            max_restarts: 2,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            stable_after: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(5),
+        };
+
+        supervise(&slot, &policy, &mut spawn);
+        assert_eq!(spawns, 3, "initial spawn plus two relaunches");
+        assert!(lock(&slot).as_mut().unwrap().try_wait().unwrap().is_some());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shutdown_kills_only_after_timeout() {
+        let timeout = Duration::from_millis(300);
+        let cooperative = Command::new("sleep").arg("5").spawn().unwrap();
+        let started = Instant::now();
+        assert!(!shutdown_child(cooperative, timeout), "SIGTERM is enough");
+        assert!(started.elapsed() < timeout);
+
+        // Ignores SIGTERM; wait for the trap to be installed before signalling
+        let mut stubborn = Command::new("sh")
+            .args(["-c", "trap '' TERM; echo ready; exec sleep 5"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        let mut line = String::new();
+        BufReader::new(stubborn.stdout.take().unwrap()).read_line(&mut line).unwrap();
+        let started = Instant::now();
+        assert!(shutdown_child(stubborn, timeout), "falls back to kill()");
+        assert!(started.elapsed() >= timeout);
+    }
+}
@@ -0,0 +1,380 @@
+use anyhow::{anyhow, Result};
+#[cfg(feature = "sqlite")]
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use std::time::Duration;
+
+use crate::file_storage::FileInfo;
+
+/// Where the uploads index is kept, from `[uploads] index_backend` in config.toml
+#[derive(Debug, Default, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexBackend {
+    /// uploads/index.json, rewritten on every change
+    #[default]
+    Json,
+    /// uploads/index.db, one row per file; needs the `sqlite` feature
+    Sqlite,
+}
+
+pub const JSON_INDEX_NAME: &str = "index.json";
+pub const SQLITE_INDEX_NAME: &str = "index.db";
+
+/// An index.json that has been imported into index.db is kept under this name
+const MIGRATED_JSON_NAME: &str = "index.json.migrated";
+
+#[cfg(feature = "sqlite")]
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS files (
+    seq INTEGER PRIMARY KEY AUTOINCREMENT,
+    id TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    file_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    upload_date TEXT NOT NULL,
+    content TEXT NOT NULL,
+    is_context_enabled INTEGER NOT NULL,
+    summary TEXT NOT NULL,
+    priority INTEGER NOT NULL,
+    content_hash TEXT,
+    detected_type TEXT
+);
+";
+
+#[cfg(feature = "sqlite")]
+/// Columns added after the table was first created, for older databases
+const ADDED_COLUMNS: &[(&str, &str)] = &[("detected_type", "TEXT")];
+
+#[cfg(feature = "sqlite")]
+const COLUMNS: &str = "id, name, file_type, size, upload_date, content, is_context_enabled, summary, priority, content_hash, detected_type";
+
+/// The uploads index, stored as a JSON file or a SQLite database
+pub enum UploadIndex {
+    Json(PathBuf),
+    #[cfg(feature = "sqlite")]
+    Sqlite(PathBuf),
+}
+
+impl UploadIndex {
+    /// Index for `uploads_dir`. Opening the SQLite backend for the first time imports
+    /// an existing index.json.
+    pub fn open(uploads_dir: &Path, backend: IndexBackend) -> Result<Self> {
+        match backend {
+            IndexBackend::Json => Ok(Self::Json(uploads_dir.join(JSON_INDEX_NAME))),
+            #[cfg(feature = "sqlite")]
+            IndexBackend::Sqlite => {
+                let index = Self::Sqlite(uploads_dir.join(SQLITE_INDEX_NAME));
+                index.migrate_json(uploads_dir)?;
+                Ok(index)
+            }
+            #[cfg(not(feature = "sqlite"))]
+            IndexBackend::Sqlite => Err(anyhow!(
+                "config.toml: `[uploads] index_backend = \"sqlite\"` needs a build with the `sqlite` feature"
+            )),
+        }
+    }
+
+    /// Whether `path` is one of the index's own files
+    pub fn is_index_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .map(|n| n == JSON_INDEX_NAME || n == MIGRATED_JSON_NAME || n.starts_with(SQLITE_INDEX_NAME))
+            .unwrap_or(false)
+    }
+
+    /// Every entry, in upload order
+    pub fn load(&self) -> Result<Vec<FileInfo>> {
+        match self {
+            Self::Json(path) => {
+                if !path.exists() {
+                    return Ok(vec![]);
+                }
+                Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => {
+                let conn = connect(path)?;
+                let mut stmt = conn.prepare(&format!("SELECT {} FROM files ORDER BY seq", COLUMNS))?;
+                let files = stmt.query_map([], row_to_file)?.collect::<rusqlite::Result<Vec<_>>>()?;
+                Ok(files)
+            }
+        }
+    }
+
+    /// One entry by id
+    pub fn get(&self, file_id: &str) -> Result<Option<FileInfo>> {
+        match self {
+            Self::Json(_) => Ok(self.load()?.into_iter().find(|f| f.id == file_id)),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => {
+                let conn = connect(path)?;
+                let file = conn
+                    .query_row(&format!("SELECT {} FROM files WHERE id = ?1", COLUMNS), [file_id], row_to_file)
+                    .optional()?;
+                Ok(file)
+            }
+        }
+    }
+
+    /// Insert `file`, or replace the entry with the same id in place
+    pub fn upsert(&self, file: &FileInfo) -> Result<()> {
+        match self {
+            Self::Json(_) => {
+                let mut files = self.load()?;
+                match files.iter().position(|f| f.id == file.id) {
+                    Some(index) => files[index] = file.clone(),
+                    None => files.push(file.clone()),
+                }
+                self.replace_all(&files)
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => {
+                upsert_row(&connect(path)?, file)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Drop the entry for `file_id`. Returns whether there was one.
+    pub fn remove(&self, file_id: &str) -> Result<bool> {
+        match self {
+            Self::Json(_) => {
+                let mut files = self.load()?;
+                let before = files.len();
+                files.retain(|f| f.id != file_id);
+                if files.len() == before {
+                    return Ok(false);
+                }
+                self.replace_all(&files)?;
+                Ok(true)
+            }
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => Ok(connect(path)?.execute("DELETE FROM files WHERE id = ?1", [file_id])? > 0),
+        }
+    }
+
+    /// Replace the whole index with `files`
+    pub fn replace_all(&self, files: &[FileInfo]) -> Result<()> {
+        match self {
+            Self::Json(path) => write_json(path, files),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite(path) => {
+                let mut conn = connect(path)?;
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM files", [])?;
+                for file in files {
+                    upsert_row(&tx, file)?;
+                }
+                tx.commit()?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Import index.json into an empty database, then set it aside
+    #[cfg(feature = "sqlite")]
+    fn migrate_json(&self, uploads_dir: &Path) -> Result<()> {
+        let json_path = uploads_dir.join(JSON_INDEX_NAME);
+        if !json_path.exists() {
+            return Ok(());
+        }
+        let Self::Sqlite(path) = self else {
+            return Ok(());
+        };
+        let files: Vec<FileInfo> = serde_json::from_str(&fs::read_to_string(&json_path)?)
+            .map_err(|e| anyhow!("Cannot import {}: {}", json_path.display(), e))?;
+        let mut conn = connect(path)?;
+        let existing: i64 = conn.query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))?;
+        if existing == 0 {
+            let tx = conn.transaction()?;
+            for file in &files {
+                upsert_row(&tx, file)?;
+            }
+            tx.commit()?;
+            println!("[uploads] Imported {} entries from index.json into index.db", files.len());
+        }
+        fs::rename(&json_path, uploads_dir.join(MIGRATED_JSON_NAME))?;
+        Ok(())
+    }
+}
+
+/// Write index.json through a temp file so an interrupted save leaves the old index intact
+pub fn write_json(path: &Path, files: &[FileInfo]) -> Result<()> {
+    // Serialize to pretty JSON for human readability
+    let index_content = serde_json::to_string_pretty(files)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, index_content)?;
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(feature = "sqlite")]
+fn connect(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.execute_batch(SCHEMA)?;
+    let existing: Vec<String> = conn
+        .prepare("SELECT name FROM pragma_table_info('files')")?
+        .query_map([], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    for (column, sql_type) in ADDED_COLUMNS {
+        if !existing.iter().any(|c| c == column) {
+            conn.execute_batch(&format!("ALTER TABLE files ADD COLUMN {} {}", column, sql_type))?;
+        }
+    }
+    Ok(conn)
+}
+
+#[cfg(feature = "sqlite")]
+fn upsert_row(conn: &Connection, file: &FileInfo) -> rusqlite::Result<usize> {
+    conn.execute(
+        &format!(
+            "INSERT INTO files ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name, file_type = excluded.file_type, size = excluded.size,
+                upload_date = excluded.upload_date, content = excluded.content,
+                is_context_enabled = excluded.is_context_enabled, summary = excluded.summary,
+                priority = excluded.priority, content_hash = excluded.content_hash,
+                detected_type = excluded.detected_type",
+            COLUMNS
+        ),
+        params![
+            file.id,
+            file.name,
+            file.file_type,
+            file.size as i64,
+            file.upload_date,
+            file.content,
+            file.is_context_enabled,
+            file.summary,
+            file.priority,
+            file.content_hash,
+            file.detected_type,
+        ],
+    )
+}
+
+#[cfg(feature = "sqlite")]
+fn row_to_file(row: &rusqlite::Row) -> rusqlite::Result<FileInfo> {
+    Ok(FileInfo {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        file_type: row.get(2)?,
+        size: row.get::<_, i64>(3)? as u64,
+        upload_date: row.get(4)?,
+        content: row.get(5)?,
+        is_context_enabled: row.get(6)?,
+        summary: row.get(7)?,
+        priority: row.get(8)?,
+        content_hash: row.get(9)?,
+        detected_type: row.get(10)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("arkangel-index-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn file(id: &str) -> FileInfo {
+        FileInfo {
+            id: id.to_string(),
+            name: format!("{}.txt", id),
+            file_type: "txt".to_string(),
+            size: 3,
+            upload_date: "2024-01-01T00:00:00Z".to_string(),
+            content: "abc".to_string(),
+            is_context_enabled: true,
+            summary: String::new(),
+            priority: 0,
+            content_hash: None,
+            detected_type: None,
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_sqlite_insert_update_delete() {
+        let dir = temp_dir();
+        let index = UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap();
+        index.upsert(&file("a")).unwrap();
+        index.upsert(&file("b")).unwrap();
+
+        let mut toggled = file("a");
+        toggled.is_context_enabled = false;
+        index.upsert(&toggled).unwrap();
+        let files = index.load().unwrap();
+        assert_eq!(files.iter().map(|f| f.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"], "update keeps upload order");
+        assert!(!files[0].is_context_enabled);
+
+        assert!(index.remove("a").unwrap());
+        assert!(!index.remove("a").unwrap());
+        assert!(index.get("a").unwrap().is_none());
+        assert_eq!(index.get("b").unwrap().unwrap().name, "b.txt");
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_interrupted_write_keeps_prior_entries() {
+        let dir = temp_dir();
+        let index = UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap();
+        index.upsert(&file("a")).unwrap();
+        index.upsert(&file("b")).unwrap();
+
+        // A bulk rewrite that dies before committing
+        {
+            let mut conn = connect(&dir.join(SQLITE_INDEX_NAME)).unwrap();
+            let tx = conn.transaction().unwrap();
+            tx.execute("DELETE FROM files", []).unwrap();
+            upsert_row(&tx, &file("c")).unwrap();
+            drop(tx);
+        }
+        let reopened = UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap();
+        let ids: Vec<String> = reopened.load().unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ids, vec!["a", "b"]);
+
+        // Likewise a JSON save that never got to the rename
+        let json = UploadIndex::open(&dir, IndexBackend::Json).unwrap();
+        json.replace_all(&[file("a")]).unwrap();
+        fs::write(dir.join("index.json.tmp"), "[{\"truncated").unwrap();
+        assert_eq!(json.load().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[cfg(feature = "sqlite")]
+    fn test_json_index_migrated_once() {
+        let dir = temp_dir();
+        write_json(&dir.join(JSON_INDEX_NAME), &[file("a"), file("b")]).unwrap();
+
+        let index = UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap();
+        assert_eq!(index.load().unwrap().len(), 2);
+        assert!(!dir.join(JSON_INDEX_NAME).exists());
+        assert!(dir.join(MIGRATED_JSON_NAME).exists());
+
+        // Reopening doesn't import again
+        index.remove("a").unwrap();
+        let index = UploadIndex::open(&dir, IndexBackend::Sqlite).unwrap();
+        assert_eq!(index.load().unwrap().len(), 1);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    #[cfg(not(feature = "sqlite"))]
+    fn test_sqlite_backend_rejected_without_feature() {
+        let dir = temp_dir();
+        let err = UploadIndex::open(&dir, IndexBackend::Sqlite).err().unwrap().to_string();
+        assert!(err.contains("`sqlite` feature"), "{}", err);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,39 +1,268 @@
-use tauri::{Manager, App, WebviewWindow};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{Manager, App, AppHandle, Monitor, Window, WebviewWindow};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 
 // The offset from the top of the screen to the window
 const TOP_OFFSET: i32 = 54;
 
-/// Sets up the main window with custom positioning
+// Show/hide shortcut: Cmd+\ on macOS, Ctrl+\ elsewhere
+#[cfg(target_os = "macos")]
+const TOGGLE_MODIFIERS: Modifiers = Modifiers::SUPER;
+#[cfg(not(target_os = "macos"))]
+const TOGGLE_MODIFIERS: Modifiers = Modifiers::CONTROL;
+
+// Where the window's last position and size are kept, under the app data dir
+const GEOMETRY_FILE: &str = "window-state.json";
+
+// How long the window must stay put after a move or resize before its geometry is saved
+const GEOMETRY_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// A window's outer position and inner size, in physical pixels
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A screen rectangle in physical pixels
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    fn of(monitor: &Monitor) -> Self {
+        let (position, size) = (monitor.position(), monitor.size());
+        Rect { x: position.x, y: position.y, width: size.width, height: size.height }
+    }
+
+    /// The monitor minus taskbars, docks and menu bars
+    fn work_area_of(monitor: &Monitor) -> Self {
+        let area = monitor.work_area();
+        Rect { x: area.position.x, y: area.position.y, width: area.size.width, height: area.size.height }
+    }
+
+    fn contains(&self, geometry: &WindowGeometry) -> bool {
+        geometry.x >= self.x
+            && geometry.y >= self.y
+            && geometry.x as i64 + geometry.width as i64 <= self.x as i64 + self.width as i64
+            && geometry.y as i64 + geometry.height as i64 <= self.y as i64 + self.height as i64
+    }
+}
+
+/// `saved` unchanged if it lies entirely on one of `monitors`; otherwise shrunk to fit
+/// and moved inside `fallback`
+pub fn clamp_to_monitors(saved: WindowGeometry, monitors: &[Rect], fallback: Rect) -> WindowGeometry {
+    if monitors.iter().any(|monitor| monitor.contains(&saved)) {
+        return saved;
+    }
+    let width = saved.width.min(fallback.width);
+    let height = saved.height.min(fallback.height);
+    let max_x = fallback.x + (fallback.width - width) as i32;
+    let max_y = fallback.y + (fallback.height - height) as i32;
+    WindowGeometry { x: saved.x.clamp(fallback.x, max_x), y: saved.y.clamp(fallback.y, max_y), width, height }
+}
+
+fn geometry_path(app: &AppHandle) -> Option<PathBuf> {
+    app.path().app_data_dir().ok().map(|dir| dir.join(GEOMETRY_FILE))
+}
+
+fn load_geometry(path: &Path) -> Option<WindowGeometry> {
+    serde_json::from_slice(&std::fs::read(path).ok()?).ok()
+}
+
+/// Remember `window`'s position and size for the next launch
+pub fn save_geometry(window: &Window) -> Result<(), Box<dyn std::error::Error>> {
+    let path = geometry_path(window.app_handle()).ok_or("No app data dir")?;
+    let (position, size) = (window.outer_position()?, window.inner_size()?);
+    let geometry = WindowGeometry { x: position.x, y: position.y, width: size.width, height: size.height };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(&path, serde_json::to_vec(&geometry)?)?;
+    Ok(())
+}
+
+/// Save `window`'s geometry once it has stopped moving and resizing, rather than on
+/// every event of a drag
+pub fn geometry_changed(window: &Window) {
+    static SAVER: OnceLock<Mutex<Sender<Window>>> = OnceLock::new();
+    let saver = SAVER.get_or_init(|| {
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            debounce(&rx, GEOMETRY_SAVE_DELAY, |window: Window| {
+                if let Err(e) = save_geometry(&window) {
+                    eprintln!("Failed to save window position: {}", e);
+                }
+            })
+        });
+        Mutex::new(tx)
+    });
+    let _ = saver.lock().unwrap().send(window.clone());
+}
+
+/// Call `flush` with the latest value from `rx` once none has arrived for `delay`.
+/// Returns when the channel closes, flushing anything still pending.
+fn debounce<T>(rx: &Receiver<T>, delay: Duration, mut flush: impl FnMut(T)) {
+    while let Ok(mut latest) = rx.recv() {
+        loop {
+            match rx.recv_timeout(delay) {
+                Ok(newer) => latest = newer,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return flush(latest),
+            }
+        }
+        flush(latest);
+    }
+}
+
+/// Put the window back where it was last time, kept on screen
+fn restore_geometry(window: &WebviewWindow, saved: WindowGeometry) -> Result<bool, Box<dyn std::error::Error>> {
+    let monitors: Vec<Rect> = window.available_monitors()?.iter().map(Rect::of).collect();
+    let fallback = match window.primary_monitor()? {
+        Some(monitor) => Rect::of(&monitor),
+        None => match monitors.first() {
+            Some(first) => *first,
+            None => return Ok(false),
+        },
+    };
+    let geometry = clamp_to_monitors(saved, &monitors, fallback);
+    window.set_size(tauri::Size::Physical(tauri::PhysicalSize { width: geometry.width, height: geometry.height }))?;
+    window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x: geometry.x, y: geometry.y }))?;
+    Ok(true)
+}
+
+/// Sets up the main window, restoring its last position or placing it top-center
 pub fn setup_main_window(app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
+    let window = main_window(app.handle()).ok_or("No window found")?;
+    
+    let saved = geometry_path(app.handle()).and_then(|path| load_geometry(&path));
+    let restored = match saved {
+        Some(saved) => restore_geometry(&window, saved).unwrap_or_else(|e| {
+            eprintln!("Failed to restore window position: {}", e);
+            false
+        }),
+        None => false,
+    };
+    if !restored {
+        position_window_top_center(&window, TOP_OFFSET)?;
+    }
+    
+    Ok(())
+}
+
+/// The app's main window
+fn main_window(app: &AppHandle) -> Option<WebviewWindow> {
     // Try different possible window labels
-    let window = app.get_webview_window("main")
+    app.get_webview_window("main")
         .or_else(|| app.get_webview_window("pluely"))
         .or_else(|| {
             // Get the first window if specific labels don't work
             app.webview_windows().values().next().cloned()
         })
-        .ok_or("No window found")?;
-    
-    position_window_top_center(&window, TOP_OFFSET)?;
-    
+}
+
+/// Register the global show/hide shortcut for the main window
+pub fn register_toggle_shortcut(app: &mut App) -> Result<(), Box<dyn std::error::Error>> {
+    let toggle = Shortcut::new(Some(TOGGLE_MODIFIERS), Code::Backslash);
+    app.handle().plugin(
+        tauri_plugin_global_shortcut::Builder::new()
+            .with_handler(move |app, shortcut, event| {
+                if shortcut != &toggle || event.state() != ShortcutState::Pressed {
+                    return;
+                }
+                if let Some(window) = main_window(app) {
+                    if let Err(e) = toggle_visibility(&window) {
+                        eprintln!("Failed to toggle window visibility: {}", e);
+                    }
+                }
+            })
+            .build(),
+    )?;
+    app.global_shortcut().register(toggle)?;
     Ok(())
 }
 
-/// Positions a window at the top center of the screen with a specified Y offset
+/// The window operations `toggle_visibility` needs, so the toggle can be tested without a webview
+pub trait ToggleWindow {
+    fn is_visible(&self) -> Result<bool, Box<dyn std::error::Error>>;
+    fn hide(&self) -> Result<(), Box<dyn std::error::Error>>;
+    /// Move back to the top-center position, then show and focus
+    fn show_top_center(&self) -> Result<(), Box<dyn std::error::Error>>;
+}
+
+impl ToggleWindow for WebviewWindow {
+    fn is_visible(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(WebviewWindow::is_visible(self)?)
+    }
+
+    fn hide(&self) -> Result<(), Box<dyn std::error::Error>> {
+        Ok(WebviewWindow::hide(self)?)
+    }
+
+    fn show_top_center(&self) -> Result<(), Box<dyn std::error::Error>> {
+        position_window_top_center(self, TOP_OFFSET)?;
+        self.show()?;
+        self.set_focus()?;
+        Ok(())
+    }
+}
+
+/// Hide the window if it is showing, otherwise bring it back at top-center.
+/// Returns whether the window is now visible.
+pub fn toggle_visibility(window: &impl ToggleWindow) -> Result<bool, Box<dyn std::error::Error>> {
+    if window.is_visible()? {
+        window.hide()?;
+        Ok(false)
+    } else {
+        window.show_top_center()?;
+        Ok(true)
+    }
+}
+
+/// The work area of the monitor under `cursor`, or `fallback` if the cursor is on none of them
+pub fn target_work_area(work_areas: &[Rect], cursor: Option<(f64, f64)>, fallback: Option<Rect>) -> Option<Rect> {
+    cursor
+        .and_then(|(x, y)| {
+            work_areas.iter().copied().find(|area| {
+                x >= area.x as f64
+                    && y >= area.y as f64
+                    && x < area.x as f64 + area.width as f64
+                    && y < area.y as f64 + area.height as f64
+            })
+        })
+        .or(fallback)
+}
+
+/// Position that centers a `window_width` wide window horizontally in `area`, `y_offset` below its top
+fn top_center_in(area: Rect, window_width: u32, y_offset: i32) -> (i32, i32) {
+    (area.x + (area.width as i32 - window_width as i32) / 2, area.y + y_offset)
+}
+
+/// Positions a window at the top center of the monitor under the cursor (falling back to
+/// the window's own monitor, then the primary one) with a specified Y offset
 pub fn position_window_top_center(window: &WebviewWindow, y_offset: i32) -> Result<(), Box<dyn std::error::Error>> {
-    // Get the primary monitor
-    if let Some(monitor) = window.primary_monitor()? {
-        let monitor_size = monitor.size();
+    let work_areas: Vec<Rect> = window.available_monitors()?.iter().map(Rect::work_area_of).collect();
+    let cursor = window.cursor_position().ok().map(|p| (p.x, p.y));
+    let fallback = match window.current_monitor()? {
+        Some(monitor) => Some(monitor),
+        None => window.primary_monitor()?,
+    };
+    if let Some(area) = target_work_area(&work_areas, cursor, fallback.as_ref().map(Rect::work_area_of)) {
         let window_size = window.outer_size()?;
-        
-        // Calculate center X position
-        let center_x = (monitor_size.width as i32 - window_size.width as i32) / 2;
+        let (x, y) = top_center_in(area, window_size.width, y_offset);
         
         // Set the window position
-        window.set_position(tauri::Position::Physical(tauri::PhysicalPosition {
-            x: center_x,
-            y: y_offset,
-        }))?;
+        window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }))?;
     }
     
     Ok(())
@@ -58,9 +287,123 @@ pub fn center_window_completely(window: &WebviewWindow) -> Result<(), Box<dyn st
     Ok(())
 }
 
+/// Resize `window` to `size` where it stands: the left edge and top stay put, so a
+/// restored or user-chosen position survives content-driven resizes
+pub fn resize_in_place(window: &WebviewWindow, size: tauri::Size) -> Result<(), Box<dyn std::error::Error>> {
+    let position = window.outer_position()?;
+    window.set_size(size)?;
+    window.set_position(tauri::Position::Physical(position))?;
+    Ok(())
+}
+
 /// Future function for positioning window at custom coordinates
 #[allow(dead_code)]
 pub fn position_window_at(window: &WebviewWindow, x: i32, y: i32) -> Result<(), Box<dyn std::error::Error>> {
     window.set_position(tauri::Position::Physical(tauri::PhysicalPosition { x, y }))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[derive(Default)]
+    struct MockWindow {
+        visible: Cell<bool>,
+        repositioned: Cell<usize>,
+    }
+
+    impl ToggleWindow for MockWindow {
+        fn is_visible(&self) -> Result<bool, Box<dyn std::error::Error>> {
+            Ok(self.visible.get())
+        }
+
+        fn hide(&self) -> Result<(), Box<dyn std::error::Error>> {
+            self.visible.set(false);
+            Ok(())
+        }
+
+        fn show_top_center(&self) -> Result<(), Box<dyn std::error::Error>> {
+            self.repositioned.set(self.repositioned.get() + 1);
+            self.visible.set(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_offscreen_geometry_clamped_to_monitor() {
+        let primary = Rect { x: 0, y: 0, width: 1920, height: 1080 };
+        let secondary = Rect { x: 1920, y: 0, width: 1280, height: 1024 };
+        let monitors = [primary, secondary];
+
+        // Fully on the secondary monitor: kept as saved
+        let on_secondary = WindowGeometry { x: 2000, y: 100, width: 700, height: 400 };
+        assert_eq!(clamp_to_monitors(on_secondary, &monitors, primary), on_secondary);
+
+        // Saved on a monitor that has since been unplugged
+        let gone = WindowGeometry { x: 3500, y: 1200, width: 700, height: 400 };
+        assert_eq!(clamp_to_monitors(gone, &[primary], primary), WindowGeometry { x: 1220, y: 680, width: 700, height: 400 });
+
+        // Partly above and left of the screen, and taller than it
+        let hanging = WindowGeometry { x: -300, y: -50, width: 700, height: 1500 };
+        assert_eq!(clamp_to_monitors(hanging, &monitors, primary), WindowGeometry { x: 0, y: 0, width: 700, height: 1080 });
+    }
+
+    #[test]
+    fn test_geometry_saves_wait_for_the_window_to_settle() {
+        let (tx, rx) = channel();
+        let (saved_tx, saved_rx) = channel();
+        let saver = std::thread::spawn(move || debounce(&rx, Duration::from_millis(200), |v| saved_tx.send(v).unwrap()));
+
+        // A drag: a burst of moves, of which only the last is written
+        for x in 0..10 {
+            tx.send(x).unwrap();
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert!(saved_rx.recv_timeout(Duration::from_millis(100)).is_err(), "saved mid-drag");
+        assert_eq!(saved_rx.recv_timeout(Duration::from_secs(2)).unwrap(), 9);
+
+        // A move right before quitting is still written
+        tx.send(42).unwrap();
+        drop(tx);
+        saver.join().unwrap();
+        assert_eq!(saved_rx.try_iter().collect::<Vec<_>>(), [42]);
+    }
+
+    #[test]
+    fn test_target_work_area_follows_cursor() {
+        let primary = Rect { x: 0, y: 25, width: 1920, height: 1055 };
+        let secondary = Rect { x: 1920, y: 0, width: 1280, height: 984 };
+        let left = Rect { x: -2560, y: -200, width: 2560, height: 1400 };
+        let areas = [primary, secondary, left];
+
+        assert_eq!(target_work_area(&areas, Some((2500.0, 400.0)), Some(primary)), Some(secondary));
+        assert_eq!(target_work_area(&areas, Some((1920.0, 0.0)), Some(primary)), Some(secondary), "left edge belongs to the secondary");
+        assert_eq!(target_work_area(&areas, Some((-10.5, -150.0)), Some(primary)), Some(left));
+        assert_eq!(target_work_area(&areas, Some((100.0, 500.0)), Some(secondary)), Some(primary));
+        assert_eq!(target_work_area(&areas, Some((9000.0, 0.0)), Some(primary)), Some(primary), "off every monitor");
+        assert_eq!(target_work_area(&areas, None, Some(secondary)), Some(secondary));
+        assert_eq!(target_work_area(&[], None, None), None);
+
+        assert_eq!(top_center_in(secondary, 700, 54), (2210, 54));
+        assert_eq!(top_center_in(left, 700, 54), (-1630, -146));
+    }
+
+    #[test]
+    fn test_toggle_visibility_alternates_and_recenters_on_show() {
+        let window = MockWindow { visible: Cell::new(true), ..MockWindow::default() };
+
+        assert!(!toggle_visibility(&window).unwrap());
+        assert!(!window.visible.get());
+        assert_eq!(window.repositioned.get(), 0, "hiding leaves the position alone");
+
+        assert!(toggle_visibility(&window).unwrap());
+        assert!(window.visible.get());
+        assert_eq!(window.repositioned.get(), 1);
+
+        assert!(!toggle_visibility(&window).unwrap());
+        assert!(toggle_visibility(&window).unwrap());
+        assert_eq!(window.repositioned.get(), 2);
+    }
+}
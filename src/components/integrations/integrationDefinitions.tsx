@@ -40,8 +40,11 @@ export const createGoogleSuiteIntegration = (): Integration => ({
   connectMessage: null,
   connect: async () => {
     try {
-      await invoke<string>("connect_google_suite");
-      console.log('[Integrations][Google] Connect successful');
+      const result = await invoke<{ message: string; granted_scopes: string[]; missing_scopes: string[] }>("connect_google_suite");
+      console.log('[Integrations][Google] Connect successful:', result.message);
+      if (result.missing_scopes.length > 0) {
+        console.warn('[Integrations][Google] Scopes not granted:', result.missing_scopes);
+      }
     } catch (e: any) {
       console.error('[Integrations][Google] Connect error:', e);
       throw e;
@@ -61,8 +64,8 @@ export const createGoogleSuiteIntegration = (): Integration => ({
 // Helper function to check Google connection status
 export const checkGoogleConnectionStatus = async (): Promise<boolean> => {
   try {
-    const connected = await invoke<boolean>("is_google_connected");
-    return Boolean(connected);
+    const accounts = await invoke<string[]>("is_google_connected");
+    return accounts.length > 0;
   } catch (err) {
     console.error('[Integrations][Google] Failed to check connection status:', err);
     return false;
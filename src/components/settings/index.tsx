@@ -39,7 +39,7 @@ export const Settings: React.FC<SettingsProps> = ({ onOpenIntegrations }) => {
     const check = async () => {
       try {
         console.log('[Settings][Google] Checking connection status...')
-        const connected = await invoke<boolean>("is_google_connected");
+        const connected = await invoke<string[]>("is_google_connected");
         console.log('[Settings][Google] Status response:', connected)
         // Note: Google connection status is now handled in the Integrations component
       } catch (err) {